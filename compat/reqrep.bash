@@ -9,7 +9,7 @@ source "$COMPAT_PATH/test_helper.bash"
 function testcase_reqrep1 {
     URL=$1
     $EXAMPLE_PATH/reqrep node0 $URL > /tmp/reqrep_tc1_node0.log & node0=$!
-    nanocat --req --connect $URL --data "DATE" --ascii -i 2 > /tmp/reqrep_tc1_nanocat.log & ncat=$!
+    $NANOCAT --req --connect $URL --data "DATE" --ascii -i 2 > /tmp/reqrep_tc1_nanocat.log & ncat=$!
     sleep 0.5 && kill $ncat $node0
     result_node0=`cat /tmp/reqrep_tc1_node0.log`
     expected_node0=`cat $COMPAT_PATH/reqrep_tc1_node0_expected.log`
@@ -28,7 +28,7 @@ function testcase_reqrep1 {
 function testcase_reqrep2 {
     URL=$1
     msg="pulvonium"
-    nanocat --rep --bind $URL --ascii --data $msg > /tmp/reqrep_tc2_nanocat.log -i 2 & ncat=$!
+    $NANOCAT --rep --bind $URL --ascii --data $msg > /tmp/reqrep_tc2_nanocat.log -i 2 & ncat=$!
     ./target/debug/examples/reqrep node1 $URL > /tmp/reqrep_tc2_node1.log & node1=$!
     sleep 0.5 && kill $ncat $node1
     result_node1=`cat /tmp/reqrep_tc2_node1.log`
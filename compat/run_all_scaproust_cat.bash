@@ -0,0 +1,5 @@
+#!/bin/bash
+
+COMPAT_PATH="$( cd "$(dirname "$0")" ; pwd -P )"
+
+NANOCAT="$COMPAT_PATH/../target/debug/scaproust-cat" $COMPAT_PATH/run_all.bash
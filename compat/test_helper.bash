@@ -2,6 +2,9 @@
 
 EXAMPLE_PATH="$( cd "$COMPAT_PATH/../target/debug/examples" ; pwd -P )"
 
+# The C implementation is the reference, run_all_scaproust_cat.bash sets NANOCAT to check scaproust-cat
+NANOCAT="${NANOCAT:-nanocat}"
+
 NO_COLOR='\033[0m' 
 RED_COLOR='\033[0;31m'
 GREEN_COLOR='\033[0;32m'
@@ -13,5 +16,5 @@ function echo_test_case_succeeded {
 function echo_test_case_failed {
     echo -e "$1 ${RED_COLOR}FAILED !${NO_COLOR}" 
     ps auxwww | grep ${EXAMPLE_PATH} | grep -v grep
-    ps auxwww | grep nanocat | grep -v grep
+    ps auxwww | grep $(basename ${NANOCAT}) | grep -v grep
 }
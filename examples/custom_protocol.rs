@@ -0,0 +1,217 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+//
+// This example implements a protocol outside of scaproust.
+// A beacon socket broadcasts the last message it was given to all its peers,
+// and repeats it periodically so that late joiners receive it too.
+
+extern crate env_logger;
+extern crate scaproust;
+
+use std::collections::HashSet;
+use std::io;
+use std::rc::Rc;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+use scaproust::*;
+use scaproust::core::context::{Context, Schedulable};
+use scaproust::core::endpoint::Pipe;
+use scaproust::core::socket::{Protocol, Reply};
+use scaproust::core::Scheduled;
+use scaproust::proto::Timeout;
+use scaproust::proto::pipes::PipeCollection;
+use scaproust::proto::priolist::Priolist;
+use scaproust::proto::policy::{broadcast, fair_queue};
+
+const BEACON: u16 = 0x7000;
+const REPEAT: u32 = 1;
+
+struct Beacon {
+    reply_tx: Sender<Reply>,
+    pipes: PipeCollection,
+    bc: HashSet<EndpointId>,
+    fq: Priolist,
+    last: Option<Rc<Message>>,
+    period: Duration,
+    repeat: Option<Scheduled>,
+    pending_recv: Option<(Option<EndpointId>, Timeout)>
+}
+
+impl From<Sender<Reply>> for Beacon {
+    fn from(tx: Sender<Reply>) -> Beacon {
+        Beacon {
+            reply_tx: tx,
+            pipes: PipeCollection::new(),
+            bc: HashSet::new(),
+            fq: Priolist::new(),
+            last: None,
+            period: Duration::from_millis(100),
+            repeat: None,
+            pending_recv: None
+        }
+    }
+}
+
+impl Beacon {
+    fn broadcast(&mut self, ctx: &mut Context) {
+        if let Some(ref msg) = self.last {
+            broadcast::send_to_all(&mut self.bc, &mut self.pipes, ctx, msg.clone());
+        }
+    }
+
+    fn start_recv(&mut self, ctx: &mut Context, timeout: Timeout) {
+        let eid = fair_queue::recv(&mut self.fq, &mut self.pipes, ctx);
+
+        self.pending_recv = Some((eid, timeout));
+    }
+}
+
+impl Protocol for Beacon {
+    fn id(&self)      -> u16 { BEACON }
+    fn peer_id(&self) -> u16 { BEACON }
+
+    fn add_pipe(&mut self, _: &mut Context, eid: EndpointId, pipe: Pipe) {
+        self.fq.insert(eid, pipe.get_recv_priority());
+        self.pipes.insert(eid, pipe);
+    }
+    fn remove_pipe(&mut self, ctx: &mut Context, eid: EndpointId) -> Option<Pipe> {
+        let was_recv_ready = self.is_recv_ready();
+
+        self.bc.remove(&eid);
+        self.fq.remove(&eid);
+
+        if let Some((Some(id), timeout)) = self.pending_recv.take() {
+            if id == eid {
+                self.start_recv(ctx, timeout);
+            } else {
+                self.pending_recv = Some((Some(id), timeout));
+            }
+        }
+
+        let is_recv_ready = self.is_recv_ready();
+        ctx.check_recv_ready_change(was_recv_ready, is_recv_ready);
+
+        self.pipes.remove(&eid)
+    }
+
+    // Sending never waits: the message replaces the current beacon,
+    // is broadcast to the ready pipes and will be repeated by the timer.
+    fn send(&mut self, ctx: &mut Context, msg: Message, timeout: Timeout) {
+        self.last = Some(Rc::new(msg));
+        self.broadcast(ctx);
+
+        if self.repeat.is_none() {
+            self.repeat = ctx.schedule(Schedulable::Custom(REPEAT), self.period).ok();
+        }
+
+        let _ = self.reply_tx.send(Reply::Send);
+        if let Some(sched) = timeout {
+            ctx.cancel(sched);
+        }
+    }
+    fn on_send_ack(&mut self, _: &mut Context, _: EndpointId) {
+    }
+    fn on_send_timeout(&mut self, _: &mut Context) {
+    }
+    fn on_send_ready(&mut self, _: &mut Context, eid: EndpointId) {
+        self.bc.insert(eid);
+    }
+    fn on_send_not_ready(&mut self, _: &mut Context, eid: EndpointId) {
+        self.bc.remove(&eid);
+    }
+
+    fn recv(&mut self, ctx: &mut Context, timeout: Timeout) {
+        let was_recv_ready = self.is_recv_ready();
+
+        self.start_recv(ctx, timeout);
+
+        let is_recv_ready = self.is_recv_ready();
+        ctx.check_recv_ready_change(was_recv_ready, is_recv_ready);
+    }
+    fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, msg: Message) {
+        if let Some((Some(id), timeout)) = self.pending_recv.take() {
+            if id == eid {
+                let _ = self.reply_tx.send(Reply::Recv(msg));
+                if let Some(sched) = timeout {
+                    ctx.cancel(sched);
+                }
+            } else {
+                self.pending_recv = Some((Some(id), timeout));
+            }
+        }
+    }
+    fn on_recv_timeout(&mut self, _: &mut Context) {
+        self.pending_recv = None;
+
+        let error = io::Error::new(io::ErrorKind::TimedOut, "Recv timed out");
+        let _ = self.reply_tx.send(Reply::Err(error));
+    }
+    fn on_recv_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
+        let was_recv_ready = self.is_recv_ready();
+
+        self.fq.activate(&eid);
+
+        if let Some((None, timeout)) = self.pending_recv.take() {
+            self.start_recv(ctx, timeout);
+        }
+
+        let is_recv_ready = self.is_recv_ready();
+        ctx.check_recv_ready_change(was_recv_ready, is_recv_ready);
+    }
+    fn on_recv_not_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
+        let was_recv_ready = self.is_recv_ready();
+
+        self.fq.deactivate(&eid);
+
+        let is_recv_ready = self.is_recv_ready();
+        ctx.check_recv_ready_change(was_recv_ready, is_recv_ready);
+    }
+
+    fn is_send_ready(&self) -> bool {
+        true
+    }
+    fn is_recv_ready(&self) -> bool {
+        self.pending_recv.is_none() && self.fq.peek()
+    }
+
+    fn on_timer_tick(&mut self, ctx: &mut Context, task: Schedulable) {
+        if let Schedulable::Custom(REPEAT) = task {
+            self.broadcast(ctx);
+            self.repeat = ctx.schedule(Schedulable::Custom(REPEAT), self.period).ok();
+        }
+    }
+    fn close(&mut self, ctx: &mut Context) {
+        if let Some(sched) = self.repeat.take() {
+            ctx.cancel(sched);
+        }
+        self.pipes.close_all(ctx);
+    }
+}
+
+fn main() {
+    env_logger::init().unwrap();
+
+    let url = "tcp://127.0.0.1:5480";
+    let mut session = SessionBuilder::new().with("tcp", Tcp).build().expect("Failed to create session !");
+    let mut lighthouse = session.create_socket::<Beacon>().expect("Failed to create socket !");
+    let mut ship = session.create_socket::<Beacon>().expect("Failed to create socket !");
+
+    lighthouse.bind(url).expect("Failed to bind socket !");
+    lighthouse.send(b"This is the lighthouse".to_vec()).expect("Send failed !");
+    println!("LIGHTHOUSE: BEACON SENT");
+
+    // The ship connects after the beacon was sent, it will get the next repetition.
+    thread::sleep(Duration::from_millis(250));
+    ship.set_recv_timeout(Some(Duration::from_secs(1))).expect("Failed to set recv timeout !");
+    ship.connect(url).expect("Failed to connect socket !");
+
+    let buffer = ship.recv().expect("Recv failed !");
+    let msg = std::str::from_utf8(&buffer).expect("Failed to parse msg !");
+
+    println!("SHIP: RECEIVED \"{}\"", msg);
+}
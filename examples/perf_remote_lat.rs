@@ -50,18 +50,29 @@ fn main() {
 
     let buffer = vec![6; msg_size];
     let mut msg = Message::from_body(buffer);
+    let mut roundtrip_micros = Vec::with_capacity(roundtrips);
     let start = Instant::now();
     for _ in 0..roundtrips {
+        let roundtrip_start = Instant::now();
         socket.send_msg(msg).unwrap();
         msg = socket.recv_msg().unwrap();
+        roundtrip_micros.push(micros(roundtrip_start.elapsed()));
     }
-    let elapsed  = start.elapsed();
-    let seconds = elapsed.as_secs() as f64;
-    let nanos = elapsed.subsec_nanos() as f64;
-    let elapsed_micros = (seconds * 1_000_000f64) + nanos / 1_000f64;
+    let elapsed_micros = micros(start.elapsed());
     let latency = elapsed_micros / (roundtrips * 2) as f64;
 
+    roundtrip_micros.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let median_latency = roundtrip_micros[roundtrips / 2] / 2f64;
+
     println!("message size: {} [B]", msg_size);
     println!("roundtrip count: {}", roundtrips);
     println!("average latency: {:0.3} [us]", latency);
+    println!("median latency: {:0.3} [us]", median_latency);
+}
+
+fn micros(duration: Duration) -> f64 {
+    let seconds = duration.as_secs() as f64;
+    let nanos = duration.subsec_nanos() as f64;
+
+    (seconds * 1_000_000f64) + nanos / 1_000f64
 }
@@ -0,0 +1,7 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate scaproust;
+
+fuzz_target!(|data: &[u8]| {
+    scaproust::testing::fuzzing::handshake(data);
+});
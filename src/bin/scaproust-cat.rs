@@ -0,0 +1,636 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+//
+// A command line interface to scaproust sockets, accepting the same arguments as nanocat.
+// See http://nanomsg.org/v1.0.0/nanocat.html
+
+extern crate scaproust;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use scaproust::*;
+
+const USAGE: &'static str = "Usage:
+    scaproust-cat (--push|--pull|--pub|--sub|--req|--rep|--surveyor|--respondent|--bus|--pair)
+                  [--bind URL]... [--connect URL]... [--bind-local PORT]... [--connect-local PORT]...
+                  [--recv-timeout SEC] [--send-timeout SEC] [--subscribe PREFIX]...
+                  [--data DATA|--file PATH] [--interval SEC] [--delay SEC]
+                  [--raw|--ascii|--quoted|--msgpack|--hex]
+
+Socket types:
+    --push, --pull, --pub, --sub, --req, --rep, --surveyor, --respondent, --bus, --pair
+
+Endpoints:
+    --bind URL, -b URL            Bind the socket to URL
+    --connect URL, -c URL         Connect the socket to URL
+    --bind-local PORT, -L PORT    Bind the socket to tcp://127.0.0.1:PORT
+    --connect-local PORT, -l PORT Connect the socket to tcp://127.0.0.1:PORT
+
+Options:
+    --recv-timeout SEC            Stop receiving after SEC seconds without a message
+    --send-timeout SEC            Give up sending after SEC seconds
+    --subscribe PREFIX            Subscribe to PREFIX, default is to subscribe to everything
+
+Sending:
+    --data DATA, -D DATA          Send DATA
+    --file PATH, -F PATH          Send the content of the file at PATH
+    --interval SEC, -i SEC        Send the message again every SEC seconds
+    --delay SEC, -d SEC           Wait SEC seconds before sending the first message
+
+Printing received messages:
+    --raw                         Print the message as is, without delimiter
+    --ascii, -A                   Print the ascii part of the message, one message per line
+    --quoted, -Q                  Print the message in double quotes, with C-like escaping
+    --msgpack                     Print the message as a msgpack binary string
+    --hex                         Print the message as a quoted string of hex escapes
+";
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum SocketType {
+    Push, Pull, Pub, Sub, Req, Rep, Surveyor, Respondent, Bus, Pair
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Format {
+    NoEcho, Raw, Ascii, Quoted, Msgpack, Hex
+}
+
+enum Endpoint {
+    Bind(String),
+    Connect(String)
+}
+
+struct Options {
+    socket_type: Option<SocketType>,
+    endpoints: Vec<Endpoint>,
+    recv_timeout: Option<Duration>,
+    send_timeout: Option<Duration>,
+    subscriptions: Vec<String>,
+    data: Option<Vec<u8>>,
+    interval: Option<Duration>,
+    delay: Option<Duration>,
+    format: Format
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* command line parsing                                                      */
+/*                                                                           */
+/*****************************************************************************/
+
+// Long options can be abbreviated as long as there is no ambiguity, as in nanocat.
+const LONG_OPTIONS: &'static [&'static str] = &[
+    "push", "pull", "pub", "sub", "req", "rep", "surveyor", "respondent", "bus", "pair",
+    "bind", "connect", "bind-local", "connect-local",
+    "recv-timeout", "send-timeout", "subscribe",
+    "data", "file", "interval", "delay",
+    "raw", "ascii", "quoted", "msgpack", "hex",
+    "help"
+];
+
+fn resolve_long_option(arg: &str) -> Result<&'static str, String> {
+    if let Some(name) = LONG_OPTIONS.iter().find(|name| **name == arg) {
+        return Ok(name);
+    }
+
+    let candidates: Vec<&'static str> = LONG_OPTIONS.iter().
+        filter(|name| name.starts_with(arg)).
+        map(|name| *name).
+        collect();
+
+    match candidates.len() {
+        1 => Ok(candidates[0]),
+        0 => Err(format!("Unknown option --{}", arg)),
+        _ => Err(format!("Ambiguous option --{}, could be any of {}", arg, candidates.join(", ")))
+    }
+}
+
+fn resolve_short_option(arg: &str) -> Result<&'static str, String> {
+    match arg {
+        "b" => Ok("bind"),
+        "c" => Ok("connect"),
+        "L" => Ok("bind-local"),
+        "l" => Ok("connect-local"),
+        "D" => Ok("data"),
+        "F" => Ok("file"),
+        "i" => Ok("interval"),
+        "d" => Ok("delay"),
+        "A" => Ok("ascii"),
+        "Q" => Ok("quoted"),
+        "h" => Ok("help"),
+        _   => Err(format!("Unknown option -{}", arg))
+    }
+}
+
+fn takes_value(name: &str) -> bool {
+    match name {
+        "bind" | "connect" | "bind-local" | "connect-local" |
+        "recv-timeout" | "send-timeout" | "subscribe" |
+        "data" | "file" | "interval" | "delay" => true,
+        _ => false
+    }
+}
+
+fn parse_seconds(name: &str, value: &str) -> Result<Duration, String> {
+    match value.parse::<f64>() {
+        Ok(secs) if secs >= 0.0 => {
+            let millis = (secs * 1000.0).round() as u64;
+
+            Ok(Duration::from_millis(millis))
+        },
+        _ => Err(format!("Invalid value for --{}: {}", name, value))
+    }
+}
+
+fn parse_port(name: &str, value: &str) -> Result<u16, String> {
+    value.parse::<u16>().map_err(|_| format!("Invalid value for --{}: {}", name, value))
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>, String> {
+    let mut buffer = Vec::new();
+
+    File::open(path).
+        and_then(|mut file| file.read_to_end(&mut buffer)).
+        map(|_| buffer).
+        map_err(|e| format!("Failed to read {}: {}", path, e))
+}
+
+impl Options {
+    fn new() -> Options {
+        Options {
+            socket_type: None,
+            endpoints: Vec::new(),
+            recv_timeout: None,
+            send_timeout: None,
+            subscriptions: Vec::new(),
+            data: None,
+            interval: None,
+            delay: None,
+            format: Format::NoEcho
+        }
+    }
+
+    fn parse<I : Iterator<Item=String>>(mut args: I) -> Result<Options, String> {
+        let mut options = Options::new();
+
+        while let Some(arg) = args.next() {
+            let name = if arg.starts_with("--") {
+                try!(resolve_long_option(&arg[2..]))
+            } else if arg.starts_with('-') && arg.len() > 1 {
+                try!(resolve_short_option(&arg[1..]))
+            } else {
+                return Err(format!("Unexpected argument {}", arg));
+            };
+
+            let value = if takes_value(name) {
+                match args.next() {
+                    Some(value) => value,
+                    None => return Err(format!("Option --{} requires a value", name))
+                }
+            } else {
+                String::new()
+            };
+
+            try!(options.apply(name, value));
+        }
+
+        options.check().map(|_| options)
+    }
+
+    fn apply(&mut self, name: &str, value: String) -> Result<(), String> {
+        match name {
+            "push"          => self.set_socket_type(SocketType::Push),
+            "pull"          => self.set_socket_type(SocketType::Pull),
+            "pub"           => self.set_socket_type(SocketType::Pub),
+            "sub"           => self.set_socket_type(SocketType::Sub),
+            "req"           => self.set_socket_type(SocketType::Req),
+            "rep"           => self.set_socket_type(SocketType::Rep),
+            "surveyor"      => self.set_socket_type(SocketType::Surveyor),
+            "respondent"    => self.set_socket_type(SocketType::Respondent),
+            "bus"           => self.set_socket_type(SocketType::Bus),
+            "pair"          => self.set_socket_type(SocketType::Pair),
+            "bind"          => Ok(self.endpoints.push(Endpoint::Bind(value))),
+            "connect"       => Ok(self.endpoints.push(Endpoint::Connect(value))),
+            "bind-local"    => parse_port(name, &value).map(|port| self.endpoints.push(Endpoint::Bind(local_url(port)))),
+            "connect-local" => parse_port(name, &value).map(|port| self.endpoints.push(Endpoint::Connect(local_url(port)))),
+            "recv-timeout"  => parse_seconds(name, &value).map(|timeout| self.recv_timeout = Some(timeout)),
+            "send-timeout"  => parse_seconds(name, &value).map(|timeout| self.send_timeout = Some(timeout)),
+            "subscribe"     => Ok(self.subscriptions.push(value)),
+            "data"          => self.set_data(value.into_bytes()),
+            "file"          => read_file(&value).and_then(|data| self.set_data(data)),
+            "interval"      => parse_seconds(name, &value).map(|ivl| self.interval = Some(ivl)),
+            "delay"         => parse_seconds(name, &value).map(|delay| self.delay = Some(delay)),
+            "raw"           => self.set_format(Format::Raw),
+            "ascii"         => self.set_format(Format::Ascii),
+            "quoted"        => self.set_format(Format::Quoted),
+            "msgpack"       => self.set_format(Format::Msgpack),
+            "hex"           => self.set_format(Format::Hex),
+            _               => Err(String::new())
+        }
+    }
+
+    fn set_socket_type(&mut self, socket_type: SocketType) -> Result<(), String> {
+        if self.socket_type.is_some() {
+            return Err("Only one socket type can be specified".to_owned());
+        }
+
+        self.socket_type = Some(socket_type);
+        Ok(())
+    }
+
+    fn set_data(&mut self, data: Vec<u8>) -> Result<(), String> {
+        if self.data.is_some() {
+            return Err("Only one of --data and --file can be specified".to_owned());
+        }
+
+        self.data = Some(data);
+        Ok(())
+    }
+
+    fn set_format(&mut self, format: Format) -> Result<(), String> {
+        if self.format != Format::NoEcho {
+            return Err("Only one output format can be specified".to_owned());
+        }
+
+        self.format = format;
+        Ok(())
+    }
+
+    fn check(&self) -> Result<(), String> {
+        let socket_type = match self.socket_type {
+            Some(socket_type) => socket_type,
+            None => return Err("A socket type must be specified".to_owned())
+        };
+
+        if self.endpoints.is_empty() {
+            return Err("At least one --bind or --connect must be specified".to_owned());
+        }
+
+        let needs_data = match socket_type {
+            SocketType::Push | SocketType::Pub | SocketType::Req | SocketType::Surveyor => true,
+            _ => false
+        };
+
+        if needs_data && self.data.is_none() {
+            return Err("This socket type requires --data or --file".to_owned());
+        }
+
+        let can_recv = match socket_type {
+            SocketType::Push | SocketType::Pub => false,
+            _ => true
+        };
+
+        if !can_recv && self.format != Format::NoEcho {
+            return Err("This socket type cannot receive messages to print".to_owned());
+        }
+
+        if socket_type != SocketType::Sub && !self.subscriptions.is_empty() {
+            return Err("--subscribe is only valid for --sub sockets".to_owned());
+        }
+
+        Ok(())
+    }
+}
+
+fn local_url(port: u16) -> String {
+    format!("tcp://127.0.0.1:{}", port)
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* printing received messages                                                */
+/*                                                                           */
+/*****************************************************************************/
+
+fn format_msg(format: Format, msg: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(msg.len() + 8);
+
+    match format {
+        Format::NoEcho => {},
+        Format::Raw => out.extend_from_slice(msg),
+        Format::Ascii => {
+            for &b in msg {
+                out.push(if is_print(b) { b } else { b'.' });
+            }
+            out.push(b'\n');
+        },
+        Format::Quoted => {
+            out.push(b'"');
+            for &b in msg {
+                match b {
+                    b'"' | b'\\' => { out.push(b'\\'); out.push(b); },
+                    b if is_print(b) => out.push(b),
+                    b => out.extend_from_slice(format!("\\x{:02x}", b).as_bytes())
+                }
+            }
+            out.extend_from_slice(b"\"\n");
+        },
+        Format::Msgpack => {
+            let len = msg.len();
+
+            if len < 256 {
+                out.push(0xC4);
+                out.push(len as u8);
+            } else if len < 65_536 {
+                out.push(0xC5);
+                out.push((len >> 8) as u8);
+                out.push(len as u8);
+            } else {
+                out.push(0xC6);
+                out.push((len >> 24) as u8);
+                out.push((len >> 16) as u8);
+                out.push((len >> 8) as u8);
+                out.push(len as u8);
+            }
+            out.extend_from_slice(msg);
+        },
+        Format::Hex => {
+            out.push(b'"');
+            for &b in msg {
+                out.extend_from_slice(format!("\\x{:02x}", b).as_bytes());
+            }
+            out.extend_from_slice(b"\"\n");
+        }
+    }
+
+    out
+}
+
+fn is_print(b: u8) -> bool {
+    b >= 0x20 && b < 0x7F
+}
+
+fn print_msg(format: Format, msg: &[u8]) {
+    if format == Format::NoEcho {
+        return;
+    }
+
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+
+    let _ = handle.write_all(&format_msg(format, msg));
+    let _ = handle.flush();
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* socket loops                                                              */
+/*                                                                           */
+/*****************************************************************************/
+
+// Those errors mean no more message can be received for now, like ETIMEDOUT and EFSM in nanocat
+fn is_end_of_recv(err: &io::Error) -> bool {
+    match err.kind() {
+        io::ErrorKind::TimedOut | io::ErrorKind::Other => true,
+        _ => false
+    }
+}
+
+fn send_loop(options: &Options, socket: &mut Socket, data: &[u8]) -> io::Result<()> {
+    loop {
+        try!(socket.send(data.to_vec()));
+
+        match options.interval {
+            Some(interval) => thread::sleep(interval),
+            None => return Ok(())
+        }
+    }
+}
+
+fn recv_loop(options: &Options, socket: &mut Socket) -> io::Result<()> {
+    loop {
+        match socket.recv() {
+            Ok(msg) => print_msg(options.format, &msg),
+            Err(ref e) if is_end_of_recv(e) => return Ok(()),
+            Err(e) => return Err(e)
+        }
+    }
+}
+
+// Sends the data, then prints what is received until it is time to send again
+fn rw_loop(options: &Options, socket: &mut Socket, data: &[u8]) -> io::Result<()> {
+    loop {
+        let start = Instant::now();
+
+        try!(socket.send(data.to_vec()));
+
+        let interval = match options.interval {
+            Some(interval) => interval,
+            None => return recv_loop(options, socket)
+        };
+
+        loop {
+            let elapsed = start.elapsed();
+            if elapsed >= interval {
+                break;
+            }
+
+            let mut time_to_sleep = interval - elapsed;
+            if let Some(recv_timeout) = options.recv_timeout {
+                if time_to_sleep > recv_timeout {
+                    time_to_sleep = recv_timeout;
+                }
+            }
+
+            try!(socket.set_recv_timeout(Some(time_to_sleep)));
+
+            match socket.recv() {
+                Ok(msg) => print_msg(options.format, &msg),
+                Err(ref e) if is_end_of_recv(e) => {
+                    let elapsed = start.elapsed();
+                    if elapsed < interval {
+                        thread::sleep(interval - elapsed);
+                    }
+                },
+                Err(e) => return Err(e)
+            }
+        }
+    }
+}
+
+// Replies to each received message with the data
+fn resp_loop(options: &Options, socket: &mut Socket, data: &[u8]) -> io::Result<()> {
+    loop {
+        match socket.recv() {
+            Ok(msg) => print_msg(options.format, &msg),
+            Err(ref e) if is_end_of_recv(e) => return Ok(()),
+            Err(e) => return Err(e)
+        }
+
+        try!(socket.send(data.to_vec()));
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* main                                                                      */
+/*                                                                           */
+/*****************************************************************************/
+
+#[cfg(not(windows))]
+fn create_session() -> io::Result<Session> {
+    SessionBuilder::new().
+        with("tcp", Tcp).
+        with("ipc", Ipc).
+        build()
+}
+
+#[cfg(windows)]
+fn create_session() -> io::Result<Session> {
+    SessionBuilder::new().
+        with("tcp", Tcp).
+        build()
+}
+
+fn create_socket(session: &mut Session, socket_type: SocketType) -> io::Result<Socket> {
+    match socket_type {
+        SocketType::Push       => session.create_socket::<Push>(),
+        SocketType::Pull       => session.create_socket::<Pull>(),
+        SocketType::Pub        => session.create_socket::<Pub>(),
+        SocketType::Sub        => session.create_socket::<Sub>(),
+        SocketType::Req        => session.create_socket::<Req>(),
+        SocketType::Rep        => session.create_socket::<Rep>(),
+        SocketType::Surveyor   => session.create_socket::<Surveyor>(),
+        SocketType::Respondent => session.create_socket::<Respondent>(),
+        SocketType::Bus        => session.create_socket::<Bus>(),
+        SocketType::Pair       => session.create_socket::<Pair>()
+    }
+}
+
+fn configure_socket(options: &Options, socket_type: SocketType, socket: &mut Socket) -> io::Result<()> {
+    try!(socket.set_recv_timeout(options.recv_timeout));
+    try!(socket.set_send_timeout(options.send_timeout));
+
+    if socket_type == SocketType::Sub {
+        if options.subscriptions.is_empty() {
+            try!(socket.set_option(ConfigOption::Subscribe(String::new())));
+        }
+        for subscription in &options.subscriptions {
+            try!(socket.set_option(ConfigOption::Subscribe(subscription.clone())));
+        }
+    }
+
+    for endpoint in &options.endpoints {
+        match *endpoint {
+            Endpoint::Bind(ref url)    => try!(bind(socket, url)),
+            Endpoint::Connect(ref url) => try!(connect(socket, url))
+        }
+    }
+
+    Ok(())
+}
+
+// Like nanomsg, take over the file left behind by a dead ipc socket
+fn bind(socket: &mut Socket, url: &str) -> io::Result<()> {
+    match socket.bind(url) {
+        Ok(_) => Ok(()),
+        Err(ref e) if e.kind() == io::ErrorKind::AddrInUse && remove_stale_ipc_file(url) => {
+            socket.bind(url).map(|_| ())
+        },
+        Err(e) => Err(e)
+    }
+}
+
+#[cfg(unix)]
+fn remove_stale_ipc_file(url: &str) -> bool {
+    use std::fs;
+    use std::os::unix::net::UnixStream;
+
+    if !url.starts_with("ipc://") {
+        return false;
+    }
+
+    let path = &url[6..];
+
+    match UnixStream::connect(path) {
+        Err(ref e) if e.kind() == io::ErrorKind::ConnectionRefused => fs::remove_file(path).is_ok(),
+        _ => false
+    }
+}
+
+#[cfg(not(unix))]
+fn remove_stale_ipc_file(_: &str) -> bool {
+    false
+}
+
+const CONNECT_ATTEMPTS: u32 = 100;
+
+// Unlike nanomsg, an ipc connection fails right away when nobody is listening yet,
+// so keep trying for a while, as nanocat would do in the background, then give up.
+fn connect(socket: &mut Socket, url: &str) -> io::Result<()> {
+    let mut attempts = 1;
+
+    loop {
+        match socket.connect(url) {
+            Ok(_) => return Ok(()),
+            Err(ref e) if is_connect_retryable(e) && attempts < CONNECT_ATTEMPTS => {
+                if attempts == 1 {
+                    let _ = writeln!(io::stderr(), "scaproust-cat: {}: {}, retrying", url, e);
+                }
+                attempts += 1;
+                thread::sleep(Duration::from_millis(100));
+            },
+            Err(e) => return Err(e)
+        }
+    }
+}
+
+fn is_connect_retryable(err: &io::Error) -> bool {
+    match err.kind() {
+        io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused => true,
+        _ => false
+    }
+}
+
+fn run(options: Options) -> io::Result<()> {
+    let socket_type = options.socket_type.unwrap();
+    let mut session = try!(create_session());
+    let mut socket = try!(create_socket(&mut session, socket_type));
+
+    try!(configure_socket(&options, socket_type, &mut socket));
+
+    if let Some(delay) = options.delay {
+        thread::sleep(delay);
+    }
+
+    match (socket_type, options.data.as_ref()) {
+        (SocketType::Push, Some(data)) |
+        (SocketType::Pub, Some(data))        => send_loop(&options, &mut socket, data),
+        (SocketType::Req, Some(data)) |
+        (SocketType::Surveyor, Some(data)) |
+        (SocketType::Bus, Some(data)) |
+        (SocketType::Pair, Some(data))       => rw_loop(&options, &mut socket, data),
+        (SocketType::Rep, Some(data)) |
+        (SocketType::Respondent, Some(data)) => resp_loop(&options, &mut socket, data),
+        _                                    => recv_loop(&options, &mut socket)
+    }
+}
+
+fn main() {
+    let args = env::args().skip(1);
+
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(ref msg) if msg.is_empty() => {
+            print!("{}", USAGE);
+            process::exit(0)
+        },
+        Err(msg) => {
+            let _ = writeln!(io::stderr(), "{}\n\n{}", msg, USAGE);
+            process::exit(1)
+        }
+    };
+
+    if let Err(e) = run(options) {
+        let _ = writeln!(io::stderr(), "scaproust-cat: {}", e);
+        process::exit(1)
+    }
+}
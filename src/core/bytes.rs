@@ -0,0 +1,174 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+//! Immutable, reference-counted payloads that can be sent several times without being copied.
+
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// Immutable slice of a reference-counted buffer.
+///
+/// Cloning and slicing only copy a pointer and two offsets,
+/// so the same payload can be kept by the application and sent on several sockets,
+/// the buffer being freed when the last of its slices is dropped.
+#[derive(Clone)]
+pub struct Bytes {
+    buffer: Arc<Vec<u8>>,
+    begin: usize,
+    end: usize
+}
+
+impl Bytes {
+    /// Creates an empty payload.
+    pub fn new() -> Bytes {
+        Bytes::from(Vec::new())
+    }
+
+    /// Number of bytes in the slice.
+    pub fn len(&self) -> usize {
+        self.end - self.begin
+    }
+
+    /// Whether the slice contains no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.begin == self.end
+    }
+
+    /// Returns the bytes from `begin` to `end`, sharing the same buffer.
+    ///
+    /// # Panics
+    /// If `begin > end` or `end > self.len()`.
+    pub fn slice(&self, begin: usize, end: usize) -> Bytes {
+        assert!(begin <= end, "slice begin {} is after its end {}", begin, end);
+        assert!(end <= self.len(), "slice end {} is out of bounds {}", end, self.len());
+
+        Bytes {
+            buffer: self.buffer.clone(),
+            begin: self.begin + begin,
+            end: self.begin + end
+        }
+    }
+
+    /// Returns the bytes from `begin` to the end, sharing the same buffer.
+    pub fn slice_from(&self, begin: usize) -> Bytes {
+        self.slice(begin, self.len())
+    }
+
+    /// Returns the bytes from the start to `end`, sharing the same buffer.
+    pub fn slice_to(&self, end: usize) -> Bytes {
+        self.slice(0, end)
+    }
+
+    /// Converts the slice into a vector, without copying
+    /// when this is the only reference to the whole buffer.
+    pub fn into_vec(self) -> Vec<u8> {
+        if self.begin == 0 && self.end == self.buffer.len() {
+            match Arc::try_unwrap(self.buffer) {
+                Ok(buffer) => buffer,
+                Err(buffer) => buffer.to_vec()
+            }
+        } else {
+            self.buffer[self.begin..self.end].to_vec()
+        }
+    }
+}
+
+impl Default for Bytes {
+    fn default() -> Bytes {
+        Bytes::new()
+    }
+}
+
+impl Deref for Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buffer[self.begin..self.end]
+    }
+}
+
+impl AsRef<[u8]> for Bytes {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl From<Vec<u8>> for Bytes {
+    fn from(value: Vec<u8>) -> Bytes {
+        let len = value.len();
+
+        Bytes {
+            buffer: Arc::new(value),
+            begin: 0,
+            end: len
+        }
+    }
+}
+
+impl<'a> From<&'a [u8]> for Bytes {
+    fn from(value: &'a [u8]) -> Bytes {
+        Bytes::from(value.to_vec())
+    }
+}
+
+impl PartialEq for Bytes {
+    fn eq(&self, other: &Bytes) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for Bytes {}
+
+impl fmt::Debug for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Bytes({:?})", &**self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clones_and_slices_share_the_buffer() {
+        let bytes = Bytes::from(vec![1, 2, 3, 4, 5]);
+        let clone = bytes.clone();
+        let slice = bytes.slice(1, 4);
+
+        assert_eq!(bytes.as_ptr(), clone.as_ptr());
+        assert_eq!(&[2, 3, 4], &*slice);
+        assert_eq!(unsafe { bytes.as_ptr().offset(1) }, slice.as_ptr());
+        assert_eq!(&[3], &*slice.slice_from(1).slice_to(1));
+        assert!(slice.slice(2, 2).is_empty());
+    }
+
+    #[test]
+    fn unique_whole_buffer_is_converted_without_copy() {
+        let vec = vec![1, 2, 3];
+        let ptr = vec.as_ptr();
+        let bytes = Bytes::from(vec);
+        let vec = bytes.into_vec();
+
+        assert_eq!(ptr, vec.as_ptr());
+    }
+
+    #[test]
+    fn shared_buffer_is_copied_on_conversion() {
+        let bytes = Bytes::from(vec![1, 2, 3]);
+        let vec = bytes.clone().into_vec();
+
+        assert_eq!(vec![1, 2, 3], vec);
+        assert_ne!(bytes.as_ptr(), vec.as_ptr());
+        assert_eq!(vec![2, 3], bytes.slice_from(1).into_vec());
+    }
+
+    #[test]
+    #[should_panic]
+    fn slice_out_of_bounds_panics() {
+        Bytes::from(vec![1, 2, 3]).slice(1, 4);
+    }
+}
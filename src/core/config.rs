@@ -17,12 +17,31 @@ pub struct Config {
     pub retry_ivl: Duration,
     pub retry_ivl_max: Option<Duration>,
     pub tcp_no_delay: bool,
+    pub tcp_keepalive: Option<TcpKeepalive>,
+    pub liveness_timeout: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+    pub handshake_timeout: Option<Duration>,
+    pub retry_on_protocol_mismatch: bool,
+    pub max_connections: Option<usize>,
     pub recv_max_size: u64
 }
 
+/// Keepalive probing of idle TCP connections, see [ConfigOption::TcpKeepalive](enum.ConfigOption.html#variant.TcpKeepalive).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TcpKeepalive {
+    /// How long the connection must stay idle before the first probe is sent.
+    pub idle: Duration,
+    /// Delay between two unanswered probes.
+    pub interval: Duration,
+    /// Number of unanswered probes after which the connection is considered dead.
+    pub count: u32
+}
+
+#[derive(Debug)]
 pub enum ConfigOption {
     /// Specifies how long the socket should try to send pending outbound messages 
-    /// after `drop` have been called. Default value is 1 second.
+    /// after `drop` have been called.
+    /// Not supported yet: setting or reading it returns an `InvalidInput` error.
     Linger(Duration),
 
     /// See [Socket::set_send_timeout](struct.Socket.html#method.set_send_timeout).
@@ -56,9 +75,44 @@ pub enum ConfigOption {
     /// Default value is `None`.
     RetryIvlMax(Option<Duration>),
 
+    /// For connection-based transports, specifies how long a connection attempt can remain pending
+    /// before being abandoned and retried after the reconnect interval.
+    /// Value of `None` means that the attempt is left to the operating system, which is the default.
+    ConnectTimeout(Option<Duration>),
+
+    /// Specifies how long a new pipe can wait for the handshake of its peer
+    /// before being closed, connected pipes being retried after the reconnect interval.
+    /// Value of `None` means that the handshake is awaited indefinitely, which is the default.
+    HandshakeTimeout(Option<Duration>),
+
+    /// Specifies whether a connected endpoint should keep reconnecting
+    /// after its peer presented a protocol the socket cannot communicate with,
+    /// such as a `Push` socket connected to a `Sub` socket.
+    /// The mismatch is logged in both cases, naming the two protocols.
+    /// Default value is `true`.
+    RetryOnProtocolMismatch(bool),
+
+    /// Limits the number of pipes each endpoint subsequently bound by the socket can accept.
+    /// While an endpoint has this many pipes open, it stops accepting connections,
+    /// which are left waiting in the operating system backlog until a pipe is closed.
+    /// Value of `None` means that the number of pipes is not limited, which is the default.
+    MaxConnections(Option<usize>),
+
     /// See [Socket::set_tcp_nodelay](struct.Socket.html#method.set_tcp_nodelay).
     TcpNoDelay(bool),
 
+    /// Enables TCP keepalive on the connections subsequently created by the socket,
+    /// so that a peer gone without closing the connection is eventually detected
+    /// and the pipe reconnected. The interval and the count of probes are only applied on Linux.
+    /// Value of `None` leaves keepalive disabled, which is the default.
+    TcpKeepalive(Option<TcpKeepalive>),
+
+    /// Closes the pipes that have neither sent nor received a message during this amount of time,
+    /// connected pipes being then re-established like after any other error.
+    /// Since idle pipes are looked for once per period, a pipe may remain idle up to twice this value.
+    /// Value of `None` means that idle pipes are kept open, which is the default.
+    LivenessTimeout(Option<Duration>),
+
     /// Defined on `Sub` socket. Subscribes for a particular topic.
     /// A single `Sub` socket can handle multiple subscriptions.
     Subscribe(String),
@@ -76,7 +130,35 @@ pub enum ConfigOption {
     /// Once the deadline expires, receive function will return a TimedOut error 
     /// and all subsequent responses to the survey will be silently dropped.
     /// Default value is 1 second.
-    SurveyDeadline(Duration)
+    SurveyDeadline(Duration),
+
+    /// Defined on `Sub` socket. Lists the topics the socket is currently subscribed to.
+    /// This option can only be read, use `Subscribe` and `Unsubscribe` to change it.
+    Subscriptions(Vec<String>)
+}
+
+/// Identifies a socket option when reading its value back.
+/// See [Socket::get_option](struct.Socket.html#method.get_option).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConfigOptionKind {
+    Linger,
+    SendTimeout,
+    SendPriority,
+    RecvTimeout,
+    RecvPriority,
+    RecvMaxSize,
+    RetryIvl,
+    RetryIvlMax,
+    ConnectTimeout,
+    HandshakeTimeout,
+    RetryOnProtocolMismatch,
+    MaxConnections,
+    TcpNoDelay,
+    TcpKeepalive,
+    LivenessTimeout,
+    Subscriptions,
+    ReqResendIvl,
+    SurveyDeadline
 }
 
 impl Default for Config {
@@ -89,6 +171,12 @@ impl Default for Config {
             retry_ivl: Duration::from_millis(100),
             retry_ivl_max: None,
             tcp_no_delay: false,
+            tcp_keepalive: None,
+            liveness_timeout: None,
+            connect_timeout: None,
+            handshake_timeout: None,
+            retry_on_protocol_mismatch: true,
+            max_connections: None,
             recv_max_size: 1024 * 1024
         }
     }
@@ -103,12 +191,40 @@ impl Config {
             ConfigOption::RecvPriority(priority) => self.recv_priority = priority,
             ConfigOption::RetryIvl(ivl) => self.retry_ivl = ivl,
             ConfigOption::RetryIvlMax(ivl) => self.retry_ivl_max = ivl,
+            ConfigOption::ConnectTimeout(timeout) => self.connect_timeout = timeout,
+            ConfigOption::HandshakeTimeout(timeout) => self.handshake_timeout = timeout,
+            ConfigOption::RetryOnProtocolMismatch(x) => self.retry_on_protocol_mismatch = x,
+            ConfigOption::MaxConnections(x) => self.max_connections = x,
             ConfigOption::RecvMaxSize(x) => self.recv_max_size = x,
             ConfigOption::TcpNoDelay(x) => self.tcp_no_delay = x,
+            ConfigOption::TcpKeepalive(x) => self.tcp_keepalive = x,
+            ConfigOption::LivenessTimeout(x) => self.liveness_timeout = x,
             _ => return Err(invalid_input_io_error("option not supported"))
         }
         Ok(())
     }
+
+    pub fn get(&self, kind: ConfigOptionKind) -> Result<ConfigOption> {
+        let cfg_opt = match kind {
+            ConfigOptionKind::SendTimeout  => ConfigOption::SendTimeout(self.send_timeout),
+            ConfigOptionKind::SendPriority => ConfigOption::SendPriority(self.send_priority),
+            ConfigOptionKind::RecvTimeout  => ConfigOption::RecvTimeout(self.recv_timeout),
+            ConfigOptionKind::RecvPriority => ConfigOption::RecvPriority(self.recv_priority),
+            ConfigOptionKind::RetryIvl     => ConfigOption::RetryIvl(self.retry_ivl),
+            ConfigOptionKind::RetryIvlMax  => ConfigOption::RetryIvlMax(self.retry_ivl_max),
+            ConfigOptionKind::ConnectTimeout => ConfigOption::ConnectTimeout(self.connect_timeout),
+            ConfigOptionKind::HandshakeTimeout => ConfigOption::HandshakeTimeout(self.handshake_timeout),
+            ConfigOptionKind::RetryOnProtocolMismatch => ConfigOption::RetryOnProtocolMismatch(self.retry_on_protocol_mismatch),
+            ConfigOptionKind::MaxConnections => ConfigOption::MaxConnections(self.max_connections),
+            ConfigOptionKind::RecvMaxSize  => ConfigOption::RecvMaxSize(self.recv_max_size),
+            ConfigOptionKind::TcpNoDelay   => ConfigOption::TcpNoDelay(self.tcp_no_delay),
+            ConfigOptionKind::TcpKeepalive => ConfigOption::TcpKeepalive(self.tcp_keepalive),
+            ConfigOptionKind::LivenessTimeout => ConfigOption::LivenessTimeout(self.liveness_timeout),
+            _ => return Err(invalid_input_io_error("option not supported"))
+        };
+
+        Ok(cfg_opt)
+    }
 }
 
 impl ConfigOption {
@@ -120,9 +236,40 @@ impl ConfigOption {
             ConfigOption::SendPriority(_) |
             ConfigOption::RecvTimeout(_)  |
             ConfigOption::RecvPriority(_) |
+            ConfigOption::RecvMaxSize(_)  |
             ConfigOption::RetryIvl(_)     |
             ConfigOption::RetryIvlMax(_)  |
-            ConfigOption::TcpNoDelay(_)   => true,
+            ConfigOption::ConnectTimeout(_) |
+            ConfigOption::HandshakeTimeout(_) |
+            ConfigOption::RetryOnProtocolMismatch(_) |
+            ConfigOption::MaxConnections(_) |
+            ConfigOption::TcpNoDelay(_)   |
+            ConfigOption::TcpKeepalive(_) |
+            ConfigOption::LivenessTimeout(_) => true,
+            _ => false
+        }
+    }
+}
+
+impl ConfigOptionKind {
+    #[doc(hidden)]
+    pub fn is_generic(&self) -> bool {
+        match *self {
+            ConfigOptionKind::Linger       |
+            ConfigOptionKind::SendTimeout  |
+            ConfigOptionKind::SendPriority |
+            ConfigOptionKind::RecvTimeout  |
+            ConfigOptionKind::RecvPriority |
+            ConfigOptionKind::RecvMaxSize  |
+            ConfigOptionKind::RetryIvl     |
+            ConfigOptionKind::RetryIvlMax  |
+            ConfigOptionKind::ConnectTimeout |
+            ConfigOptionKind::HandshakeTimeout |
+            ConfigOptionKind::RetryOnProtocolMismatch |
+            ConfigOptionKind::MaxConnections |
+            ConfigOptionKind::TcpNoDelay   |
+            ConfigOptionKind::TcpKeepalive |
+            ConfigOptionKind::LivenessTimeout => true,
             _ => false
         }
     }
@@ -11,13 +11,21 @@ use std::time::Duration;
 use core::{EndpointId, EndpointSpec, Scheduled};
 use core::network::Network;
 
+/// Execution context handed to every [`Protocol`](../socket/trait.Protocol.html) callback.
+/// It gives access to the pipes through the [`Network`](../network/trait.Network.html) trait,
+/// to timers through the [`Scheduler`](trait.Scheduler.html) trait,
+/// and lets the protocol notify the socket about readiness changes.
 pub trait Context : Network + Scheduler + fmt::Debug {
+    /// Notifies the socket owning the protocol that something happened,
+    /// typically that it became ready or not ready to send or receive.
     fn raise(&mut self, evt: Event);
+    /// Raises `CanSend` when the send readiness changed.
     fn check_send_ready_change(&mut self, was_ready: bool, is_ready: bool) {
         if was_ready != is_ready {
             self.raise(Event::CanSend(is_ready));
         }
     }
+    /// Raises `CanRecv` when the recv readiness changed.
     fn check_recv_ready_change(&mut self, was_ready: bool, is_ready: bool) {
         if was_ready != is_ready {
             self.raise(Event::CanRecv(is_ready));
@@ -25,30 +33,58 @@ pub trait Context : Network + Scheduler + fmt::Debug {
     }
 }
 
+/// Timer facility of the I/O thread.
 pub trait Scheduler {
+    /// Registers a timer that will be delivered after `delay` has elapsed.
+    /// Timers created by a protocol are delivered back to
+    /// [`Protocol::on_timer_tick`](../socket/trait.Protocol.html#method.on_timer_tick),
+    /// except `SendTimeout` and `RecvTimeout` which are routed to the dedicated callbacks.
     fn schedule(&mut self, schedulable: Schedulable, delay: Duration) -> Result<Scheduled>;
+    /// Cancels a pending timer, it is a no-op if the timer has already been delivered.
     fn cancel(&mut self, scheduled: Scheduled);
 }
 
+#[doc(hidden)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Command {
     Poll
 }
 
+/// Notifications raised by a protocol or an endpoint.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Event {
+    /// The socket can, or can no longer, send a message without waiting.
     CanSend(bool),
+    /// The socket can, or can no longer, receive a message without waiting.
     CanRecv(bool),
+    /// The socket or endpoint has been closed.
     Closed
 }
 
+/// What a timer is about.
 pub enum Schedulable {
+    #[doc(hidden)]
     Reconnect(EndpointId, EndpointSpec),
+    #[doc(hidden)]
     Rebind(EndpointId, EndpointSpec),
+    #[doc(hidden)]
+    LivenessCheck,
+    #[doc(hidden)]
+    ConnectTimeout(EndpointId),
+    #[doc(hidden)]
+    HandshakeTimeout(EndpointId),
+    #[doc(hidden)]
+    ResumeAccept(EndpointId),
+    /// The pending send operation expired, delivered to `Protocol::on_send_timeout`.
     SendTimeout,
+    /// The pending recv operation expired, delivered to `Protocol::on_recv_timeout`.
     RecvTimeout,
+    /// Used by the `Req` protocol to resend a request.
     ReqResend,
-    SurveyCancel
+    /// Used by the `Surveyor` protocol to close a survey.
+    SurveyCancel,
+    /// Timer defined by a user protocol, the value is chosen by the protocol.
+    Custom(u32)
 }
 
 impl fmt::Debug for Scheduled {
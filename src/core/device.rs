@@ -4,20 +4,34 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
-use std::sync::mpsc::Sender;
+use std::sync::mpsc::{Sender, Receiver};
+use std::io;
 
-use super::SocketId;
+use super::{SocketId, Message};
+use super::socket;
 
 pub enum Request {
     Check,
+    GetCounters,
     Close
 }
 
 pub enum Reply {
+    Err(io::Error),
     Check(bool, bool),
+    Counters(DeviceCounters),
     Closed
 }
 
+/// Number of messages and bytes forwarded by a device running on the I/O thread.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DeviceCounters {
+    pub left_to_right_msgs: u64,
+    pub left_to_right_bytes: u64,
+    pub right_to_left_msgs: u64,
+    pub right_to_left_bytes: u64
+}
+
 pub trait Context {
     fn poll(&mut self, sid: SocketId);
 }
@@ -91,3 +105,375 @@ impl Drop for Device {
         self.send_reply(Reply::Closed)
     }
 }
+
+/*****************************************************************************/
+/*                                                                           */
+/* Forwarder                                                                 */
+/*                                                                           */
+/*****************************************************************************/
+
+/// What the forwarder needs the controller to do with the sockets it links.
+pub enum Action {
+    Poll(SocketId),
+    Recv(SocketId),
+    Send(SocketId, Message),
+    Close(SocketId, SocketId)
+}
+
+struct Link {
+    id: SocketId,
+    reply_receiver: Receiver<socket::Reply>,
+    can_recv: bool,
+    refresh: bool,
+    receiving: bool,
+    sending: Option<Message>,
+    outgoing: Option<Message>,
+    sent_msgs: u64,
+    sent_bytes: u64
+}
+
+/// A device running on the I/O thread, moving messages between two sockets
+/// without going through the facade. It owns the reply channels of both sockets.
+pub struct Forwarder {
+    reply_sender: Sender<Reply>,
+    left: Link,
+    right: Link,
+    error: Option<io::Error>,
+    closed: bool
+}
+
+impl Link {
+    fn new(id: SocketId, reply_rx: Receiver<socket::Reply>) -> Link {
+        Link {
+            id: id,
+            reply_receiver: reply_rx,
+            can_recv: false,
+            refresh: false,
+            receiving: false,
+            sending: None,
+            outgoing: None,
+            sent_msgs: 0,
+            sent_bytes: 0
+        }
+    }
+
+    fn on_send_ack(&mut self) {
+        if let Some(msg) = self.sending.take() {
+            self.sent_msgs += 1;
+            self.sent_bytes += msg.len() as u64;
+        }
+    }
+
+    // Only one operation is in flight on a socket, so the error is the outcome of that one.
+    // A failed send is tried again with the copy of the message, a failed recv waits for readiness.
+    fn on_retryable_error(&mut self) {
+        if let Some(msg) = self.sending.take() {
+            self.outgoing = Some(msg);
+        }
+
+        self.receiving = false;
+        self.can_recv = false;
+        self.refresh = true;
+    }
+
+    fn is_busy(&self) -> bool {
+        self.receiving || self.sending.is_some()
+    }
+}
+
+impl Forwarder {
+    pub fn new(
+        reply_tx: Sender<Reply>,
+        l: SocketId, l_rx: Receiver<socket::Reply>,
+        r: SocketId, r_rx: Receiver<socket::Reply>) -> Forwarder {
+
+        Forwarder {
+            reply_sender: reply_tx,
+            left: Link::new(l, l_rx),
+            right: Link::new(r, r_rx),
+            error: None,
+            closed: false
+        }
+    }
+
+    pub fn on_socket_can_recv(&mut self, sid: SocketId, can_recv: bool) {
+        if sid == self.left.id {
+            self.left.can_recv = can_recv;
+        } else if sid == self.right.id {
+            self.right.can_recv = can_recv;
+        }
+    }
+
+    /// Collects the replies of both sockets and tells what should be done next.
+    /// The controller must call this until it returns `None`.
+    pub fn next_action(&mut self) -> Option<Action> {
+        if self.closed {
+            return None;
+        }
+
+        let drained = drain_replies(&mut self.left, &mut self.right).
+            and_then(|_| drain_replies(&mut self.right, &mut self.left));
+
+        if let Err(e) = drained {
+            self.error = Some(e);
+            self.closed = true;
+
+            return Some(Action::Close(self.left.id, self.right.id));
+        }
+
+        next_transfer(&mut self.left, &mut self.right).
+            or_else(|| next_transfer(&mut self.right, &mut self.left))
+    }
+
+    pub fn report_counters(&self) {
+        let counters = DeviceCounters {
+            left_to_right_msgs: self.right.sent_msgs,
+            left_to_right_bytes: self.right.sent_bytes,
+            right_to_left_msgs: self.left.sent_msgs,
+            right_to_left_bytes: self.left.sent_bytes
+        };
+
+        self.send_reply(Reply::Counters(counters));
+    }
+
+    /// Stops forwarding, reporting the error that may have stopped the device earlier.
+    pub fn stop(&mut self) -> Option<Action> {
+        let reply = match self.error.take() {
+            Some(e) => Reply::Err(e),
+            None => Reply::Closed
+        };
+
+        self.send_reply(reply);
+
+        if self.closed {
+            None
+        } else {
+            self.closed = true;
+            Some(Action::Close(self.left.id, self.right.id))
+        }
+    }
+
+    fn send_reply(&self, reply: Reply) {
+        let _ = self.reply_sender.send(reply);
+    }
+
+    pub fn get_left_id(&self) -> &SocketId {
+        &self.left.id
+    }
+
+    pub fn get_right_id(&self) -> &SocketId {
+        &self.right.id
+    }
+}
+
+// Replies to a recv carry a message for the other socket,
+// replies to a send acknowledge a message coming from the other socket.
+fn drain_replies(link: &mut Link, other: &mut Link) -> io::Result<()> {
+    while let Ok(reply) = link.reply_receiver.try_recv() {
+        match reply {
+            socket::Reply::Recv(msg) => {
+                // the last readiness event may predate the recv, so ask for a fresh one
+                link.receiving = false;
+                link.can_recv = false;
+                link.refresh = true;
+                other.outgoing = Some(msg);
+            },
+            socket::Reply::Send => link.on_send_ack(),
+            socket::Reply::Err(ref e) if is_retryable(e) => link.on_retryable_error(),
+            socket::Reply::Err(e) => return Err(e),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+// Timeouts only mean a peer was not ready in time, the device keeps forwarding
+fn is_retryable(err: &io::Error) -> bool {
+    match err.kind() {
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => true,
+        _ => false
+    }
+}
+
+// Keeps a copy of the message to send it again if the send times out,
+// the body becomes a shared payload so that copy does not duplicate it.
+fn share(msg: Message) -> (Message, Message) {
+    let header = msg.get_header().to_vec();
+    let body = msg.into_bytes();
+    let mut sent = Message::from_bytes(body.clone());
+    let mut copy = Message::from_bytes(body);
+
+    sent.header = header.clone();
+    copy.header = header;
+
+    (sent, copy)
+}
+
+fn next_transfer(from: &mut Link, to: &mut Link) -> Option<Action> {
+    if !to.is_busy() {
+        if let Some(msg) = to.outgoing.take() {
+            let (sent, copy) = share(msg);
+
+            to.sending = Some(copy);
+
+            return Some(Action::Send(to.id, sent));
+        }
+    }
+
+    if from.refresh {
+        from.refresh = false;
+
+        return Some(Action::Poll(from.id));
+    }
+
+    if from.can_recv && !from.is_busy() && to.outgoing.is_none() {
+        from.receiving = true;
+
+        return Some(Action::Recv(from.id));
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::sync::mpsc;
+
+    use core::{SocketId, Message};
+    use core::socket;
+
+    use super::*;
+
+    #[test]
+    fn when_socket_can_recv_forwarder_asks_to_recv_from_it() {
+        let (tx, _) = mpsc::channel();
+        let (_, l_rx) = mpsc::channel();
+        let (_, r_rx) = mpsc::channel();
+        let (l, r) = (SocketId::from(1), SocketId::from(2));
+        let mut forwarder = Forwarder::new(tx, l, l_rx, r, r_rx);
+
+        assert!(forwarder.next_action().is_none());
+
+        forwarder.on_socket_can_recv(l, true);
+
+        match forwarder.next_action() {
+            Some(Action::Recv(sid)) => assert_eq!(l, sid),
+            _ => panic!("forwarder should ask to recv from left")
+        }
+        assert!(forwarder.next_action().is_none());
+    }
+
+    #[test]
+    fn when_message_is_received_forwarder_asks_to_send_it_to_the_other_socket() {
+        let (tx, rx) = mpsc::channel();
+        let (l_tx, l_rx) = mpsc::channel();
+        let (r_tx, r_rx) = mpsc::channel();
+        let (l, r) = (SocketId::from(1), SocketId::from(2));
+        let mut forwarder = Forwarder::new(tx, l, l_rx, r, r_rx);
+
+        forwarder.on_socket_can_recv(l, true);
+        forwarder.next_action();
+        l_tx.send(socket::Reply::Recv(Message::from_body(vec![65, 66, 67]))).unwrap();
+
+        match forwarder.next_action() {
+            Some(Action::Send(sid, msg)) => {
+                assert_eq!(r, sid);
+                assert_eq!(&[65, 66, 67], msg.get_body());
+            },
+            _ => panic!("forwarder should ask to send to right")
+        }
+
+        match forwarder.next_action() {
+            Some(Action::Poll(sid)) => assert_eq!(l, sid),
+            _ => panic!("forwarder should ask to poll left")
+        }
+
+        r_tx.send(socket::Reply::Send).unwrap();
+        forwarder.on_socket_can_recv(l, false);
+        assert!(forwarder.next_action().is_none());
+        forwarder.report_counters();
+
+        match rx.try_recv().unwrap() {
+            Reply::Counters(counters) => {
+                assert_eq!(1, counters.left_to_right_msgs);
+                assert_eq!(3, counters.left_to_right_bytes);
+                assert_eq!(0, counters.right_to_left_msgs);
+            },
+            _ => panic!("forwarder should have sent its counters")
+        }
+    }
+
+    #[test]
+    fn when_socket_fails_forwarder_closes_both_sockets_and_reports_the_error_on_stop() {
+        let (tx, rx) = mpsc::channel();
+        let (_, l_rx) = mpsc::channel();
+        let (r_tx, r_rx) = mpsc::channel();
+        let (l, r) = (SocketId::from(1), SocketId::from(2));
+        let mut forwarder = Forwarder::new(tx, l, l_rx, r, r_rx);
+
+        r_tx.send(socket::Reply::Err(io::Error::new(io::ErrorKind::ConnectionReset, "reset"))).unwrap();
+
+        match forwarder.next_action() {
+            Some(Action::Close(x, y)) => assert_eq!((l, r), (x, y)),
+            _ => panic!("forwarder should ask to close the sockets")
+        }
+        assert!(forwarder.next_action().is_none());
+        assert!(forwarder.stop().is_none());
+
+        match rx.try_recv().unwrap() {
+            Reply::Err(e) => assert_eq!(io::ErrorKind::ConnectionReset, e.kind()),
+            _ => panic!("forwarder should have reported the error")
+        }
+    }
+
+    #[test]
+    fn when_send_times_out_forwarder_sends_the_message_again() {
+        let (tx, _) = mpsc::channel();
+        let (l_tx, l_rx) = mpsc::channel();
+        let (r_tx, r_rx) = mpsc::channel();
+        let (l, r) = (SocketId::from(1), SocketId::from(2));
+        let mut forwarder = Forwarder::new(tx, l, l_rx, r, r_rx);
+
+        forwarder.on_socket_can_recv(l, true);
+        forwarder.next_action();
+        l_tx.send(socket::Reply::Recv(Message::from_body(vec![65, 66, 67]))).unwrap();
+        forwarder.next_action();
+        r_tx.send(socket::Reply::Err(io::Error::new(io::ErrorKind::TimedOut, "timeout"))).unwrap();
+
+        match forwarder.next_action() {
+            Some(Action::Send(sid, msg)) => {
+                assert_eq!(r, sid);
+                assert_eq!(&[65, 66, 67], msg.get_body());
+            },
+            _ => panic!("forwarder should ask to send to right again")
+        }
+    }
+
+    #[test]
+    fn when_recv_times_out_forwarder_waits_for_the_socket_to_be_readable_again() {
+        let (tx, _) = mpsc::channel();
+        let (l_tx, l_rx) = mpsc::channel();
+        let (_, r_rx) = mpsc::channel();
+        let (l, r) = (SocketId::from(1), SocketId::from(2));
+        let mut forwarder = Forwarder::new(tx, l, l_rx, r, r_rx);
+
+        forwarder.on_socket_can_recv(l, true);
+        forwarder.next_action();
+        l_tx.send(socket::Reply::Err(io::Error::new(io::ErrorKind::WouldBlock, "not ready"))).unwrap();
+
+        match forwarder.next_action() {
+            Some(Action::Poll(sid)) => assert_eq!(l, sid),
+            _ => panic!("forwarder should ask to poll left")
+        }
+        assert!(forwarder.next_action().is_none());
+
+        forwarder.on_socket_can_recv(l, true);
+
+        match forwarder.next_action() {
+            Some(Action::Recv(sid)) => assert_eq!(l, sid),
+            _ => panic!("forwarder should ask to recv from left again")
+        }
+    }
+}
@@ -9,17 +9,23 @@ use std::rc::Rc;
 use super::{EndpointId, Message, EndpointSpec, EndpointDesc};
 use super::context::Context;
 
+#[doc(hidden)]
 pub enum Request {
     Close(bool)
 }
 
+#[doc(hidden)]
 pub struct Endpoint {
     id: EndpointId,
     url: Option<String>,
     desc: EndpointDesc
 }
 
+/// A connection to a peer, as seen by a [`Protocol`](../socket/trait.Protocol.html).
+/// Each operation only starts the I/O, the completion is notified to the protocol
+/// through the matching callback.
 pub struct Pipe(Endpoint);
+#[doc(hidden)]
 pub struct Acceptor(Endpoint);
 
 impl Endpoint {
@@ -66,42 +72,59 @@ impl Endpoint {
             None => None,
         }
     }
+    fn get_url(&self) -> Option<&str> {
+        self.url.as_ref().map(|url| url.as_str())
+    }
     fn get_send_priority(&self) -> u8 {
         self.desc.send_priority
     }
     fn get_recv_priority(&self) -> u8 {
         self.desc.recv_priority
     }
+    fn get_max_connections(&self) -> Option<usize> {
+        self.desc.max_connections
+    }
 }
 
 impl Pipe {
+    #[doc(hidden)]
     pub fn new_connected(id: EndpointId, url: String, desc: EndpointDesc) -> Pipe {
         Pipe(Endpoint::new_created(id, url, desc))
     }
 
+    #[doc(hidden)]
     pub fn new_accepted(id: EndpointId, desc: EndpointDesc) -> Pipe {
         Pipe(Endpoint::new_accepted(id, desc))
     }
 
+    #[doc(hidden)]
     pub fn from_spec(id: EndpointId, spec: EndpointSpec) -> Pipe {
         Pipe(Endpoint::from_spec(id, spec))
     }
 
+    /// Starts the handshake, this is done by the socket before the pipe is given to the protocol.
     pub fn open(&self, network: &mut Context) {
         self.0.open(network, true)
     }
+    /// Starts writing the message, completion is notified by `Protocol::on_send_ack`.
+    /// Must only be called after `Protocol::on_send_ready`.
     pub fn send(&self, network: &mut Context, msg: Rc<Message>) {
         self.0.send(network, msg)
     }
+    /// Starts reading a message, completion is notified by `Protocol::on_recv_ack`.
+    /// Must only be called after `Protocol::on_recv_ready`.
     pub fn recv(&self, network: &mut Context) {
         self.0.recv(network)
     }
+    /// Closes the connection.
     pub fn close(self, network: &mut Context) -> Option<EndpointSpec> {
         self.0.close(network, true)
     }
+    /// Send priority configured on the socket when the endpoint was created.
     pub fn get_send_priority(&self) -> u8 {
         self.0.get_send_priority()
     }
+    /// Recv priority configured on the socket when the endpoint was created.
     pub fn get_recv_priority(&self) -> u8 {
         self.0.get_recv_priority()
     }
@@ -120,10 +143,22 @@ impl Acceptor {
     pub fn close(self, network: &mut Context) -> Option<EndpointSpec> {
         self.0.close(network, false)
     }
+    pub fn pause(&self, network: &mut Context) {
+        network.pause(self.0.id)
+    }
+    pub fn resume(&self, network: &mut Context) {
+        network.resume(self.0.id)
+    }
+    pub fn get_url(&self) -> Option<&str> {
+        self.0.get_url()
+    }
     pub fn get_send_priority(&self) -> u8 {
         self.0.get_send_priority()
     }
     pub fn get_recv_priority(&self) -> u8 {
         self.0.get_recv_priority()
     }
+    pub fn get_max_connections(&self) -> Option<usize> {
+        self.0.get_max_connections()
+    }
 }
@@ -4,14 +4,21 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
-#[doc(hidden)] pub mod network;
-#[doc(hidden)] pub mod context;
+//! Building blocks shared by the socket runtime and the protocols.
+//! The [`socket::Protocol`](socket/trait.Protocol.html), [`context::Context`](context/trait.Context.html)
+//! and [`endpoint::Pipe`](endpoint/struct.Pipe.html) items form the extension API used to implement
+//! additional protocols outside of this crate.
+
+pub mod network;
+pub mod context;
 #[doc(hidden)] pub mod config;
-#[doc(hidden)] pub mod socket;
+pub mod socket;
 #[doc(hidden)] pub mod session;
-#[doc(hidden)] pub mod endpoint;
+pub mod endpoint;
 #[doc(hidden)] pub mod device;
 #[doc(hidden)] pub mod probe;
+pub mod pool;
+pub mod bytes;
 
 #[cfg(test)]
 pub mod tests;
@@ -19,7 +26,10 @@ pub mod tests;
 use std::fmt;
 use std::hash::{BuildHasher, Hasher};
 
-#[doc(hidden)]
+use self::bytes::Bytes;
+
+/// Handle on a timer registered with [`Scheduler::schedule`](context/trait.Scheduler.html#tymethod.schedule).
+/// It can be handed back to [`Scheduler::cancel`](context/trait.Scheduler.html#tymethod.cancel).
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Scheduled(usize);
 
@@ -29,7 +39,7 @@ pub struct Scheduled(usize);
 /*                                                                           */
 /*****************************************************************************/
 
-#[doc(hidden)]
+/// Identifies an endpoint, and therefore a connection, within a session.
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub struct EndpointId(usize);
 
@@ -75,12 +85,77 @@ pub struct EndpointSpec {
     pub desc: EndpointDesc
 }
 
+#[doc(hidden)]
+pub struct EndpointInfo {
+    pub url: String,
+    pub local_addr: Option<String>,
+    pub peer_addr: Option<String>
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* PipeInfo                                                                  */
+/*                                                                           */
+/*****************************************************************************/
+
+/// Credentials of the process at the other end of an IPC connection.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PeerCred {
+    pub uid: u32,
+    pub gid: u32,
+    pub pid: i32
+}
+
+/// Describes the connection a message was received from.
+/// See [recv_with_info](struct.Socket.html#method.recv_with_info).
+#[derive(Clone, Debug)]
+pub struct PipeInfo {
+    eid: EndpointId,
+    scheme: String,
+    peer_addr: Option<String>,
+    peer_cred: Option<PeerCred>
+}
+
+impl PipeInfo {
+    #[doc(hidden)]
+    pub fn new(eid: EndpointId, scheme: String, peer_addr: Option<String>, peer_cred: Option<PeerCred>) -> PipeInfo {
+        PipeInfo {
+            eid: eid,
+            scheme: scheme,
+            peer_addr: peer_addr,
+            peer_cred: peer_cred
+        }
+    }
+
+    /// Returns the id of the endpoint the message was received from.
+    pub fn endpoint_id(&self) -> EndpointId {
+        self.eid
+    }
+
+    /// Returns the transport scheme of the connection, `tcp` or `ipc` for example.
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    /// Returns the remote address of the connection, in the format of its transport.
+    pub fn peer_addr(&self) -> Option<&str> {
+        self.peer_addr.as_ref().map(|addr| addr.as_str())
+    }
+
+    /// Returns the credentials of the remote process, only available for IPC connections on Linux.
+    pub fn peer_cred(&self) -> Option<PeerCred> {
+        self.peer_cred
+    }
+}
+
 #[doc(hidden)]
 pub struct EndpointDesc {
     pub send_priority: u8,
     pub recv_priority: u8,
     pub tcp_no_delay: bool,
-    pub recv_max_size: u64
+    pub tcp_keepalive: Option<config::TcpKeepalive>,
+    pub recv_max_size: u64,
+    pub max_connections: Option<usize>
 }
 
 /*****************************************************************************/
@@ -105,6 +180,12 @@ impl From<usize> for SocketId {
     }
 }
 
+impl Into<usize> for SocketId {
+    fn into(self) -> usize {
+        self.0
+    }
+}
+
 /*****************************************************************************/
 /*                                                                           */
 /* DeviceId                                                                  */
@@ -127,6 +208,12 @@ impl From<usize> for DeviceId {
     }
 }
 
+impl Into<usize> for DeviceId {
+    fn into(self) -> usize {
+        self.0
+    }
+}
+
 /*****************************************************************************/
 /*                                                                           */
 /* ProbeId                                                                   */
@@ -149,6 +236,12 @@ impl From<usize> for ProbeId {
     }
 }
 
+impl Into<usize> for ProbeId {
+    fn into(self) -> usize {
+        self.0
+    }
+}
+
 /// Request for socket polling, tells whether the poll should wait for the socket to become readable or writable.
 pub struct PollReq {
     pub sid: SocketId,
@@ -171,33 +264,47 @@ pub struct PollRes {
 #[derive(Default, Debug)]
 pub struct Message {
     pub header: Vec<u8>,
-    pub body: Vec<u8>
+    /// Owned body of the message, empty when the message was created from a shared payload.
+    #[deprecated(since = "0.3.2", note = "use `get_body`, `split` or `into_bytes`, which also handle shared payloads")]
+    pub body: Vec<u8>,
+    // Set instead of `body` when the payload is shared with the application and other messages.
+    shared: Option<Bytes>,
+    // Endpoint the message was received from, used to describe its pipe to the application.
+    origin: Option<EndpointId>
 }
 
+#[allow(deprecated)]
 impl Message {
     pub fn new() -> Message {
-        Message {
-            header: Vec::new(),
-            body: Vec::new()
-        }
+        Message::default()
     }
 
     pub fn from_body(body: Vec<u8>) -> Message {
-        Message {
-            header: Vec::new(),
-            body: body
-        }
+        Message::from_header_and_body(Vec::new(), body)
     }
 
     pub fn from_header_and_body(header: Vec<u8>, body: Vec<u8>) -> Message {
         Message {
             header: header,
-            body: body
+            body: body,
+            shared: None,
+            origin: None
+        }
+    }
+
+    /// Creates a message whose body is shared with the application and the other messages
+    /// created from the same payload, it is never copied when sent.
+    pub fn from_bytes(body: Bytes) -> Message {
+        Message {
+            header: Vec::new(),
+            body: Vec::new(),
+            shared: Some(body),
+            origin: None
         }
     }
 
     pub fn len(&self) -> usize {
-        self.header.len() + self.body.len()
+        self.header.len() + self.get_body().len()
     }
 
     pub fn get_header(&self) -> &[u8] {
@@ -205,21 +312,56 @@ impl Message {
     }
 
     pub fn get_body(&self) -> &[u8] {
-        &self.body
+        match self.shared {
+            Some(ref shared) => shared,
+            None => &self.body
+        }
     }
 
+    /// Returns the header and the body, the latter being copied
+    /// only if it is a payload still shared with other messages.
     pub fn split(self) -> (Vec<u8>, Vec<u8>) {
-        (self.header, self.body)
+        match self.shared {
+            Some(shared) => (self.header, shared.into_vec()),
+            None => (self.header, self.body)
+        }
+    }
+
+    /// Converts the body into a shared payload, without copying it.
+    pub fn into_bytes(self) -> Bytes {
+        match self.shared {
+            Some(shared) => shared,
+            None => Bytes::from(self.body)
+        }
     }
 
     pub fn without_header(self) -> Message {
-        Message::from_body(self.body)
+        Message {
+            header: Vec::new(),
+            body: self.body,
+            shared: self.shared,
+            origin: self.origin
+        }
+    }
+
+    /// Id of the endpoint the message was received from, if any.
+    #[doc(hidden)]
+    pub fn get_origin(&self) -> Option<EndpointId> {
+        self.origin
+    }
+
+    /// Remembers the endpoint the message was received from,
+    /// protocols decoding a received message into a new one must keep it.
+    #[doc(hidden)]
+    pub fn with_origin(mut self, origin: Option<EndpointId>) -> Message {
+        self.origin = origin;
+        self
     }
 }
 
 impl Into<Vec<u8>> for Message {
     fn into(self) -> Vec<u8> {
-        self.body
+        self.split().1
     }
 }
 
@@ -229,6 +371,12 @@ impl From<Vec<u8>> for Message {
     }
 }
 
+impl From<Bytes> for Message {
+    fn from(value: Bytes) -> Message {
+        Message::from_bytes(value)
+    }
+}
+
 /*****************************************************************************/
 /*                                                                           */
 /* Hash                                                                      */
@@ -8,8 +8,10 @@ use std::rc::Rc;
 use std::io::Result;
 
 use super::{EndpointTmpl};
-use core::{SocketId, EndpointId, Message};
+use core::{SocketId, EndpointId, Message, PeerCred};
 
+/// Raw endpoint operations implemented by the I/O thread.
+/// Protocols should not need to call it directly and use [`Pipe`](../endpoint/struct.Pipe.html) instead.
 pub trait Network {
     fn connect(&mut self, sid: SocketId, tmpl: &EndpointTmpl) -> Result<EndpointId>;
     fn reconnect(&mut self, sid: SocketId, eid: EndpointId, tmpl: &EndpointTmpl) -> Result<()>;
@@ -17,6 +19,11 @@ pub trait Network {
     fn rebind(&mut self, sid: SocketId, eid: EndpointId, tmpl: &EndpointTmpl) -> Result<()>;
     fn open(&mut self, eid: EndpointId, remote: bool);
     fn close(&mut self, eid: EndpointId, remote: bool);
+    fn pause(&mut self, eid: EndpointId);
+    fn resume(&mut self, eid: EndpointId);
     fn send(&mut self, eid: EndpointId, msg: Rc<Message>);
     fn recv(&mut self, eid: EndpointId);
+    fn local_addr(&self, eid: EndpointId, remote: bool) -> Option<String>;
+    fn peer_addr(&self, eid: EndpointId) -> Option<String>;
+    fn peer_cred(&self, eid: EndpointId) -> Option<PeerCred>;
 }
\ No newline at end of file
@@ -0,0 +1,157 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+//! Buffers shared by all the sockets of a session to receive messages without allocating.
+//!
+//! The transports take the buffers they fill from the pool, and the buffers of the received messages
+//! are given back by [`Socket::recycle`](../../struct.Socket.html#method.recycle).
+//! Messages that are not recycled are simply dropped and their buffers freed,
+//! in which case the transports allocate new ones.
+
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use super::Message;
+
+/// Number of buffers kept by the pool of a session, unless specified otherwise.
+pub const DEFAULT_MAX_BUFFERS: usize = 256;
+
+/// Buffers larger than this are freed rather than kept in the pool.
+pub const MAX_BUFFER_CAPACITY: usize = 1024 * 1024;
+
+/// Pool of reusable buffers, clones share the same buffers.
+#[derive(Clone)]
+pub struct BufferPool {
+    buffers: Arc<Mutex<Vec<Vec<u8>>>>,
+    max_buffers: usize
+}
+
+impl BufferPool {
+    /// Creates an empty pool keeping at most `max_buffers` buffers, zero disables pooling.
+    pub fn new(max_buffers: usize) -> BufferPool {
+        BufferPool {
+            buffers: Arc::new(Mutex::new(Vec::new())),
+            max_buffers: max_buffers
+        }
+    }
+
+    /// Returns an empty buffer able to hold at least `capacity` bytes,
+    /// taken from the pool when possible.
+    pub fn take(&self, capacity: usize) -> Vec<u8> {
+        match self.lock().pop() {
+            Some(mut buffer) => {
+                buffer.reserve(capacity);
+                buffer
+            },
+            None => Vec::with_capacity(capacity)
+        }
+    }
+
+    /// Gives a buffer back to the pool, unless the pool is full or the buffer is too large.
+    pub fn give(&self, mut buffer: Vec<u8>) {
+        if buffer.capacity() == 0 || buffer.capacity() > MAX_BUFFER_CAPACITY {
+            return;
+        }
+
+        let mut buffers = self.lock();
+
+        if buffers.len() < self.max_buffers {
+            buffer.clear();
+            buffers.push(buffer);
+        }
+    }
+
+    /// Gives the header and the body of the message back to the pool,
+    /// unless the body is a payload shared with the application.
+    #[allow(deprecated)]
+    pub fn recycle(&self, msg: Message) {
+        self.give(msg.header);
+        if msg.shared.is_none() {
+            self.give(msg.body);
+        }
+    }
+
+    /// Number of buffers currently available in the pool.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Whether no buffer is currently available in the pool.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> MutexGuard<Vec<Vec<u8>>> {
+        self.buffers.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for BufferPool {
+    fn default() -> BufferPool {
+        BufferPool::new(DEFAULT_MAX_BUFFERS)
+    }
+}
+
+impl fmt::Debug for BufferPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "BufferPool({}/{})", self.len(), self.max_buffers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::Message;
+    use core::bytes::Bytes;
+    use super::*;
+
+    #[test]
+    fn recycled_buffers_are_reused() {
+        let pool = BufferPool::new(4);
+        let body = Vec::with_capacity(100);
+        let ptr = body.as_ptr();
+
+        pool.recycle(Message::from_body(body));
+        assert_eq!(1, pool.len());
+
+        let buffer = pool.take(50);
+
+        assert_eq!(ptr, buffer.as_ptr());
+        assert!(buffer.is_empty());
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn pool_keeps_a_bounded_number_of_buffers() {
+        let pool = BufferPool::new(1);
+
+        pool.give(vec![1, 2, 3]);
+        pool.give(vec![4, 5, 6]);
+        pool.give(Vec::new());
+        pool.give(Vec::with_capacity(MAX_BUFFER_CAPACITY + 1));
+
+        assert_eq!(1, pool.len());
+        assert!(pool.take(0).is_empty());
+    }
+
+    #[test]
+    fn shared_bodies_are_not_recycled() {
+        let pool = BufferPool::new(4);
+        let msg = Message::from_bytes(Bytes::from(vec![1, 2, 3]));
+
+        pool.recycle(msg);
+
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn disabled_pool_keeps_nothing() {
+        let pool = BufferPool::new(0);
+
+        pool.give(vec![1, 2, 3]);
+
+        assert!(pool.is_empty());
+    }
+}
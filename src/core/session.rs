@@ -14,6 +14,7 @@ use sequence::Sequence;
 pub enum Request {
     CreateSocket(socket::ProtocolCtor),
     CreateDevice(SocketId, SocketId),
+    SpawnDevice(SocketId, mpsc::Receiver<socket::Reply>, SocketId, mpsc::Receiver<socket::Reply>),
     CreateProbe(Vec<PollReq>),
     Shutdown
 }
@@ -22,6 +23,7 @@ pub enum Reply {
     Err(io::Error),
     SocketCreated(SocketId, mpsc::Receiver<socket::Reply>),
     DeviceCreated(DeviceId, mpsc::Receiver<device::Reply>),
+    DeviceSpawned(DeviceId, mpsc::Receiver<device::Reply>),
     ProbeCreated(ProbeId, mpsc::Receiver<probe::Reply>),
     Shutdown
 }
@@ -30,6 +32,7 @@ pub struct Session {
     reply_sender: mpsc::Sender<Reply>,
     sockets: SocketCollection,
     devices: DeviceCollection,
+    forwarders: ForwarderCollection,
     probes: ProbeCollection
 }
 
@@ -44,6 +47,12 @@ struct DeviceCollection {
     devices: HashMap<DeviceId, device::Device, BuildIdHasher>
 }
 
+struct ForwarderCollection {
+    ids: Sequence,
+    mapping: HashMap<SocketId, DeviceId, BuildIdHasher>,
+    forwarders: HashMap<DeviceId, device::Forwarder, BuildIdHasher>
+}
+
 struct ProbeCollection {
     ids: Sequence,
     mapping: HashMap<SocketId, ProbeId, BuildIdHasher>,
@@ -56,6 +65,7 @@ impl Session {
             reply_sender: reply_tx,
             sockets: SocketCollection::new(seq.clone()),
             devices: DeviceCollection::new(seq.clone()),
+            forwarders: ForwarderCollection::new(seq.clone()),
             probes: ProbeCollection::new(seq.clone())
         }
     }
@@ -72,8 +82,9 @@ impl Session {
 
     pub fn add_socket(&mut self, protocol_ctor: socket::ProtocolCtor) {
         let (tx, rx) = mpsc::channel();
-        let protocol = protocol_ctor(tx.clone());
-        let id = self.sockets.add(tx, protocol);
+        let (proto_tx, proto_rx) = mpsc::channel();
+        let protocol = protocol_ctor(proto_tx);
+        let id = self.sockets.add(tx, proto_rx, protocol);
 
         self.send_reply(Reply::SocketCreated(id, rx));
     }
@@ -111,6 +122,35 @@ impl Session {
         self.devices.remove(did);
     }
 
+/*****************************************************************************/
+/*                                                                           */
+/* Forwarders                                                                */
+/*                                                                           */
+/*****************************************************************************/
+
+    pub fn add_forwarder(
+        &mut self,
+        left: SocketId, left_rx: mpsc::Receiver<socket::Reply>,
+        right: SocketId, right_rx: mpsc::Receiver<socket::Reply>) {
+
+        let (tx, rx) = mpsc::channel();
+        let id = self.forwarders.add(tx, left, left_rx, right, right_rx);
+
+        self.send_reply(Reply::DeviceSpawned(id, rx));
+    }
+
+    pub fn get_forwarder_mut(&mut self, id: DeviceId) -> Option<&mut device::Forwarder> {
+        self.forwarders.get_forwarder_mut(id)
+    }
+
+    pub fn find_forwarder_mut(&mut self, id: SocketId) -> Option<(&DeviceId, &mut device::Forwarder)> {
+        self.forwarders.find_forwarder_mut(id)
+    }
+
+    pub fn remove_forwarder(&mut self, id: DeviceId) {
+        self.forwarders.remove(id);
+    }
+
 /*****************************************************************************/
 /*                                                                           */
 /* Probes                                                                   */
@@ -151,9 +191,9 @@ impl SocketCollection {
         }
     }
 
-    fn add(&mut self, reply_tx: mpsc::Sender<socket::Reply>, proto: Box<socket::Protocol>) -> SocketId {
+    fn add(&mut self, reply_tx: mpsc::Sender<socket::Reply>, proto_reply_rx: mpsc::Receiver<socket::Reply>, proto: Box<socket::Protocol>) -> SocketId {
         let id = SocketId::from(self.ids.next());
-        let socket = socket::Socket::new(id, reply_tx, proto);
+        let socket = socket::Socket::new(id, reply_tx, proto_reply_rx, proto);
 
         self.sockets.insert(id, socket);
 
@@ -215,6 +255,57 @@ impl DeviceCollection {
     }
 }
 
+/*****************************************************************************/
+/*                                                                           */
+/* Forwarder collection                                                      */
+/*                                                                           */
+/*****************************************************************************/
+
+impl ForwarderCollection {
+    fn new(seq: Sequence) -> ForwarderCollection {
+        ForwarderCollection {
+            ids: seq,
+            mapping: HashMap::default(),
+            forwarders: HashMap::default()
+        }
+    }
+
+    fn add(
+        &mut self,
+        reply_tx: mpsc::Sender<device::Reply>,
+        left: SocketId, left_rx: mpsc::Receiver<socket::Reply>,
+        right: SocketId, right_rx: mpsc::Receiver<socket::Reply>) -> DeviceId {
+
+        let id = DeviceId::from(self.ids.next());
+        let forwarder = device::Forwarder::new(reply_tx, left, left_rx, right, right_rx);
+
+        self.forwarders.insert(id, forwarder);
+        self.mapping.insert(left, id);
+        self.mapping.insert(right, id);
+
+        id
+    }
+
+    fn get_forwarder_mut(&mut self, id: DeviceId) -> Option<&mut device::Forwarder> {
+        self.forwarders.get_mut(&id)
+    }
+
+    fn find_forwarder_mut(&mut self, sid: SocketId) -> Option<(&DeviceId, &mut device::Forwarder)> {
+        if let Some(did) = self.mapping.get(&sid) {
+            self.forwarders.get_mut(did).map(|forwarder| (did, forwarder))
+        } else {
+            None
+        }
+    }
+
+    fn remove(&mut self, id: DeviceId) {
+        if let Some(forwarder) = self.forwarders.remove(&id) {
+            self.mapping.remove(forwarder.get_left_id());
+            self.mapping.remove(forwarder.get_right_id());
+        }
+    }
+}
+
 /*****************************************************************************/
 /*                                                                           */
 /* Probe collection                                                          */
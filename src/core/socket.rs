@@ -4,42 +4,88 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
-use std::collections::HashMap;
-use std::sync::mpsc::Sender;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{Sender, Receiver};
 use std::io;
 use std::time::Duration;
 
-use super::{BuildIdHasher, SocketId, EndpointId, Message, EndpointTmpl, EndpointSpec, EndpointDesc, Scheduled };
+use super::{BuildIdHasher, SocketId, EndpointId, Message, EndpointTmpl, EndpointSpec, EndpointDesc, EndpointInfo, PipeInfo, Scheduled };
 use super::endpoint::{Pipe, Acceptor};
-use super::config::{Config, ConfigOption};
+use super::config::{Config, ConfigOption, ConfigOptionKind};
 use super::context::{Context, Schedulable, Event};
+use proto::ProtocolMismatch;
 use io_error::*;
 
+#[doc(hidden)]
 pub enum Request {
     Connect(String),
     Bind(String),
     Send(Message, bool),
+    SendBatch(Vec<Message>),
     Recv(bool),
+    RecvWithInfo,
+    RecvBatch(usize, Option<Duration>),
     SetOption(ConfigOption),
+    GetOption(ConfigOptionKind),
     Close
 }
 
+/// Replies sent back to the `Socket` facade.
+/// A protocol receives the sender when it is created, see [`Protocol`](trait.Protocol.html),
+/// and must answer each `send` and `recv` call with exactly one reply.
 pub enum Reply {
+    /// The operation failed.
     Err(io::Error),
-    Connect(EndpointId),
-    Bind(EndpointId),
+    #[doc(hidden)]
+    Connect(EndpointId, Arc<Mutex<EndpointInfo>>),
+    #[doc(hidden)]
+    Bind(EndpointId, Arc<Mutex<EndpointInfo>>),
+    /// The message given to `send` has been handed to the pipes.
     Send,
+    /// A message has been received.
     Recv(Message),
-    SetOption
+    #[doc(hidden)]
+    RecvWithInfo(Message, Option<PipeInfo>),
+    #[doc(hidden)]
+    SendBatch(usize),
+    #[doc(hidden)]
+    RecvBatch(Vec<Message>),
+    #[doc(hidden)]
+    SetOption,
+    #[doc(hidden)]
+    GetOption(ConfigOption)
 }
 
+#[doc(hidden)]
 pub struct Socket {
     id: SocketId,
     reply_sender: Sender<Reply>,
+    protocol_replies: Receiver<Reply>,
     protocol: Box<Protocol>,
     pipes: HashMap<EndpointId, Pipe, BuildIdHasher>,
     acceptors: HashMap<EndpointId, Acceptor, BuildIdHasher>,
-    config: Config
+    pipe_infos: HashMap<EndpointId, PipeInfo, BuildIdHasher>,
+    recv_with_info: bool,
+    endpoint_infos: HashMap<EndpointId, Arc<Mutex<EndpointInfo>>, BuildIdHasher>,
+    config: Config,
+    batch: Option<Batch>,
+    pipe_activity: HashMap<EndpointId, bool, BuildIdHasher>,
+    unread_pipes: HashSet<EndpointId, BuildIdHasher>,
+    liveness_check: Option<Scheduled>,
+    pipe_timeouts: HashMap<EndpointId, Scheduled, BuildIdHasher>,
+    accepted_pipes: HashMap<EndpointId, EndpointId, BuildIdHasher>,
+    paused_acceptors: HashSet<EndpointId, BuildIdHasher>,
+    accept_backoffs: HashMap<EndpointId, Scheduled, BuildIdHasher>
+}
+
+// A batch is performed as a sequence of regular operations on the protocol,
+// whose replies are collected into a single one for the facade.
+enum Batch {
+    Send(VecDeque<Message>, usize),
+    SendOnHold(VecDeque<Message>, usize, Option<Scheduled>),
+    Recv(Vec<Message>, usize),
+    RecvOnHold(Vec<Message>, usize)
 }
 
 /*****************************************************************************/
@@ -48,37 +94,76 @@ pub struct Socket {
 /*                                                                           */
 /*****************************************************************************/
 
+/// Behavior of a socket, implemented by each scalability protocol.
+///
+/// A protocol is created on the I/O thread by [`Session::create_socket`](../../struct.Session.html#method.create_socket)
+/// from the `Sender<Reply>` connected to the `Socket` facade, so implementations also provide `From<Sender<Reply>>`.
+/// All the callbacks run on the I/O thread and must not block.
+///
+/// Each call to `send` or `recv` must eventually be answered by exactly one [`Reply`](enum.Reply.html),
+/// either immediately or later from another callback, and the associated timeout, if any,
+/// must then be cancelled through the context.
+/// Pipe readiness is reported one operation at a time: after `on_send_ready`,
+/// a single message can be sent on the pipe, which is later acknowledged by `on_send_ack`.
+/// The same goes for `on_recv_ready`, `Pipe::recv` and `on_recv_ack`.
+///
+/// See `examples/custom_protocol.rs` for a complete implementation.
 pub trait Protocol {
+    /// Identifier exchanged with the peers during the handshake.
     fn id(&self) -> u16;
+    /// Identifier the peers must present for the connection to be accepted.
     fn peer_id(&self) -> u16;
 
+    /// A new pipe has completed its handshake, it is not yet ready to send or receive.
     fn add_pipe(&mut self, ctx: &mut Context, eid: EndpointId, pipe: Pipe);
+    /// The pipe has been closed or lost, the protocol must give it back.
     fn remove_pipe(&mut self, ctx: &mut Context, eid: EndpointId) -> Option<Pipe>;
 
+    /// The user wants to send a message.
     fn send(&mut self, ctx: &mut Context, msg: Message, timeout: Option<Scheduled>);
+    /// The message previously sent on the pipe has been written.
     fn on_send_ack(&mut self, ctx: &mut Context, eid: EndpointId);
+    /// The pending send operation has expired, the protocol must reply with a `TimedOut` error.
     fn on_send_timeout(&mut self, ctx: &mut Context);
+    /// The pipe can accept a message.
     fn on_send_ready(&mut self, ctx: &mut Context, eid: EndpointId);
+    /// The pipe can no longer accept a message.
     fn on_send_not_ready(&mut self, ctx: &mut Context, eid: EndpointId);
     
+    /// The user wants to receive a message.
     fn recv(&mut self, ctx: &mut Context, timeout: Option<Scheduled>);
+    /// The message requested from the pipe has been read.
     fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, msg: Message);
+    /// The pending recv operation has expired, the protocol must reply with a `TimedOut` error.
     fn on_recv_timeout(&mut self, ctx: &mut Context);
+    /// The pipe has a message available.
     fn on_recv_ready(&mut self, ctx: &mut Context, eid: EndpointId);
+    /// The pipe no longer has a message available.
     fn on_recv_not_ready(&mut self, ctx: &mut Context, eid: EndpointId);
 
+    /// Whether a `send` would complete without waiting.
     fn is_send_ready(&self) -> bool;
+    /// Whether a `recv` would complete without waiting.
     fn is_recv_ready(&self) -> bool;
 
+    /// Applies a protocol specific option.
     fn set_option(&mut self, _: ConfigOption) -> io::Result<()> {
         Err(invalid_input_io_error("option not supported"))
     }
+    /// Reads a protocol specific option.
+    fn get_option(&self, _: ConfigOptionKind) -> io::Result<ConfigOption> {
+        Err(invalid_input_io_error("option not supported"))
+    }
+    /// A timer scheduled by the protocol has elapsed, see [`Schedulable::Custom`](../context/enum.Schedulable.html#variant.Custom).
     fn on_timer_tick(&mut self, _: &mut Context, _: Schedulable) {
     }
+    #[doc(hidden)]
     fn on_device_plugged(&mut self, _: &mut Context) {}
+    /// The socket is being closed, the protocol should close all its pipes.
     fn close(&mut self, ctx: &mut Context);
 }
 
+#[doc(hidden)]
 pub type ProtocolCtor = Box<Fn(Sender<Reply>) -> Box<Protocol> + Send>;
 
 /*****************************************************************************/
@@ -88,14 +173,28 @@ pub type ProtocolCtor = Box<Fn(Sender<Reply>) -> Box<Protocol> + Send>;
 /*****************************************************************************/
 
 impl Socket {
-    pub fn new(id: SocketId, reply_tx: Sender<Reply>, proto: Box<Protocol>) -> Socket {
+    /// Creates a socket replying to the facade through `reply_tx`,
+    /// the protocol replying to the socket through the sender of `proto_reply_rx`.
+    pub fn new(id: SocketId, reply_tx: Sender<Reply>, proto_reply_rx: Receiver<Reply>, proto: Box<Protocol>) -> Socket {
         Socket {
             id: id,
             reply_sender: reply_tx,
+            protocol_replies: proto_reply_rx,
             protocol: proto,
             pipes: HashMap::default(),
             acceptors: HashMap::default(),
-            config: Config::default()
+            pipe_infos: HashMap::default(),
+            recv_with_info: false,
+            endpoint_infos: HashMap::default(),
+            config: Config::default(),
+            batch: None,
+            pipe_activity: HashMap::default(),
+            unread_pipes: HashSet::default(),
+            liveness_check: None,
+            pipe_timeouts: HashMap::default(),
+            accepted_pipes: HashMap::default(),
+            paused_acceptors: HashSet::default(),
+            accept_backoffs: HashMap::default()
         }
     }
 
@@ -110,6 +209,43 @@ impl Socket {
         let _ = self.reply_sender.send(reply);
     }
 
+    /// Hands the replies of the protocol over to the facade,
+    /// or to the batch in progress, if any.
+    pub fn forward_replies(&mut self, ctx: &mut Context) {
+        while let Ok(reply) = self.protocol_replies.try_recv() {
+            self.on_operation_reply(ctx, reply);
+        }
+    }
+
+    fn on_operation_reply(&mut self, ctx: &mut Context, reply: Reply) {
+        let reply = match reply {
+            Reply::Recv(msg) if self.recv_with_info => self.describe_origin(msg),
+            other => other
+        };
+
+        match reply {
+            Reply::Recv(_) | Reply::RecvWithInfo(..) | Reply::Err(_) => self.recv_with_info = false,
+            _ => {}
+        }
+
+        match self.batch.take() {
+            None => self.send_reply(reply),
+            Some(Batch::Send(msgs, sent)) => self.on_send_batch_reply(ctx, msgs, sent, reply),
+            Some(Batch::Recv(msgs, max)) => self.on_recv_batch_reply(ctx, msgs, max, reply),
+            other => {
+                self.batch = other;
+                self.send_reply(reply);
+            }
+        }
+    }
+
+    // The pipe is described as long as it is open, whether the message was queued or just received
+    fn describe_origin(&self, msg: Message) -> Reply {
+        let info = msg.get_origin().and_then(|eid| self.pipe_infos.get(&eid).cloned());
+
+        Reply::RecvWithInfo(msg, info)
+    }
+
     pub fn poll(&self, ctx: &mut Context) {
         ctx.raise(Event::CanRecv(self.protocol.is_recv_ready()));
         ctx.raise(Event::CanSend(self.protocol.is_send_ready()));
@@ -126,7 +262,9 @@ impl Socket {
             send_priority: self.config.send_priority,
             recv_priority: self.config.recv_priority,
             tcp_no_delay: self.config.tcp_no_delay,
-            recv_max_size: self.config.recv_max_size
+            tcp_keepalive: self.config.tcp_keepalive,
+            recv_max_size: self.config.recv_max_size,
+            max_connections: self.config.max_connections
         }
     }
 
@@ -160,10 +298,20 @@ impl Socket {
     }
 
     fn on_connect_success(&mut self, ctx: &mut Context, eid: EndpointId, spec: EndpointSpec) {
+        let info = Arc::new(Mutex::new(EndpointInfo {
+            url: spec.url.clone(),
+            local_addr: ctx.local_addr(eid, true),
+            peer_addr: ctx.peer_addr(eid)
+        }));
+        self.endpoint_infos.insert(eid, info.clone());
+        self.insert_pipe_info(ctx, eid, &spec.url);
+
         let pipe = self.connect_pipe(eid, spec);
+        let timeout = self.config.connect_timeout;
 
         self.insert_pipe(ctx, eid, pipe);
-        self.send_reply(Reply::Connect(eid));
+        self.schedule_pipe_timeout(ctx, eid, Schedulable::ConnectTimeout(eid), timeout);
+        self.send_reply(Reply::Connect(eid, info));
     }
 
     fn on_connect_error(&mut self, err: io::Error) {
@@ -192,7 +340,18 @@ impl Socket {
     }
 
     fn on_reconnect_success(&mut self, ctx: &mut Context, eid: EndpointId, spec: EndpointSpec) {
+        // Each connection may use other addresses, e.g. a new local port
+        if let Some(info) = self.endpoint_infos.get(&eid) {
+            if let Ok(mut info) = info.lock() {
+                info.local_addr = ctx.local_addr(eid, true);
+                info.peer_addr = ctx.peer_addr(eid);
+            }
+        }
+        self.insert_pipe_info(ctx, eid, &spec.url);
+        let timeout = self.config.connect_timeout;
+
         self.insert_pipe(ctx, eid, Pipe::from_spec(eid, spec));
+        self.schedule_pipe_timeout(ctx, eid, Schedulable::ConnectTimeout(eid), timeout);
     }
 
     fn on_reconnect_error(&mut self, ctx: &mut Context, eid: EndpointId, spec: EndpointSpec) {
@@ -214,13 +373,26 @@ impl Socket {
         };
     }
 
-    fn on_bind_success(&mut self, ctx: &mut Context, eid: EndpointId, spec: EndpointSpec) {
+    fn on_bind_success(&mut self, ctx: &mut Context, eid: EndpointId, mut spec: EndpointSpec) {
+        let local_addr = ctx.local_addr(eid, false);
+
+        // When the OS picked the address, e.g. an ephemeral port,
+        // keep it so that a rebind will not move the endpoint elsewhere.
+        if let Some(ref addr) = local_addr {
+            spec.url = resolve_url(&spec.url, addr);
+        }
+
+        let info = Arc::new(Mutex::new(EndpointInfo {
+            url: spec.url.clone(),
+            local_addr: local_addr,
+            peer_addr: None
+        }));
         let acceptor = self.connect_acceptor(eid, spec);
 
         acceptor.open(ctx);
 
         self.acceptors.insert(eid, acceptor);
-        self.send_reply(Reply::Bind(eid));
+        self.send_reply(Reply::Bind(eid, info));
     }
 
     fn on_bind_error(&mut self, err: io::Error) {
@@ -264,28 +436,69 @@ impl Socket {
 /*                                                                           */
 /*****************************************************************************/
 
+    pub fn on_pipe_connected(&mut self, ctx: &mut Context, eid: EndpointId) {
+        let timeout = self.config.handshake_timeout;
+
+        self.schedule_pipe_timeout(ctx, eid, Schedulable::HandshakeTimeout(eid), timeout);
+    }
+
     pub fn on_pipe_opened(&mut self, ctx: &mut Context, eid: EndpointId) {
+        self.cancel_pipe_timeout(ctx, eid);
+
         if let Some(pipe) = self.pipes.remove(&eid) {
+            self.pipe_activity.insert(eid, true);
             self.protocol.add_pipe(ctx, eid, pipe);
         }
     }
 
     pub fn on_pipe_accepted(&mut self, ctx: &mut Context, aid: EndpointId, eid: EndpointId) {
+        // Several connections may have been accepted at once, before the acceptor got paused
+        if self.is_acceptor_full(aid) {
+            self.accept_pipe(aid, eid).close(ctx);
+            return;
+        }
+
+        let url = self.acceptors.get(&aid).and_then(|acceptor| acceptor.get_url()).map(|url| url.to_owned());
+        if let Some(url) = url {
+            self.insert_pipe_info(ctx, eid, &url);
+        }
+
         let pipe = self.accept_pipe(aid, eid);
 
         self.insert_pipe(ctx, eid, pipe);
+        self.accepted_pipes.insert(eid, aid);
+        self.update_acceptor(ctx, aid);
     }
 
     pub fn close_pipe(&mut self, ctx: &mut Context, eid: EndpointId) {
+        self.endpoint_infos.remove(&eid);
         let _ = self.remove_pipe(ctx, eid);
     }
 
-    pub fn on_pipe_error(&mut self, ctx: &mut Context, eid: EndpointId, _: io::Error) {
+    pub fn on_pipe_error(&mut self, ctx: &mut Context, eid: EndpointId, err: io::Error) {
+        if let Some(mismatch) = ProtocolMismatch::from_io_error(&err) {
+            return self.on_pipe_rejected(ctx, eid, mismatch);
+        }
+
         if let Some(spec) = self.remove_pipe(ctx, eid) {
             self.schedule_reconnect(ctx, eid, spec);
         }
     }
 
+    // The peer is reachable but will never be able to talk with this socket,
+    // retrying is likely pointless unless the peer is replaced.
+    fn on_pipe_rejected(&mut self, ctx: &mut Context, eid: EndpointId, mismatch: ProtocolMismatch) {
+        let peer = self.pipe_infos.get(&eid).and_then(|info| info.peer_addr()).unwrap_or("unknown peer").to_owned();
+
+        error!("[{:?}] ep {:?} to {} closed: {}", ctx, eid, peer, mismatch);
+
+        if let Some(spec) = self.remove_pipe(ctx, eid) {
+            if self.config.retry_on_protocol_mismatch {
+                self.schedule_reconnect(ctx, eid, spec);
+            }
+        }
+    }
+
     fn insert_pipe(&mut self, ctx: &mut Context, eid: EndpointId, pipe: Pipe) {
         pipe.open(ctx);
 
@@ -293,6 +506,14 @@ impl Socket {
     }
 
     fn remove_pipe(&mut self, ctx: &mut Context, eid: EndpointId) -> Option<EndpointSpec> {
+        self.pipe_infos.remove(&eid);
+        self.pipe_activity.remove(&eid);
+        self.unread_pipes.remove(&eid);
+        self.cancel_pipe_timeout(ctx, eid);
+        if let Some(aid) = self.accepted_pipes.remove(&eid) {
+            self.update_acceptor(ctx, aid);
+        }
+
         if let Some(pipe) = self.pipes.remove(&eid) {
             return pipe.close(ctx)
         }
@@ -302,6 +523,43 @@ impl Socket {
         None
     }
 
+    // Replaces the timer of the pipe, if any, by a new one
+    fn schedule_pipe_timeout(&mut self, ctx: &mut Context, eid: EndpointId, task: Schedulable, delay: Option<Duration>) {
+        self.cancel_pipe_timeout(ctx, eid);
+
+        if let Some(delay) = delay {
+            if let Ok(timeout) = ctx.schedule(task, delay) {
+                self.pipe_timeouts.insert(eid, timeout);
+            }
+        }
+    }
+
+    fn cancel_pipe_timeout(&mut self, ctx: &mut Context, eid: EndpointId) {
+        if let Some(timeout) = self.pipe_timeouts.remove(&eid) {
+            ctx.cancel(timeout);
+        }
+    }
+
+    pub fn on_connect_timeout(&mut self, ctx: &mut Context, eid: EndpointId) {
+        if self.pipe_timeouts.remove(&eid).is_some() {
+            self.on_pipe_error(ctx, eid, timedout_io_error("connect timed out"));
+        }
+    }
+
+    pub fn on_handshake_timeout(&mut self, ctx: &mut Context, eid: EndpointId) {
+        if self.pipe_timeouts.remove(&eid).is_some() {
+            self.on_pipe_error(ctx, eid, timedout_io_error("handshake timed out"));
+        }
+    }
+
+    // Collected once, when the pipe is created, for the messages received with their info
+    fn insert_pipe_info(&mut self, ctx: &mut Context, eid: EndpointId, url: &str) {
+        let scheme = url.find("://").map_or(url, |index| &url[..index]);
+        let info = PipeInfo::new(eid, scheme.to_owned(), ctx.peer_addr(eid), ctx.peer_cred(eid));
+
+        self.pipe_infos.insert(eid, info);
+    }
+
     fn connect_pipe(&self, eid: EndpointId, spec: EndpointSpec) -> Pipe {
         Pipe::from_spec(eid, spec)
     }
@@ -316,7 +574,9 @@ impl Socket {
             send_priority: send_prio,
             recv_priority: recv_prio,
             tcp_no_delay: self.config.tcp_no_delay,
-            recv_max_size: self.config.recv_max_size
+            tcp_keepalive: self.config.tcp_keepalive,
+            recv_max_size: self.config.recv_max_size,
+            max_connections: None
         };
 
         Pipe::new_accepted(eid, desc)
@@ -334,6 +594,45 @@ impl Socket {
         }
     }
 
+    // The listener is kept, accepting is retried once some resources may have been released
+    pub fn on_transient_acceptor_error(&mut self, ctx: &mut Context, eid: EndpointId, err: &io::Error) {
+        info!("[{:?}] acceptor {:?} backs off: {}", ctx, eid, err);
+
+        if !self.accept_backoffs.contains_key(&eid) {
+            if let Ok(backoff) = ctx.schedule(Schedulable::ResumeAccept(eid), self.config.retry_ivl) {
+                self.accept_backoffs.insert(eid, backoff);
+            }
+        }
+
+        self.update_acceptor(ctx, eid);
+    }
+
+    pub fn on_accept_backoff_elapsed(&mut self, ctx: &mut Context, eid: EndpointId) {
+        if self.accept_backoffs.remove(&eid).is_some() {
+            self.update_acceptor(ctx, eid);
+        }
+    }
+
+    fn is_acceptor_full(&self, eid: EndpointId) -> bool {
+        match self.acceptors.get(&eid).and_then(|acceptor| acceptor.get_max_connections()) {
+            Some(max) => self.accepted_pipes.values().filter(|&&aid| aid == eid).count() >= max,
+            None => false
+        }
+    }
+
+    // Pauses the acceptor while it is backing off or has reached its connection limit, resumes it otherwise
+    fn update_acceptor(&mut self, ctx: &mut Context, eid: EndpointId) {
+        let pause = self.accept_backoffs.contains_key(&eid) || self.is_acceptor_full(eid);
+
+        if let Some(acceptor) = self.acceptors.get(&eid) {
+            if pause && self.paused_acceptors.insert(eid) {
+                acceptor.pause(ctx);
+            } else if !pause && self.paused_acceptors.remove(&eid) {
+                acceptor.resume(ctx);
+            }
+        }
+    }
+
     pub fn close_acceptor(&mut self, ctx: &mut Context, eid: EndpointId) {
         let _ = self.remove_acceptor(ctx, eid);
     }
@@ -345,6 +644,11 @@ impl Socket {
     }
 
     fn remove_acceptor(&mut self, ctx: &mut Context, eid: EndpointId) -> Option<EndpointSpec> {
+        self.paused_acceptors.remove(&eid);
+        if let Some(backoff) = self.accept_backoffs.remove(&eid) {
+            ctx.cancel(backoff);
+        }
+
         self.acceptors.remove(&eid).map_or(None, |acceptor| acceptor.close(ctx))
     }
 
@@ -365,7 +669,7 @@ impl Socket {
 
             match ctx.schedule(task, delay) {
                 Ok(timeout) => self.protocol.send(ctx, msg, Some(timeout)),
-                Err(e) => self.send_reply(Reply::Err(e))
+                Err(e) => self.on_operation_reply(ctx, Reply::Err(e))
             }
         } else {
             self.protocol.send(ctx, msg, None);
@@ -383,14 +687,78 @@ impl Socket {
         }
     }
 
+    /// Sends the messages one after the other, as many regular sends would do,
+    /// and replies once with the number of messages sent.
+    pub fn send_batch(&mut self, ctx: &mut Context, msgs: Vec<Message>) {
+        #[cfg(debug_assertions)] debug!("[{:?}] send_batch {}", ctx, msgs.len());
+        self.send_next_in_batch(ctx, msgs.into_iter().collect(), 0);
+    }
+
+    fn send_next_in_batch(&mut self, ctx: &mut Context, mut msgs: VecDeque<Message>, sent: usize) {
+        match msgs.pop_front() {
+            Some(msg) => {
+                self.batch = Some(Batch::Send(msgs, sent));
+                self.send(ctx, msg);
+            },
+            None => self.send_reply(Reply::SendBatch(sent))
+        }
+    }
+
+    // The next message waits for the protocol to be send ready again, otherwise protocols
+    // that do not block, such as pub, would drop it for the pipes still busy with the previous one.
+    fn continue_send_batch(&mut self, ctx: &mut Context, msgs: VecDeque<Message>, sent: usize) {
+        if msgs.is_empty() || self.protocol.is_send_ready() {
+            return self.send_next_in_batch(ctx, msgs, sent);
+        }
+
+        match self.get_send_timeout().map(|delay| ctx.schedule(Schedulable::SendTimeout, delay)) {
+            Some(Ok(timeout)) => self.batch = Some(Batch::SendOnHold(msgs, sent, Some(timeout))),
+            Some(Err(_)) => self.send_reply(Reply::SendBatch(sent)),
+            None => self.batch = Some(Batch::SendOnHold(msgs, sent, None))
+        }
+    }
+
+    /// The protocol became send ready, resumes the batch waiting for it, if any.
+    pub fn resume_send_batch(&mut self, ctx: &mut Context) {
+        match self.batch.take() {
+            Some(Batch::SendOnHold(msgs, sent, timeout)) => {
+                if let Some(sched) = timeout {
+                    ctx.cancel(sched);
+                }
+                self.send_next_in_batch(ctx, msgs, sent);
+            },
+            other => self.batch = other
+        }
+    }
+
+    // A failure ends the batch, it is reported only when nothing could be sent.
+    fn on_send_batch_reply(&mut self, ctx: &mut Context, msgs: VecDeque<Message>, sent: usize, reply: Reply) {
+        match reply {
+            Reply::Send => self.continue_send_batch(ctx, msgs, sent + 1),
+            Reply::Err(e) => if sent == 0 {
+                self.send_reply(Reply::Err(e))
+            } else {
+                self.send_reply(Reply::SendBatch(sent))
+            },
+            other => self.send_reply(other)
+        }
+    }
+
     pub fn on_send_ack(&mut self, ctx: &mut Context, eid: EndpointId) {
         #[cfg(debug_assertions)] debug!("[{:?}] send ack from ep {:?}", ctx, eid);
+        self.mark_pipe_active(eid);
         self.protocol.on_send_ack(ctx, eid);
     }
 
     pub fn on_send_timeout(&mut self, ctx: &mut Context) {
         #[cfg(debug_assertions)] debug!("[{:?}] send timeout", ctx);
-        self.protocol.on_send_timeout(ctx);
+        match self.batch.take() {
+            Some(Batch::SendOnHold(_, sent, _)) => self.send_reply(Reply::SendBatch(sent)),
+            other => {
+                self.batch = other;
+                self.protocol.on_send_timeout(ctx);
+            }
+        }
     }
 
     fn get_send_timeout(&self) -> Option<Duration> {
@@ -399,6 +767,8 @@ impl Socket {
 
     pub fn on_send_ready(&mut self, ctx: &mut Context, eid: EndpointId, ready: bool) {
         #[cfg(debug_assertions)] debug!("[{:?}] ep {:?} send ready: {} ", ctx, eid, ready);
+        self.mark_pipe_active(eid);
+
         if ready {
             self.protocol.on_send_ready(ctx, eid)
         } else {
@@ -414,18 +784,94 @@ impl Socket {
 
     pub fn recv(&mut self, ctx: &mut Context) {
         #[cfg(debug_assertions)] debug!("[{:?}] recv", ctx);
-        if let Some(delay) = self.get_recv_timeout() {
+        let timeout = self.get_recv_timeout();
+
+        self.recv_with_timeout(ctx, timeout);
+    }
+
+    pub fn recv_with_info(&mut self, ctx: &mut Context) {
+        self.recv_with_info = true;
+        self.recv(ctx);
+    }
+
+    fn recv_with_timeout(&mut self, ctx: &mut Context, timeout: Option<Duration>) {
+        if let Some(delay) = timeout {
             let task = Schedulable::RecvTimeout;
 
             match ctx.schedule(task, delay) {
                 Ok(timeout) => self.protocol.recv(ctx, Some(timeout)),
-                Err(e) => self.send_reply(Reply::Err(e))
+                Err(e) => self.on_operation_reply(ctx, Reply::Err(e))
             }
         } else {
             self.protocol.recv(ctx, None);
         }
     }
 
+    /// Receives up to `max` messages, waiting at most `timeout` for the first one only,
+    /// and replies once with it and the messages that were already available.
+    pub fn recv_batch(&mut self, ctx: &mut Context, max: usize, timeout: Option<Duration>) {
+        #[cfg(debug_assertions)] debug!("[{:?}] recv_batch {}", ctx, max);
+        if max == 0 {
+            self.send_reply(Reply::RecvBatch(Vec::new()));
+        } else {
+            self.batch = Some(Batch::Recv(Vec::with_capacity(max), max));
+            self.recv_with_timeout(ctx, timeout);
+        }
+    }
+
+    // A failure ends the batch, it is reported only when nothing was received.
+    fn on_recv_batch_reply(&mut self, ctx: &mut Context, mut msgs: Vec<Message>, max: usize, reply: Reply) {
+        match reply {
+            Reply::Recv(msg) => {
+                msgs.push(msg);
+                self.continue_recv_batch(ctx, msgs, max);
+            },
+            Reply::Err(e) => if msgs.is_empty() {
+                self.send_reply(Reply::Err(e))
+            } else {
+                self.send_reply(Reply::RecvBatch(msgs))
+            },
+            other => self.send_reply(other)
+        }
+    }
+
+    // Only the first message is waited for, the batch then takes the ones the protocol can deliver now.
+    // When the protocol is not ready, the batch is held until the controller calls flush_recv_batch,
+    // once the pipes have had a chance to announce the messages that are already readable.
+    fn continue_recv_batch(&mut self, ctx: &mut Context, msgs: Vec<Message>, max: usize) {
+        if msgs.len() == max {
+            self.send_reply(Reply::RecvBatch(msgs));
+        } else if self.protocol.is_recv_ready() {
+            self.batch = Some(Batch::Recv(msgs, max));
+            self.protocol.recv(ctx, None);
+        } else {
+            self.batch = Some(Batch::RecvOnHold(msgs, max));
+        }
+    }
+
+    /// Whether a receive batch is waiting to be flushed.
+    pub fn has_recv_batch_on_hold(&self) -> bool {
+        match self.batch {
+            Some(Batch::RecvOnHold(..)) => true,
+            _ => false
+        }
+    }
+
+    fn resume_recv_batch(&mut self, ctx: &mut Context) {
+        match self.batch.take() {
+            Some(Batch::RecvOnHold(msgs, max)) => self.continue_recv_batch(ctx, msgs, max),
+            other => self.batch = other
+        }
+    }
+
+    /// No more message was available for the batch waiting for them, if any, replies with what it has.
+    pub fn flush_recv_batch(&mut self, _: &mut Context) {
+        match self.batch.take() {
+            Some(Batch::RecvOnHold(msgs, _)) => self.send_reply(Reply::RecvBatch(msgs)),
+            other => self.batch = other
+        }
+    }
+
     pub fn try_recv(&mut self, ctx: &mut Context) {
         #[cfg(debug_assertions)] debug!("[{:?}] try_recv", ctx);
         if self.protocol.is_recv_ready() {
@@ -439,7 +885,10 @@ impl Socket {
 
     pub fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, msg: Message) {
         #[cfg(debug_assertions)] debug!("[{:?}] recv ack from ep {:?}", ctx, eid);
-        self.protocol.on_recv_ack(ctx, eid, msg);
+        self.mark_pipe_active(eid);
+        self.unread_pipes.remove(&eid);
+
+        self.protocol.on_recv_ack(ctx, eid, msg.with_origin(Some(eid)));
     }
 
     pub fn on_recv_timeout(&mut self, ctx: &mut Context) {
@@ -453,25 +902,84 @@ impl Socket {
 
     pub fn on_recv_ready(&mut self, ctx: &mut Context, eid: EndpointId, ready: bool) {
         #[cfg(debug_assertions)] debug!("[{:?}] ep {:?} recv ready: {}", ctx, eid, ready);
+        self.mark_pipe_active(eid);
+
         if ready {
-            self.protocol.on_recv_ready(ctx, eid)
+            self.unread_pipes.insert(eid);
+            self.protocol.on_recv_ready(ctx, eid);
+
+            if self.protocol.is_recv_ready() {
+                self.resume_recv_batch(ctx);
+            }
         } else {
+            self.unread_pipes.remove(&eid);
             self.protocol.on_recv_not_ready(ctx, eid)
         }
     }
 
+/*****************************************************************************/
+/*                                                                           */
+/* liveness                                                                  */
+/*                                                                           */
+/*****************************************************************************/
+
+    fn mark_pipe_active(&mut self, eid: EndpointId) {
+        if let Some(active) = self.pipe_activity.get_mut(&eid) {
+            *active = true;
+        }
+    }
+
+    fn schedule_liveness_check(&mut self, ctx: &mut Context) {
+        if let Some(sched) = self.liveness_check.take() {
+            ctx.cancel(sched);
+        }
+        if let Some(delay) = self.config.liveness_timeout {
+            self.liveness_check = ctx.schedule(Schedulable::LivenessCheck, delay).ok();
+        }
+    }
+
+    /// Closes the pipes that have been idle since the previous check,
+    /// the others being given a new period to prove they are still alive.
+    /// A pipe holding input the protocol did not read yet is not idle, its peer is just ahead of us.
+    pub fn on_liveness_check(&mut self, ctx: &mut Context) {
+        let idle_pipes: Vec<EndpointId> = self.pipe_activity.iter()
+            .filter(|&(_, active)| !*active)
+            .map(|(eid, _)| *eid)
+            .collect();
+
+        for eid in idle_pipes {
+            #[cfg(debug_assertions)] debug!("[{:?}] ep {:?} is idle, closing it", ctx, eid);
+            self.on_pipe_error(ctx, eid, timedout_io_error("no activity on the pipe"));
+        }
+        for (eid, active) in self.pipe_activity.iter_mut() {
+            *active = self.unread_pipes.contains(eid);
+        }
+
+        self.liveness_check = None;
+        self.schedule_liveness_check(ctx);
+    }
+
 /*****************************************************************************/
 /*                                                                           */
 /* options                                                                   */
 /*                                                                           */
 /*****************************************************************************/
 
-    pub fn set_option(&mut self, _: &mut Context, opt: ConfigOption) {
+    pub fn set_option(&mut self, ctx: &mut Context, opt: ConfigOption) {
+        let restart_liveness_check = match opt {
+            ConfigOption::LivenessTimeout(_) => true,
+            _ => false
+        };
         let res = if opt.is_generic() {
             self.config.set(opt)
         } else {
             self.protocol.set_option(opt)
         };
+
+        if restart_liveness_check && res.is_ok() {
+            self.schedule_liveness_check(ctx);
+        }
+
         let reply = match res {
             Ok(()) => Reply::SetOption,
             Err(e) => Reply::Err(e)
@@ -480,6 +988,20 @@ impl Socket {
         self.send_reply(reply);
     }
 
+    pub fn get_option(&mut self, _: &mut Context, kind: ConfigOptionKind) {
+        let res = if kind.is_generic() {
+            self.config.get(kind)
+        } else {
+            self.protocol.get_option(kind)
+        };
+        let reply = match res {
+            Ok(opt) => Reply::GetOption(opt),
+            Err(e) => Reply::Err(e)
+        };
+
+        self.send_reply(reply);
+    }
+
     pub fn on_timer_tick(&mut self, ctx: &mut Context, task: Schedulable) {
         self.protocol.on_timer_tick(ctx, task)
     }
@@ -489,6 +1011,20 @@ impl Socket {
     }
 
     pub fn close(&mut self, ctx: &mut Context) {
+        self.batch = None;
+        self.endpoint_infos.clear();
+        if let Some(sched) = self.liveness_check.take() {
+            ctx.cancel(sched);
+        }
+        for (_, timeout) in self.pipe_timeouts.drain() {
+            ctx.cancel(timeout);
+        }
+        for (_, backoff) in self.accept_backoffs.drain() {
+            ctx.cancel(backoff);
+        }
+        self.accepted_pipes.clear();
+        self.paused_acceptors.clear();
+
         for (_, pipe) in self.pipes.drain() {
             pipe.close(ctx);
         }
@@ -502,6 +1038,13 @@ impl Socket {
     }
 }
 
+fn resolve_url(url: &str, addr: &str) -> String {
+    match url.find("://") {
+        Some(index) => format!("{}://{}", &url[..index], addr),
+        None => url.to_owned()
+    }
+}
+
 /*****************************************************************************/
 /*                                                                           */
 /* tests                                                                     */
@@ -519,7 +1062,7 @@ mod tests {
     use super::*;
     use core::network;
     use core::context::*;
-    use core::{SocketId, EndpointId, Message, EndpointTmpl, Scheduled};
+    use core::{SocketId, EndpointId, Message, EndpointTmpl, PeerCred, Scheduled};
     use core::endpoint::Pipe;
 
     struct TestProto;
@@ -563,10 +1106,23 @@ mod tests {
         }
         fn close(&mut self, _: EndpointId, _: bool) {
         }
+        fn pause(&mut self, _: EndpointId) {
+        }
+        fn resume(&mut self, _: EndpointId) {
+        }
         fn send(&mut self, _: EndpointId, _: Rc<Message>) {
         }
         fn recv(&mut self, _: EndpointId) {
         }
+        fn local_addr(&self, _: EndpointId, _: bool) -> Option<String> {
+            None
+        }
+        fn peer_addr(&self, _: EndpointId) -> Option<String> {
+            None
+        }
+        fn peer_cred(&self, _: EndpointId) -> Option<PeerCred> {
+            None
+        }
     }
 
     impl Scheduler for FailingNetwork {
@@ -592,9 +1148,10 @@ mod tests {
     fn when_connect_fails() {
         let id = SocketId::from(1);
         let (tx, rx) = mpsc::channel();
+        let (_, proto_rx) = mpsc::channel();
         let proto = Box::new(TestProto) as Box<Protocol>;
         let mut network = FailingNetwork;
-        let mut socket = Socket::new(id, tx, proto);
+        let mut socket = Socket::new(id, tx, proto_rx, proto);
 
         socket.connect(&mut network, String::from("test://fake"));
 
@@ -625,8 +1182,13 @@ mod tests {
         }
         fn open(&mut self, _: EndpointId, _: bool) {}
         fn close(&mut self, _: EndpointId, _: bool) {}
+        fn pause(&mut self, _: EndpointId) {}
+        fn resume(&mut self, _: EndpointId) {}
         fn send(&mut self, _: EndpointId, _: Rc<Message>) {}
         fn recv(&mut self, _: EndpointId) {}
+        fn local_addr(&self, _: EndpointId, _: bool) -> Option<String> { None }
+        fn peer_addr(&self, _: EndpointId) -> Option<String> { None }
+        fn peer_cred(&self, _: EndpointId) -> Option<PeerCred> { None }
     }
 
     impl Scheduler for WorkingNetwork {
@@ -653,16 +1215,17 @@ mod tests {
     fn when_connect_succeeds() {
         let id = SocketId::from(1);
         let (tx, rx) = mpsc::channel();
+        let (_, proto_rx) = mpsc::channel();
         let proto = Box::new(TestProto) as Box<Protocol>;
         let mut network = WorkingNetwork(EndpointId::from(1));
-        let mut socket = Socket::new(id, tx, proto);
+        let mut socket = Socket::new(id, tx, proto_rx, proto);
 
         socket.connect(&mut network, String::from("test://fake"));
 
         let reply = rx.recv().expect("Socket should have sent a reply to the connect request");
 
         match reply {
-            Reply::Connect(eid) => {
+            Reply::Connect(eid, _) => {
                 assert_eq!(EndpointId::from(1), eid);
             },
             _ => {
@@ -670,4 +1233,66 @@ mod tests {
             },
         }
     }
+
+    // Keeps the received message until it is asked for, like a protocol with a queue
+    struct HoldingProto {
+        reply_tx: mpsc::Sender<Reply>,
+        held: Option<Message>
+    }
+
+    impl Protocol for HoldingProto {
+        fn id(&self) -> u16 {0}
+        fn peer_id(&self) -> u16 {0}
+        fn add_pipe(&mut self, _: &mut Context, _: EndpointId, _: Pipe) {}
+        fn remove_pipe(&mut self, _: &mut Context, _: EndpointId) -> Option<Pipe> {None}
+        fn send(&mut self, _: &mut Context, _: Message, _: Option<Scheduled>) {}
+        fn on_send_ack(&mut self, _: &mut Context, _: EndpointId) {}
+        fn on_send_timeout(&mut self, _: &mut Context) {}
+        fn on_send_ready(&mut self, _: &mut Context, _: EndpointId) {}
+        fn on_send_not_ready(&mut self, _: &mut Context, _: EndpointId) {}
+        fn recv(&mut self, _: &mut Context, _: Option<Scheduled>) {
+            if let Some(msg) = self.held.take() {
+                let _ = self.reply_tx.send(Reply::Recv(msg));
+            }
+        }
+        fn on_recv_ack(&mut self, _: &mut Context, _: EndpointId, msg: Message) {
+            self.held = Some(msg);
+        }
+        fn on_recv_timeout(&mut self, _: &mut Context) {}
+        fn on_recv_ready(&mut self, _: &mut Context, _: EndpointId) {}
+        fn on_recv_not_ready(&mut self, _: &mut Context, _: EndpointId) {}
+        fn is_send_ready(&self) -> bool { false }
+        fn is_recv_ready(&self) -> bool { self.held.is_some() }
+        fn close(&mut self, _: &mut Context) {}
+    }
+
+    #[test]
+    fn when_recv_with_info_gets_a_message_held_by_the_protocol() {
+        let id = SocketId::from(1);
+        let eid = EndpointId::from(1);
+        let (tx, rx) = mpsc::channel();
+        let (proto_tx, proto_rx) = mpsc::channel();
+        let proto = Box::new(HoldingProto { reply_tx: proto_tx, held: None }) as Box<Protocol>;
+        let mut network = WorkingNetwork(eid);
+        let mut socket = Socket::new(id, tx, proto_rx, proto);
+
+        socket.connect(&mut network, String::from("test://fake"));
+        let _ = rx.recv().expect("Socket should have sent a reply to the connect request");
+
+        socket.on_recv_ack(&mut network, eid, Message::from_body(vec![65]));
+        socket.forward_replies(&mut network);
+        socket.recv_with_info(&mut network);
+        socket.forward_replies(&mut network);
+
+        match rx.recv().expect("Socket should have sent a reply to the recv request") {
+            Reply::RecvWithInfo(msg, Some(info)) => {
+                assert_eq!(&[65], msg.get_body());
+                assert_eq!(eid, info.endpoint_id());
+                assert_eq!("test", info.scheme());
+            },
+            _ => {
+                assert!(false, "Socket should have replied the held message with its pipe info");
+            },
+        }
+    }
 }
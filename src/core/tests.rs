@@ -10,7 +10,7 @@ use std::cell::RefCell;
 use std::io::Result;
 use std::time::Duration;
 
-use super::{SocketId, EndpointId, Message, EndpointTmpl, EndpointDesc, Scheduled};
+use super::{SocketId, EndpointId, Message, EndpointTmpl, EndpointDesc, PeerCred, Scheduled};
 use super::endpoint::Pipe;
 use super::context::{Context, Scheduler, Schedulable, Event};
 use super::network::Network;
@@ -25,7 +25,9 @@ pub fn new_test_endpoint_desc() -> EndpointDesc {
         send_priority: 0,
         recv_priority: 0,
         tcp_no_delay: false,
-        recv_max_size: 1024
+        tcp_keepalive: None,
+        recv_max_size: 1024,
+        max_connections: None
     }
 }
 
@@ -161,12 +163,25 @@ impl Network for TestContext {
     fn close(&mut self, eid: EndpointId, remote: bool) {
         self.sensor.borrow_mut().push_close_call(eid, remote)
     }
+    fn pause(&mut self, _: EndpointId) {
+    }
+    fn resume(&mut self, _: EndpointId) {
+    }
     fn send(&mut self, eid: EndpointId, msg: Rc<Message>) {
         self.sensor.borrow_mut().push_send_call(eid, msg)
     }
     fn recv(&mut self, eid: EndpointId) {
         self.sensor.borrow_mut().push_recv_call(eid)
     }
+    fn local_addr(&self, _: EndpointId, _: bool) -> Option<String> {
+        None
+    }
+    fn peer_addr(&self, _: EndpointId) -> Option<String> {
+        None
+    }
+    fn peer_cred(&self, _: EndpointId) -> Option<PeerCred> {
+        None
+    }
 }
 
 impl Scheduler for TestContext {
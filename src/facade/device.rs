@@ -5,13 +5,13 @@
 // This file may not be copied, modified, or distributed except according to those terms.
 
 
-use std::sync::mpsc;
 use std::io;
+use std::mem;
 
 use super::*;
 use reactor;
-use core::DeviceId;
-use core::device::{Request, Reply};
+use core::{DeviceId, Message};
+use core::device::{Request, Reply, DeviceCounters};
 use io_error::*;
 
 /// A device to forward messages between sockets, working like a message broker.
@@ -55,8 +55,32 @@ impl Device for Relay {
 /*                                                                           */
 /*****************************************************************************/
 
+/// Tells which way a message is going through a bridge device.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Direction {
+    LeftToRight,
+    RightToLeft
+}
+
+/// Lets a bridge device inspect, rewrite, drop or duplicate the messages it forwards.
+/// See [create_bridge_device_with](struct.Session.html#method.create_bridge_device_with).
+pub trait BridgeHook : Send {
+    /// Called for each message received by the bridge, returns the messages to forward instead.
+    /// Returning an empty vector drops the message, returning several duplicates it.  
+    /// The hook is only given the message body, the header holding the backtrace 
+    /// of Req/Rep and Surveyor/Respondent exchanges is restored on each returned message,
+    /// so the replies still find their way back.
+    fn on_msg(&mut self, direction: Direction, msg: Message) -> Vec<Message>;
+}
+
+impl<F> BridgeHook for F where F : FnMut(Direction, Message) -> Vec<Message> + Send {
+    fn on_msg(&mut self, direction: Direction, msg: Message) -> Vec<Message> {
+        self(direction, msg)
+    }
+}
+
 #[doc(hidden)]
-pub type ReplyReceiver = mpsc::Receiver<Reply>;
+pub type ReplyReceiver = super::ReplyReceiver<Request, Reply>;
 
 #[doc(hidden)]
 pub struct RequestSender {
@@ -72,7 +96,7 @@ impl RequestSender {
         }
     }
     fn send(&self, req: Request) -> io::Result<()> {
-        self.req_tx.send(reactor::Request::Device(self.device_id, req)).map_err(from_send_error)
+        self.req_tx.send(reactor::Request::Device(self.device_id, req))
     }
 }
 
@@ -81,7 +105,8 @@ pub struct Bridge {
     request_sender: RequestSender,
     reply_receiver: ReplyReceiver,
     left: Option<socket::Socket>,
-    right: Option<socket::Socket>
+    right: Option<socket::Socket>,
+    hook: Option<Box<BridgeHook>>
 }
 
 impl Bridge {
@@ -90,18 +115,20 @@ impl Bridge {
         request_tx: RequestSender, 
         reply_rx: ReplyReceiver,
         left: socket::Socket,
-        right: socket::Socket) -> Bridge {
+        right: socket::Socket,
+        hook: Option<Box<BridgeHook>>) -> Bridge {
 
         Bridge {
             request_sender: request_tx,
             reply_receiver: reply_rx,
             left: Some(left),
-            right: Some(right)
+            right: Some(right),
+            hook: hook
         }
     }
 
     fn execute_request(&self, request: Request) -> io::Result<Reply> {
-        self.send_request(request).and_then(|_| self.recv_reply())
+        self.reply_receiver.execute(request, |request| self.send_request(request))
     }
 
     fn send_request(&self, request: Request) -> io::Result<()> {
@@ -115,9 +142,9 @@ impl Bridge {
     fn run_once(&mut self, left: &mut socket::Socket, right: &mut socket::Socket) -> io::Result<()> {
         if let Reply::Check(l, r) = try!(self.execute_request(Request::Check)) {
             match (l, r) {
-                (true, true) => exchange_msg(left, right),
-                (true, _)    => forward_msg(left, right),
-                (_, true)    => forward_msg(right, left),
+                (true, true) => self.exchange_msg(left, right),
+                (true, _)    => self.forward_msg(Direction::LeftToRight, left, right),
+                (_, true)    => self.forward_msg(Direction::RightToLeft, right, left),
                 (_, _)       => Ok(())
             }
         } else {
@@ -125,6 +152,31 @@ impl Bridge {
         }
 
     }
+
+    fn forward_msg(&mut self, direction: Direction, from: &mut socket::Socket, to: &mut socket::Socket) -> io::Result<()> {
+        from.recv_msg().and_then(|msg| self.send_msg(direction, to, msg))
+    }
+
+    fn exchange_msg(&mut self, left: &mut socket::Socket, right: &mut socket::Socket) -> io::Result<()> {
+        let from_left = try!(left.recv_msg());
+        let from_right = try!(right.recv_msg());
+
+        try!(self.send_msg(Direction::LeftToRight, right, from_left));
+
+        self.send_msg(Direction::RightToLeft, left, from_right)
+    }
+
+    fn send_msg(&mut self, direction: Direction, to: &mut socket::Socket, msg: Message) -> io::Result<()> {
+        match self.hook {
+            Some(ref mut hook) => {
+                for msg in apply_hook(hook.as_mut(), direction, msg) {
+                    try!(to.send_msg(msg));
+                }
+                Ok(())
+            },
+            None => to.send_msg(msg)
+        }
+    }
 }
 
 impl Device for Bridge {
@@ -138,15 +190,16 @@ impl Device for Bridge {
     }
 }
 
-fn forward_msg(from: &mut socket::Socket, to: &mut socket::Socket) -> io::Result<()> {
-    from.recv_msg().and_then(|msg| to.send_msg(msg))
-}
+// The header holds the backtrace, see DEVICE.MD, so it is kept away from the hook
+fn apply_hook(hook: &mut BridgeHook, direction: Direction, mut msg: Message) -> Vec<Message> {
+    let header = mem::replace(&mut msg.header, Vec::new());
+    let mut msgs = hook.on_msg(direction, msg);
 
-fn exchange_msg(left: &mut socket::Socket, right: &mut socket::Socket) -> io::Result<()> {
-    let from_left = try!(left.recv_msg());
-    let from_right = try!(right.recv_msg());
+    for msg in &mut msgs {
+        msg.header = header.clone();
+    }
 
-    right.send_msg(from_left).and_then(|_| left.send_msg(from_right))
+    msgs
 }
 
 impl Drop for Bridge {
@@ -155,3 +208,65 @@ impl Drop for Bridge {
         let _ = self.recv_reply();
     }
 }
+
+/*****************************************************************************/
+/*                                                                           */
+/* SPAWNED DEVICE                                                            */
+/*                                                                           */
+/*****************************************************************************/
+
+/// Handle to a device running on the I/O thread.
+///   
+/// Obtained via the session [spawn_device](struct.Session.html#method.spawn_device) method.  
+/// Dropping the handle stops the device.
+pub struct DeviceHandle {
+    request_sender: RequestSender,
+    reply_receiver: ReplyReceiver,
+    stopped: bool
+}
+
+impl DeviceHandle {
+    #[doc(hidden)]
+    pub fn new(request_tx: RequestSender, reply_rx: ReplyReceiver) -> DeviceHandle {
+        DeviceHandle {
+            request_sender: request_tx,
+            reply_receiver: reply_rx,
+            stopped: false
+        }
+    }
+
+    /// Returns the number of messages and bytes forwarded so far in each direction.
+    pub fn counters(&self) -> io::Result<DeviceCounters> {
+        match try!(self.execute_request(Request::GetCounters)) {
+            Reply::Counters(counters) => Ok(counters),
+            Reply::Err(e) => Err(e),
+            _ => Err(other_io_error("unexpected reply"))
+        }
+    }
+
+    /// Stops the device and closes both sockets.
+    /// If the device had already stopped because of an error, this error is returned.
+    /// Send and recv timeouts do not stop the device, the operation is tried again.
+    pub fn stop(mut self) -> io::Result<()> {
+        self.stopped = true;
+
+        match try!(self.execute_request(Request::Close)) {
+            Reply::Closed => Ok(()),
+            Reply::Err(e) => Err(e),
+            _ => Err(other_io_error("unexpected reply"))
+        }
+    }
+
+    fn execute_request(&self, request: Request) -> io::Result<Reply> {
+        self.reply_receiver.execute(request, |request| self.request_sender.send(request))
+    }
+}
+
+impl Drop for DeviceHandle {
+    fn drop(&mut self) {
+        if !self.stopped {
+            let _ = self.request_sender.send(Request::Close);
+            let _ = self.reply_receiver.receive();
+        }
+    }
+}
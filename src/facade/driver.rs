@@ -0,0 +1,106 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::time::Duration;
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use reactor::dispatcher::ManualDispatcher;
+use io_error::*;
+
+/// Runs the event loop of a session created with
+/// [build_manual](struct.SessionBuilder.html#method.build_manual), on the caller's thread.
+/// The host application decides when I/O is processed, by calling [run_once](#method.run_once),
+/// typically when the file descriptor of the underlying poller becomes readable.
+/// The operations of the session never wait for the driver, they return a `WouldBlock` error
+/// carrying a [ReplyPending](struct.ReplyPending.html) until it has processed them, see [run_until](#method.run_until).
+pub struct Driver {
+    dispatcher: ManualDispatcher
+}
+
+impl Driver {
+    #[doc(hidden)]
+    pub fn new(dispatcher: ManualDispatcher) -> Driver {
+        Driver {
+            dispatcher: dispatcher
+        }
+    }
+
+    /// Waits for I/O readiness, timers or requests for at most `timeout`, and processes them.
+    /// `None` waits until something happens. Once the session is dropped, this returns immediately.
+    pub fn run_once(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.dispatcher.run_once(timeout).map(|_| ())
+    }
+
+    /// Processes I/O readiness, timers and requests, without waiting, until there is nothing left to do.
+    /// The timeouts expired by a [VirtualClock](struct.VirtualClock.html) have been processed on return.
+    pub fn run_until_idle(&mut self) -> io::Result<()> {
+        while try!(self.dispatcher.run_once(Some(Duration::from_millis(0)))) > 0 {}
+
+        Ok(())
+    }
+
+    /// Calls `operation` until it completes, running the event loop each time its reply is pending.
+    /// This performs a blocking operation of the session on the thread running the driver,
+    /// the `WouldBlock` errors of the non-blocking operations are returned as usual.
+    pub fn run_until<T, F>(&mut self, mut operation: F) -> io::Result<T> where F : FnMut() -> io::Result<T> {
+        loop {
+            match operation() {
+                Err(ref e) if ReplyPending::from_io_error(e).is_some() => {},
+                res => return res
+            }
+
+            if !self.is_running() {
+                return Err(other_io_error("session is shut down"));
+            }
+
+            try!(self.run_once(None));
+        }
+    }
+
+    /// Returns `false` once the session has been dropped.
+    pub fn is_running(&self) -> bool {
+        self.dispatcher.is_running()
+    }
+}
+
+/// Returns the file descriptor of the poller used by the event loop.
+/// It becomes readable when [run_once](#method.run_once) has work to do.
+#[cfg(unix)]
+impl AsRawFd for Driver {
+    fn as_raw_fd(&self) -> RawFd {
+        self.dispatcher.as_raw_fd()
+    }
+}
+
+/// Error of an operation of a manual session, whose reply is not available until the driver has run.
+/// It comes as a `WouldBlock` error, the same operation must then be called again.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ReplyPending;
+
+impl ReplyPending {
+    /// Returns the pending reply marker carried by the error, if any.
+    pub fn from_io_error(err: &io::Error) -> Option<ReplyPending> {
+        err.get_ref().and_then(|inner| inner.downcast_ref::<ReplyPending>()).cloned()
+    }
+}
+
+impl From<ReplyPending> for io::Error {
+    fn from(pending: ReplyPending) -> io::Error {
+        io::Error::new(io::ErrorKind::WouldBlock, pending)
+    }
+}
+
+impl fmt::Display for ReplyPending {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "reply is not available, the driver must run")
+    }
+}
+
+impl error::Error for ReplyPending {}
@@ -5,12 +5,12 @@
 // This file may not be copied, modified, or distributed except according to those terms.
 
 use std::io;
+use std::sync::{Arc, Mutex};
 
 use super::*;
 use reactor;
-use core::{SocketId, EndpointId};
+use core::{SocketId, EndpointId, EndpointInfo};
 use core::endpoint::Request;
-use io_error::*;
 
 #[doc(hidden)]
 pub struct RequestSender {
@@ -28,7 +28,7 @@ impl RequestSender {
         }
     }
     fn send(&self, req: Request) -> io::Result<()> {
-        self.req_tx.send(reactor::Request::Endpoint(self.socket_id, self.id, req)).map_err(from_send_error)
+        self.req_tx.send(reactor::Request::Endpoint(self.socket_id, self.id, req))
     }
 }
 
@@ -36,22 +36,48 @@ impl RequestSender {
 ///   
 /// Obtained via the socket [bind](struct.Socket.html#method.bind) or 
 /// [connect](struct.Socket.html#method.connect) methods.  
-/// Can only be used to close an endpoint.  
+/// Can be used to find out the addresses of the endpoint, and to close it.  
 /// Note that `drop(Endpoint)` will **NOT** close it.
 pub struct Endpoint {
     request_sender: RequestSender,
-    remote: bool
+    remote: bool,
+    url: String,
+    info: Arc<Mutex<EndpointInfo>>
 }
 
 impl Endpoint {
     #[doc(hidden)]
-    pub fn new(request_tx: RequestSender, remote: bool) -> Endpoint {
+    pub fn new(request_tx: RequestSender, remote: bool, info: Arc<Mutex<EndpointInfo>>) -> Endpoint {
+        let url = info.lock().map(|info| info.url.clone()).unwrap_or_default();
+
         Endpoint {
             request_sender: request_tx,
-            remote: remote
+            remote: remote,
+            url: url,
+            info: info
         }
     }
 
+    /// Returns the url of the endpoint.  
+    /// For a bound endpoint, this is the address actually listened on, 
+    /// so binding to `tcp://127.0.0.1:0` gives back an url with the port assigned by the OS.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Returns the local address of the endpoint, in the format of its transport.  
+    /// For a bound endpoint, this is the address the socket listens on.
+    /// For a connected endpoint, this is the local side of the latest connection, when it is known.
+    pub fn local_addr(&self) -> Option<String> {
+        self.info.lock().ok().and_then(|info| info.local_addr.clone())
+    }
+
+    /// Returns the remote address of the latest connection of a connected endpoint, in the format of its transport.
+    /// Always `None` for a bound endpoint.
+    pub fn peer_addr(&self) -> Option<String> {
+        self.info.lock().ok().and_then(|info| info.peer_addr.clone())
+    }
+
     pub fn close(self) -> io::Result<()> {
         self.request_sender.send(Request::Close(self.remote))
     }
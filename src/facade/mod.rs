@@ -9,26 +9,78 @@ pub mod socket;
 pub mod endpoint;
 pub mod device;
 pub mod probe;
+pub mod driver;
 
+use std::cell::Cell;
 use std::sync::mpsc;
 use std::io;
-
-use mio;
+use std::mem;
 
 use reactor;
 use io_error::*;
 
-pub trait Receiver<T> {
-    fn receive(&self) -> io::Result<T>;
+/// Receives the replies to the requests sent to the event loop.  
+/// When the session is driven manually, waiting for a reply would dead lock the thread running the driver,
+/// so a reply that is not available yet is reported as a `ReplyPending` error and the request stays pending.
+/// Calling the same operation again, once the driver has run, returns the reply instead of sending another request.
+pub struct ReplyReceiver<Q, R> {
+    rx: mpsc::Receiver<R>,
+    manual: bool,
+    pending: Cell<Option<mem::Discriminant<Q>>>
 }
 
-impl<T> Receiver<T> for mpsc::Receiver<T> {
-    fn receive(&self) -> io::Result<T> {
-        match mpsc::Receiver::recv(self) {
-            Ok(t)  => Ok(t),
-            Err(_) => Err(other_io_error("evt channel closed")),
+impl<Q, R> ReplyReceiver<Q, R> {
+    pub fn new(rx: mpsc::Receiver<R>, manual: bool) -> ReplyReceiver<Q, R> {
+        ReplyReceiver {
+            rx: rx,
+            manual: manual,
+            pending: Cell::new(None)
+        }
+    }
+
+    pub fn is_manual(&self) -> bool {
+        self.manual
+    }
+
+    pub fn into_inner(self) -> mpsc::Receiver<R> {
+        self.rx
+    }
+
+    /// Sends the request with `send`, unless a request of the same kind is pending, and returns its reply.
+    pub fn execute<F>(&self, request: Q, send: F) -> io::Result<R> where F : FnOnce(Q) -> io::Result<()> {
+        let kind = mem::discriminant(&request);
+
+        match self.pending.get() {
+            None => try!(send(request)),
+            Some(pending) if pending == kind => {},
+            Some(_) => return Err(other_io_error("another operation is waiting for its reply"))
+        }
+
+        let reply = self.receive();
+
+        match reply {
+            Err(ref e) if driver::ReplyPending::from_io_error(e).is_some() => self.pending.set(Some(kind)),
+            _ => self.pending.set(None)
+        }
+
+        reply
+    }
+
+    /// Waits for the next reply, or checks whether it is available when the session is driven manually.
+    pub fn receive(&self) -> io::Result<R> {
+        if self.manual {
+            match self.rx.try_recv() {
+                Ok(t) => Ok(t),
+                Err(mpsc::TryRecvError::Empty) => Err(io::Error::from(driver::ReplyPending)),
+                Err(mpsc::TryRecvError::Disconnected) => Err(other_io_error("evt channel closed"))
+            }
+        } else {
+            match self.rx.recv() {
+                Ok(t)  => Ok(t),
+                Err(_) => Err(other_io_error("evt channel closed")),
+            }
         }
     }
 }
 
-pub type EventLoopRequestSender = mio::channel::Sender<reactor::Request>;
\ No newline at end of file
+pub type EventLoopRequestSender = reactor::mailbox::Mailbox<reactor::Request>;
@@ -4,7 +4,6 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
-use std::sync::mpsc;
 use std::io;
 use std::time::Duration;
 
@@ -15,7 +14,7 @@ use core::probe::{Request, Reply};
 use io_error::*;
 
 #[doc(hidden)]
-pub type ReplyReceiver = mpsc::Receiver<Reply>;
+pub type ReplyReceiver = super::ReplyReceiver<Request, Reply>;
 
 #[doc(hidden)]
 pub struct RequestSender {
@@ -37,7 +36,7 @@ impl RequestSender {
         }
     }
     fn send(&self, req: Request) -> io::Result<()> {
-        self.req_tx.send(reactor::Request::Probe(self.probe_id, req)).map_err(from_send_error)
+        self.req_tx.send(reactor::Request::Probe(self.probe_id, req))
     }
 }
 
@@ -74,7 +73,7 @@ impl Probe {
     }
 
     fn execute_request(&self, request: Request) -> io::Result<Reply> {
-        self.send_request(request).and_then(|_| self.recv_reply())
+        self.reply_receiver.execute(request, |request| self.send_request(request))
     }
 
     fn send_request(&self, request: Request) -> io::Result<()> {
@@ -5,58 +5,113 @@
 // This file may not be copied, modified, or distributed except according to those terms.
 
 use std::collections::HashMap;
+use std::cell::Cell;
 use std::io;
 use std::thread;
-use std::sync::mpsc;
-
-use mio;
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
 
 use super::*;
 use transport::Transport;
 use reactor;
 use reactor::dispatcher;
+use reactor::mailbox;
+use reactor::clock::VirtualClock;
 use core::session::{Request, Reply};
 use core::socket::{Protocol, ProtocolCtor};
+use core::pool::BufferPool;
 use core;
 use io_error::*;
 
 #[doc(hidden)]
-type ReplyReceiver = mpsc::Receiver<Reply>;
+type ReplyReceiver = super::ReplyReceiver<Request, Reply>;
 
 #[doc(hidden)]
 struct RequestSender {
-    req_tx: EventLoopRequestSender
+    req_txs: Vec<EventLoopRequestSender>,
+    next_shard: Cell<usize>
 }
 
 impl RequestSender {
-    fn new(tx: EventLoopRequestSender) -> RequestSender {
-        RequestSender { req_tx: tx }
+    fn new(txs: Vec<EventLoopRequestSender>) -> RequestSender {
+        RequestSender {
+            req_txs: txs,
+            next_shard: Cell::new(0)
+        }
     }
     fn socket_sender(&self, socket_id: core::SocketId) -> socket::RequestSender {
-        socket::RequestSender::new(self.req_tx.clone(), socket_id)
+        socket::RequestSender::new(self.shard_sender(socket_id), socket_id)
     }
     fn device_sender(&self, device_id: core::DeviceId) -> device::RequestSender {
-        device::RequestSender::new(self.req_tx.clone(), device_id)
+        device::RequestSender::new(self.shard_sender(device_id), device_id)
     }
     fn probe_sender(&self, probe_id: core::ProbeId) -> probe::RequestSender {
-        probe::RequestSender::new(self.req_tx.clone(), probe_id)
+        probe::RequestSender::new(self.shard_sender(probe_id), probe_id)
+    }
+    // each socket, device or probe gets its own mailbox to the dispatcher owning it
+    fn shard_sender<T : Into<usize>>(&self, id: T) -> EventLoopRequestSender {
+        self.req_txs[reactor::shard_of(id, self.req_txs.len())].sibling()
     }
     fn send(&self, req: Request) -> io::Result<()> {
-        self.req_tx.send(reactor::Request::Session(req)).map_err(from_send_error)
+        let shard = match req {
+            Request::CreateSocket(_) => self.next_shard(),
+            Request::CreateDevice(left, _) | 
+            Request::SpawnDevice(left, _, _, _) => reactor::shard_of(left, self.req_txs.len()),
+            Request::CreateProbe(ref poll_opts) => match poll_opts.first() {
+                Some(poll_opt) => reactor::shard_of(poll_opt.sid, self.req_txs.len()),
+                None => 0
+            },
+            Request::Shutdown => return self.broadcast_shutdown()
+        };
+
+        self.req_txs[shard].send(reactor::Request::Session(req))
+    }
+    // Sockets are spread over the I/O threads in a round robin fashion
+    fn next_shard(&self) -> usize {
+        let shard = self.next_shard.get();
+
+        self.next_shard.set((shard + 1) % self.req_txs.len());
+        shard
+    }
+    fn broadcast_shutdown(&self) -> io::Result<()> {
+        let mut res = Ok(());
+
+        for req_tx in &self.req_txs {
+            let req = reactor::Request::Session(Request::Shutdown);
+
+            if let Err(e) = req_tx.send(req) {
+                res = Err(e);
+            }
+        }
+
+        res
     }
 }
 
 /// Creates the session and starts the I/O thread.
-#[derive(Default)]
 pub struct SessionBuilder {
-    transports: HashMap<String, Box<Transport + Send>, core::BuildIdHasher>
+    transports: HashMap<String, Box<Transport + Send>, core::BuildIdHasher>,
+    config: reactor::Config,
+    io_threads: usize,
+    thread_name: String,
+    thread_hook: Option<Arc<Fn(usize) + Send + Sync>>
+}
+
+impl Default for SessionBuilder {
+    fn default() -> SessionBuilder {
+        SessionBuilder::new()
+    }
 }
 
 impl SessionBuilder {
 
     pub fn new() -> SessionBuilder {
         SessionBuilder {
-            transports: HashMap::with_hasher(core::BuildIdHasher)
+            transports: HashMap::with_hasher(core::BuildIdHasher),
+            config: reactor::Config::default(),
+            io_threads: 1,
+            thread_name: String::from("scaproust-io"),
+            thread_hook: None
         }
     }
 
@@ -66,30 +121,172 @@ impl SessionBuilder {
         self
     }
 
+    /// Sets the resolution of the timer used to schedule timeouts, 25 ms by default.
+    /// Timeouts are rounded up to the next tick, so a finer tick makes short timeouts more accurate
+    /// at the expense of more frequent wake ups. The tick must be at least one millisecond
+    /// and is truncated to whole milliseconds.
+    pub fn with_timer_tick(mut self, tick: Duration) -> SessionBuilder {
+        self.config.timer_tick = tick;
+        self
+    }
+
+    /// Sets the number of slots of the timer wheel, 1024 by default.
+    /// The value is rounded up to the next power of two.
+    pub fn with_timer_slots(mut self, slots: usize) -> SessionBuilder {
+        self.config.timer_slots = slots;
+        self
+    }
+
+    /// Sets the maximum number of pending timeouts, 8192 by default.
+    /// The value is rounded up to the next power of two.
+    /// When the capacity is exhausted, operations requiring a timeout fail.
+    pub fn with_timer_capacity(mut self, capacity: usize) -> SessionBuilder {
+        self.config.timer_capacity = capacity;
+        self
+    }
+
+    /// Drives all the timeouts of the session with a virtual clock instead of the system clock.
+    /// Timeouts then only expire when the clock is [advanced](struct.VirtualClock.html#method.advance),
+    /// which makes tests relying on them deterministic. The timer tick, slots and capacity are ignored.
+    pub fn with_virtual_clock(mut self, clock: VirtualClock) -> SessionBuilder {
+        self.config.clock = Some(clock);
+        self
+    }
+
+    /// Sets the maximum number of readiness events processed in a single event loop iteration, 1024 by default.
+    pub fn with_event_capacity(mut self, capacity: usize) -> SessionBuilder {
+        self.config.event_capacity = capacity;
+        self
+    }
+
+    /// Sets the maximum number of buffers kept by the session for reuse, 256 by default, zero disables the pool.  
+    /// Buffers given back with [Socket::recycle](struct.Socket.html#method.recycle) are used to receive
+    /// the next messages instead of allocating new ones. Buffers larger than 1 MiB are never kept.
+    pub fn with_buffer_pool_size(mut self, max_buffers: usize) -> SessionBuilder {
+        self.config.buffer_pool = BufferPool::new(max_buffers);
+        self
+    }
+
+    /// Sets the number of I/O threads, one by default.
+    /// Each thread runs its own event loop, and each socket is assigned to one of them
+    /// along with its endpoints. Devices and probes can link sockets assigned to different threads.
+    pub fn with_io_threads(mut self, count: usize) -> SessionBuilder {
+        self.io_threads = count;
+        self
+    }
+
+    /// Sets the name of the I/O thread, `scaproust-io` by default.
+    /// When there are several I/O threads, their index is appended to the name.
+    pub fn with_thread_name(mut self, name: &str) -> SessionBuilder {
+        self.thread_name = name.into();
+        self
+    }
+
+    /// Sets a function to be called on each I/O thread, with the index of the thread, 
+    /// before it starts processing requests.
+    /// This can be used to pin the thread to a CPU, change its priority or install thread locals.
+    pub fn with_thread_hook<F>(mut self, hook: F) -> SessionBuilder
+    where F : Fn(usize) + Send + Sync + 'static {
+        self.thread_hook = Some(Arc::new(hook));
+        self
+    }
+
     pub fn build(self) -> io::Result<Session> {
+        try!(self.check_config());
 
+        let count = self.io_threads;
         let (reply_tx, reply_rx) = mpsc::channel();
-        let (request_tx, request_rx) = mio::channel::channel();
-        let session = Session::new(RequestSender::new(request_tx), reply_rx);
-
-        thread::spawn(move || dispatcher::Dispatcher::dispatch(self.transports, request_rx, reply_tx));
+        let channels: Vec<_> = try!((0..count).map(|_| mailbox::channel()).collect());
+        let request_txs = channels.iter().map(|&(ref tx, _)| tx.clone()).collect();
+        let reply_rx = ReplyReceiver::new(reply_rx, false);
+        let mut session = Session::new(RequestSender::new(request_txs), reply_rx, self.config.buffer_pool.clone());
+        let shards = dispatcher::create_shards(count);
+        let transports = dispatcher::share_transports(self.transports, count);
+        let threads = channels.into_iter().zip(shards).zip(transports);
+
+        // should a thread fail to start, dropping the session stops the other ones
+        for (((_, request_rx), shard), transports) in threads {
+            let index = shard.index();
+            let name = if count == 1 {
+                self.thread_name.clone()
+            } else {
+                format!("{}-{}", self.thread_name, index)
+            };
+            let reply_tx = reply_tx.clone();
+            let config = self.config.clone();
+            let hook = self.thread_hook.clone();
+
+            let io_thread = try!(thread::Builder::new().name(name).spawn(move || {
+                if let Some(hook) = hook {
+                    hook(index);
+                }
+                dispatcher::Dispatcher::dispatch(transports, request_rx, reply_tx, config, shard)
+            }));
+
+            session.io_threads.push(io_thread);
+        }
 
         Ok(session)
     }
+
+    /// Creates a session whose event loop is run by the caller through the returned driver,
+    /// instead of a dedicated I/O thread. The thread name and hook settings are ignored,
+    /// and the session must have a single I/O thread.  
+    /// Operations on the session and its sockets never block: when the reply is not available yet,
+    /// a `WouldBlock` error carrying a [ReplyPending](struct.ReplyPending.html) is returned,
+    /// and the same operation must be called again after the driver has run,
+    /// which [Driver::run_until](struct.Driver.html#method.run_until) does. Devices are not supported.
+    pub fn build_manual(self) -> io::Result<(Session, driver::Driver)> {
+        try!(self.check_config());
+
+        if self.io_threads != 1 {
+            return Err(invalid_input_io_error("manual session must have a single io thread"));
+        }
+
+        let (reply_tx, reply_rx) = mpsc::channel();
+        let (request_tx, request_rx) = try!(mailbox::channel());
+        let reply_rx = ReplyReceiver::new(reply_rx, true);
+        let shard = dispatcher::create_shards(1).remove(0);
+        let buffer_pool = self.config.buffer_pool.clone();
+        let dispatcher = try!(dispatcher::ManualDispatcher::new(self.transports, request_rx, reply_tx, self.config, shard));
+        let session = Session::new(RequestSender::new(vec![request_tx]), reply_rx, buffer_pool);
+
+        Ok((session, driver::Driver::new(dispatcher)))
+    }
+
+    fn check_config(&self) -> io::Result<()> {
+        if self.io_threads == 0 {
+            Err(invalid_input_io_error("io threads must not be zero"))
+        } else if self.config.timer_tick < Duration::from_millis(1) {
+            Err(invalid_input_io_error("timer tick must be at least one millisecond"))
+        } else if self.config.timer_slots == 0 {
+            Err(invalid_input_io_error("timer slots must not be zero"))
+        } else if self.config.timer_capacity == 0 {
+            Err(invalid_input_io_error("timer capacity must not be zero"))
+        } else if self.config.event_capacity == 0 {
+            Err(invalid_input_io_error("event capacity must not be zero"))
+        } else {
+            Ok(())
+        }
+    }
 }
 
 /// Creates sockets and devices.
 pub struct Session {
     request_sender: RequestSender,
-    reply_receiver: ReplyReceiver
+    reply_receiver: ReplyReceiver,
+    buffer_pool: BufferPool,
+    io_threads: Vec<thread::JoinHandle<io::Result<()>>>
 }
 
 impl Session {
 
-    fn new(request_tx: RequestSender, reply_rx: ReplyReceiver) -> Session {
+    fn new(request_tx: RequestSender, reply_rx: ReplyReceiver, buffer_pool: BufferPool) -> Session {
         Session {
             request_sender: request_tx,
-            reply_receiver: reply_rx
+            reply_receiver: reply_rx,
+            buffer_pool: buffer_pool,
+            io_threads: Vec::new()
         }
     }
 
@@ -125,7 +322,8 @@ impl Session {
         match reply {
             Reply::SocketCreated(id, rx) => {
                 let sender = self.request_sender.socket_sender(id);
-                let sock = socket::Socket::new(sender, rx);
+                let rx = socket::ReplyReceiver::new(rx, self.is_manual());
+                let sock = socket::Socket::new(sender, rx, self.buffer_pool.clone());
                 
                 Ok(sock)
             },
@@ -148,16 +346,58 @@ impl Session {
     /// Creates a bridge device to forward messages between two sockets. 
     /// It loops and sends any messages received from `left` to `right` and vice versa.
     pub fn create_bridge_device(&mut self, left: socket::Socket, right: socket::Socket) -> io::Result<Box<device::Device>> {
+        try!(self.check_device_supported());
+
+        let request = Request::CreateDevice(left.id(), right.id());
+
+        self.call(request, |reply| self.on_create_device_reply(reply, left, right, None))
+    }
+
+    /// Creates a bridge device that calls `hook` on each message before forwarding it.
+    /// The hook can inspect, rewrite, drop or duplicate the messages going in both directions.
+    pub fn create_bridge_device_with<H>(&mut self, left: socket::Socket, right: socket::Socket, hook: H) -> io::Result<Box<device::Device>> 
+    where H : device::BridgeHook + 'static {
+        try!(self.check_device_supported());
+
         let request = Request::CreateDevice(left.id(), right.id());
+        let hook: Box<device::BridgeHook> = Box::new(hook);
 
-        self.call(request, |reply| self.on_create_device_reply(reply, left, right))
+        self.call(request, |reply| self.on_create_device_reply(reply, left, right, Some(hook)))
     }
 
-    fn on_create_device_reply(&self, reply: Reply, left: socket::Socket, right: socket::Socket) -> io::Result<Box<device::Device>> {
+    /// Starts a device forwarding messages between two sockets, like a bridge device,
+    /// but running on the I/O thread so that messages do not go through a user thread.  
+    /// Returns a handle that can be used to stop the device and to read its counters.
+    pub fn spawn_device(&mut self, left: socket::Socket, right: socket::Socket) -> io::Result<device::DeviceHandle> {
+        try!(self.check_device_supported());
+
+        let (left_id, left_rx) = left.detach();
+        let (right_id, right_rx) = right.detach();
+        let request = Request::SpawnDevice(left_id, left_rx, right_id, right_rx);
+
+        self.call(request, |reply| self.on_spawn_device_reply(reply))
+    }
+
+    fn on_spawn_device_reply(&self, reply: Reply) -> io::Result<device::DeviceHandle> {
+        match reply {
+            Reply::DeviceSpawned(id, rx) => {
+                let sender = self.request_sender.device_sender(id);
+
+                let rx = device::ReplyReceiver::new(rx, self.is_manual());
+
+                Ok(device::DeviceHandle::new(sender, rx))
+            },
+            Reply::Err(e) => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
+    fn on_create_device_reply(&self, reply: Reply, left: socket::Socket, right: socket::Socket, hook: Option<Box<device::BridgeHook>>) -> io::Result<Box<device::Device>> {
         match reply {
             Reply::DeviceCreated(id, rx) => {
                 let sender = self.request_sender.device_sender(id);
-                let bridge = device::Bridge::new(sender, rx, left, right);
+                let rx = device::ReplyReceiver::new(rx, self.is_manual());
+                let bridge = device::Bridge::new(sender, rx, left, right, hook);
                 
                 Ok(Box::new(bridge))
             },
@@ -183,6 +423,7 @@ impl Session {
         match reply {
             Reply::ProbeCreated(id, rx) => {
                 let sender = self.request_sender.probe_sender(id);
+                let rx = probe::ReplyReceiver::new(rx, self.is_manual());
                 let probe = probe::Probe::new(sender, rx);
 
                 Ok(probe)
@@ -207,21 +448,35 @@ impl Session {
     }
 
     fn execute_request(&self, request: Request) -> io::Result<Reply> {
-        self.send_request(request).and_then(|_| self.recv_reply())
+        self.reply_receiver.execute(request, |request| self.send_request(request))
     }
 
     fn send_request(&self, request: Request) -> io::Result<()> {
         self.request_sender.send(request)
     }
 
-    fn recv_reply(&self) -> io::Result<Reply> {
-        self.reply_receiver.receive()
+    fn is_manual(&self) -> bool {
+        self.reply_receiver.is_manual()
+    }
+
+    // The sockets would be lost if the reply was not available yet
+    fn check_device_supported(&self) -> io::Result<()> {
+        if self.is_manual() {
+            Err(invalid_input_io_error("devices are not supported by manual sessions"))
+        } else {
+            Ok(())
+        }
     }
 }
 
+// Every I/O thread is asked to stop, and waited for, so that the endpoints are all closed
+// when the drop returns. A manual session has no thread, its driver processes the request.
 impl Drop for Session {
     fn drop(&mut self) {
         let _ = self.send_request(Request::Shutdown);
-        let _ = self.recv_reply();
+
+        for io_thread in self.io_threads.drain(..) {
+            let _ = io_thread.join();
+        }
     }
 }
@@ -5,19 +5,22 @@
 // This file may not be copied, modified, or distributed except according to those terms.
 
 use std::sync::mpsc;
+use std::cmp;
 use std::io;
+use std::mem;
 use std::time::Duration;
 
 use super::*;
 use reactor;
-use core::{SocketId, Message, PollReq};
+use core::{SocketId, Message, PipeInfo, PollReq};
+use core::pool::BufferPool;
 use core::socket::{Request, Reply};
-use core::config::ConfigOption;
+use core::config::{ConfigOption, ConfigOptionKind};
 use core;
 use io_error::*;
 
 #[doc(hidden)]
-pub type ReplyReceiver = mpsc::Receiver<Reply>;
+pub type ReplyReceiver = super::ReplyReceiver<Request, Reply>;
 
 #[doc(hidden)]
 pub struct RequestSender {
@@ -36,7 +39,7 @@ impl RequestSender {
         endpoint::RequestSender::new(self.req_tx.clone(), self.socket_id, eid)
     }
     fn send(&self, req: Request) -> io::Result<()> {
-        self.req_tx.send(reactor::Request::Socket(self.socket_id, req)).map_err(from_send_error)
+        self.req_tx.send(reactor::Request::Socket(self.socket_id, req))
     }
 }
 
@@ -46,15 +49,19 @@ impl RequestSender {
 /// Applications can have more than one Socket open at a time.
 pub struct Socket {
     request_sender: RequestSender,
-    reply_receiver: ReplyReceiver
+    reply_receiver: ReplyReceiver,
+    buffer_pool: BufferPool,
+    detached: bool
 }
 
 impl Socket {
     #[doc(hidden)]
-    pub fn new(request_tx: RequestSender, reply_rx: ReplyReceiver) -> Socket {
+    pub fn new(request_tx: RequestSender, reply_rx: ReplyReceiver, buffer_pool: BufferPool) -> Socket {
         Socket {
             request_sender: request_tx,
-            reply_receiver: reply_rx
+            reply_receiver: reply_rx,
+            buffer_pool: buffer_pool,
+            detached: false
         }
     }
 
@@ -63,6 +70,18 @@ impl Socket {
         self.request_sender.socket_id
     }
 
+    // Hands the replies over to something else, closing the socket is then its responsibility.
+    #[doc(hidden)]
+    pub fn detach(mut self) -> (SocketId, mpsc::Receiver<Reply>) {
+        let (_, dummy_rx) = mpsc::channel();
+        let dummy_rx = ReplyReceiver::new(dummy_rx, self.reply_receiver.is_manual());
+        let reply_rx = mem::replace(&mut self.reply_receiver, dummy_rx);
+
+        self.detached = true;
+
+        (self.id(), reply_rx.into_inner())
+    }
+
     /// Creates a poll request that can be used to initialize a probe.
     /// The probe will then allow polling a group of sockets
     pub fn create_poll_req(&self, recv: bool, send: bool) -> PollReq {
@@ -95,9 +114,9 @@ impl Socket {
 
     fn on_connect_reply(&self, reply: Reply) -> io::Result<endpoint::Endpoint> {
         match reply {
-            Reply::Connect(id) => {
+            Reply::Connect(id, info) => {
                 let request_tx = self.request_sender.child_sender(id);
-                let ep = endpoint::Endpoint::new(request_tx, true);
+                let ep = endpoint::Endpoint::new(request_tx, true, info);
                 
                 Ok(ep)
             },
@@ -127,9 +146,9 @@ impl Socket {
 
     fn on_bind_reply(&self, reply: Reply) -> io::Result<endpoint::Endpoint> {
         match reply {
-            Reply::Bind(id) => {
+            Reply::Bind(id, info) => {
                 let request_tx = self.request_sender.child_sender(id);
-                let ep = endpoint::Endpoint::new(request_tx, false);
+                let ep = endpoint::Endpoint::new(request_tx, false, info);
                 
                 Ok(ep)
             },
@@ -170,6 +189,26 @@ impl Socket {
         self.call(request, |reply| self.on_send_reply(reply))
     }
 
+    /// Sends several messages with a single request to the I/O thread,
+    /// each of them being handled by the protocol as if given to [send_msg](#method.send_msg),
+    /// so that, for example, push load balances them and pub broadcasts each of them.  
+    /// Returns the number of messages sent, which is less than the batch size
+    /// when a send failed or timed out after the first one.
+    /// An error is returned only when not even the first message could be sent.
+    pub fn send_batch(&mut self, msgs: Vec<Message>) -> io::Result<usize> {
+        let request = Request::SendBatch(msgs);
+
+        self.call(request, |reply| self.on_send_batch_reply(reply))
+    }
+
+    fn on_send_batch_reply(&self, reply: Reply) -> io::Result<usize> {
+        match reply {
+            Reply::SendBatch(sent) => Ok(sent),
+            Reply::Err(e) => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
     fn on_send_reply(&self, reply: Reply) -> io::Result<()> {
         match reply {
             Reply::Send => Ok(()),
@@ -196,6 +235,24 @@ impl Socket {
         self.call(request, |reply| self.on_recv_reply(reply))
     }
 
+    /// Receives a buffer, along with the description of the connection it came from.  
+    /// The description is missing when the connection was closed before the message was read,
+    /// whether the message was just received or already queued by the protocol.
+    pub fn recv_with_info(&mut self) -> io::Result<(Vec<u8>, Option<PipeInfo>)> {
+        let request = Request::RecvWithInfo;
+
+        self.call(request, |reply| self.on_recv_with_info_reply(reply))
+    }
+
+    fn on_recv_with_info_reply(&self, reply: Reply) -> io::Result<(Vec<u8>, Option<PipeInfo>)> {
+        match reply {
+            Reply::RecvWithInfo(msg, info) => Ok((msg.into(), info)),
+            Reply::Recv(msg) => Ok((msg.into(), None)),
+            Reply::Err(e) => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
     /// Non-blocking version of the recv method.
     pub fn try_recv(&mut self) -> io::Result<Vec<u8>> {
         self.try_recv_msg().map(|msg| msg.into())
@@ -208,6 +265,47 @@ impl Socket {
         self.call(request, |reply| self.on_recv_reply(reply))
     }
 
+    /// Receives a message into the given buffer and returns the length of the message body.  
+    /// If the buffer is too small, the body is truncated and the returned length is greater than the buffer's, as with `nn_recv`.
+    /// The message buffers are then given back to the pool of the session, so that receiving
+    /// a steady flow of messages does not allocate once the pool is warmed up.
+    pub fn recv_into(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.recv_msg().map(|msg| self.copy_and_recycle(msg, buffer))
+    }
+
+    /// Non-blocking version of the recv_into method.
+    pub fn try_recv_into(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.try_recv_msg().map(|msg| self.copy_and_recycle(msg, buffer))
+    }
+
+    fn copy_and_recycle(&self, msg: Message, buffer: &mut [u8]) -> usize {
+        let len = msg.get_body().len();
+        let copied = cmp::min(len, buffer.len());
+
+        buffer[..copied].copy_from_slice(&msg.get_body()[..copied]);
+        self.buffer_pool.recycle(msg);
+        len
+    }
+
+    /// Receives up to `max` messages with a single request to the I/O thread,
+    /// waiting at most `timeout` for the first one, `None` meaning no limit.  
+    /// The batch then takes the messages that are already available, without waiting for more,
+    /// and returns as soon as `max` messages have been received or none is left.
+    /// An error, such as `TimedOut`, is returned only when no message was received.
+    pub fn recv_batch(&mut self, max: usize, timeout: Option<Duration>) -> io::Result<Vec<Message>> {
+        let request = Request::RecvBatch(max, timeout);
+
+        self.call(request, |reply| self.on_recv_batch_reply(reply))
+    }
+
+    fn on_recv_batch_reply(&self, reply: Reply) -> io::Result<Vec<Message>> {
+        match reply {
+            Reply::RecvBatch(msgs) => Ok(msgs),
+            Reply::Err(e) => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
     fn on_recv_reply(&self, reply: Reply) -> io::Result<Message> {
         match reply {
             Reply::Recv(msg) => Ok(msg),
@@ -216,6 +314,15 @@ impl Socket {
         }
     }
 
+    /// Gives the buffers of a message back to the session, once its content is no longer needed.  
+    /// They are then used by the transports to receive the next messages, saving an allocation each.
+    /// A buffer returned by [recv](#method.recv) can be recycled as well, after being converted with `Message::from`.
+    /// Buffers are shared by all the sockets of the session, see
+    /// [SessionBuilder::with_buffer_pool_size](struct.SessionBuilder.html#method.with_buffer_pool_size).
+    pub fn recycle(&self, msg: Message) {
+        self.buffer_pool.recycle(msg)
+    }
+
 /*****************************************************************************/
 /*                                                                           */
 /* options                                                                   */
@@ -280,6 +387,23 @@ impl Socket {
         }
     }
 
+    /// Reads the current value of a socket option.
+    /// See [ConfigOptionKind](core/config/enum.ConfigOptionKind.html) to get the list of options.
+    /// The value is returned as the [ConfigOption](core/config/enum.ConfigOption.html) variant of the same name.
+    pub fn get_option(&mut self, kind: ConfigOptionKind) -> io::Result<ConfigOption> {
+        let request = Request::GetOption(kind);
+
+        self.call(request, |reply| self.on_get_option_reply(reply))
+    }
+
+    fn on_get_option_reply(&self, reply: Reply) -> io::Result<ConfigOption> {
+        match reply {
+            Reply::GetOption(opt) => Ok(opt),
+            Reply::Err(e)         => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
 /*****************************************************************************/
 /*                                                                           */
 /* backend                                                                   */
@@ -291,7 +415,7 @@ impl Socket {
     }
 
     fn execute_request(&self, request: Request) -> io::Result<Reply> {
-        self.send_request(request).and_then(|_| self.recv_reply())
+        self.reply_receiver.execute(request, |request| self.send_request(request))
     }
 
     fn send_request(&self, request: Request) -> io::Result<()> {
@@ -309,6 +433,10 @@ impl Socket {
 
 impl Drop for Socket {
     fn drop(&mut self) {
+        if self.detached {
+            return;
+        }
+
         let _ = self.send_request(Request::Close);
         let _ = self.recv_reply();
     }
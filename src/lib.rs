@@ -63,6 +63,8 @@ extern crate mio;
 extern crate mio_uds;
 extern crate iovec;
 
+#[cfg(unix)]
+extern crate libc;
 #[cfg(windows)]
 extern crate mio_named_pipes;
 #[cfg(windows)]
@@ -71,6 +73,7 @@ extern crate winapi;
 pub mod core;
 pub mod proto;
 pub mod transport;
+pub mod testing;
 
 #[doc(hidden)]
 mod reactor;
@@ -84,17 +87,31 @@ mod io_error;
 
 pub use facade::session::SessionBuilder;
 pub use facade::session::Session;
+pub use facade::driver::Driver;
+pub use facade::driver::ReplyPending;
+pub use reactor::clock::VirtualClock;
 pub use facade::socket::Socket;
 pub use facade::device::Device;
+pub use facade::device::BridgeHook;
+pub use facade::device::Direction;
+pub use facade::device::DeviceHandle;
+pub use core::device::DeviceCounters;
 pub use facade::probe::Probe;
 pub use facade::endpoint::Endpoint;
 pub use core::Message;
+pub use core::bytes::Bytes;
+pub use core::EndpointId;
+pub use core::PipeInfo;
+pub use core::PeerCred;
 pub use core::PollReq;
 pub use core::PollRes;
 pub use core::config::ConfigOption;
+pub use core::config::ConfigOptionKind;
+pub use core::config::TcpKeepalive;
 
 pub use transport::tcp::Tcp;
 pub use transport::ipc::Ipc;
+pub use transport::memory::Memory;
 
 pub use proto::pair::Pair;
 pub use proto::publ::Pub;
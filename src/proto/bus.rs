@@ -346,17 +346,16 @@ fn decode(raw_msg: Message, eid: EndpointId) -> Message {
     msg
 }
 
-fn encode(msg: Message) -> (Message, Option<EndpointId>) {
+fn encode(mut msg: Message) -> (Message, Option<EndpointId>) {
     if msg.get_header().len() < 4 {
         return (msg, None);
     }
 
-    let (mut header, body) = msg.split();
-    let remaining_header = header.split_off(4);
-    let originator = BigEndian::read_u32(&header) as usize;
-    let raw_msg = Message::from_header_and_body(remaining_header, body);
+    let remaining_header = msg.header.split_off(4);
+    let originator = BigEndian::read_u32(&msg.header) as usize;
 
-    (raw_msg, Some(EndpointId::from(originator)))
+    msg.header = remaining_header;
+    (msg, Some(EndpointId::from(originator)))
 }
 
 /*****************************************************************************/
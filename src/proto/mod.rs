@@ -5,6 +5,9 @@
 // This file may not be copied, modified, or distributed except according to those terms.
 
 //! Scalability protocols provided by scaproust
+//!
+//! The `pipes`, `priolist` and `policy` modules contain the helpers the built-in protocols are made of.
+//! They can be used to implement additional protocols, see [`Protocol`](../core/socket/trait.Protocol.html).
 
 pub mod pair;
 pub mod push;
@@ -17,13 +20,17 @@ pub mod surv;
 pub mod resp;
 pub mod bus;
 
-mod pipes;
-mod priolist;
-mod policy;
+pub mod pipes;
+pub mod priolist;
+pub mod policy;
+
+use std::error;
+use std::fmt;
+use std::io;
 
 use core::Scheduled;
 
-#[doc(hidden)]
+/// Timer associated to a pending send or recv operation, to be cancelled once the operation is replied.
 pub type Timeout = Option<Scheduled>;
 
 /// **One-to-one protocol**   
@@ -147,3 +154,53 @@ pub const RESPONDENT: u16 = (6 * 16) + 3;
 /// Sent messages are distributed to all nodes in the topology. 
 /// Incoming messages from all other nodes in the topology are fair-queued in the socket.
 pub const BUS:        u16 = (7 * 16)    ;
+
+/// Returns the name of the built-in protocol having this id.
+pub fn name(id: u16) -> Option<&'static str> {
+    match id {
+        PAIR       => Some("Pair"),
+        PUB        => Some("Pub"),
+        SUB        => Some("Sub"),
+        REQ        => Some("Req"),
+        REP        => Some("Rep"),
+        PUSH       => Some("Push"),
+        PULL       => Some("Pull"),
+        SURVEYOR   => Some("Surveyor"),
+        RESPONDENT => Some("Respondent"),
+        BUS        => Some("Bus"),
+        _          => None
+    }
+}
+
+/// Error of a pipe whose peer presented, during the handshake,
+/// a protocol the socket cannot communicate with.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ProtocolMismatch {
+    /// Protocol id the peer was expected to present.
+    pub expected: u16,
+    /// Protocol id the peer presented.
+    pub received: u16
+}
+
+impl ProtocolMismatch {
+    /// Returns the mismatch carried by the error, if any.
+    pub fn from_io_error(err: &io::Error) -> Option<ProtocolMismatch> {
+        err.get_ref().and_then(|inner| inner.downcast_ref::<ProtocolMismatch>()).cloned()
+    }
+}
+
+impl From<ProtocolMismatch> for io::Error {
+    fn from(mismatch: ProtocolMismatch) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, mismatch)
+    }
+}
+
+impl fmt::Display for ProtocolMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "protocol mismatch: expected a {} peer ({}) but received {} ({})",
+            name(self.expected).unwrap_or("unknown"), self.expected,
+            name(self.received).unwrap_or("unknown"), self.received)
+    }
+}
+
+impl error::Error for ProtocolMismatch {}
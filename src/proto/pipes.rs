@@ -4,6 +4,8 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
+//! Storage for the pipes of a protocol.
+
 use std::collections::HashMap;
 use std::rc::Rc;
 
@@ -11,6 +13,7 @@ use core::{BuildIdHasher, EndpointId, Message};
 use core::endpoint::Pipe;
 use core::context::Context;
 
+/// Pipes indexed by endpoint id.
 pub struct PipeCollection {
     pipes: HashMap<EndpointId, Pipe, BuildIdHasher>
 }
@@ -28,10 +31,12 @@ impl PipeCollection {
         }
     }
 
+    /// Stores the pipe, returning the previous one with the same id.
     pub fn insert(&mut self, id: EndpointId, pipe: Pipe) -> Option<Pipe> {
         self.pipes.insert(id, pipe)
     }
 
+    /// Removes the pipe, typically from `Protocol::remove_pipe`.
     pub fn remove(&mut self, id: &EndpointId) -> Option<Pipe> {
         self.pipes.remove(id)
     }
@@ -40,6 +45,7 @@ impl PipeCollection {
         self.pipes.get_mut(id)
     }
 
+    /// Sends the message on the specified pipe, returns `None` if there is no such pipe.
     pub fn send_to(&mut self, ctx: &mut Context, msg: Rc<Message>, eid: EndpointId) -> Option<EndpointId> {
         self.pipes.get_mut(&eid).map_or(None, |pipe| {
             pipe.send(ctx, msg); 
@@ -47,6 +53,7 @@ impl PipeCollection {
         })
     }
 
+    /// Starts receiving on the specified pipe, returns `None` if there is no such pipe.
     pub fn recv_from(&mut self, ctx: &mut Context, eid: EndpointId) -> Option<EndpointId> {
         self.pipes.get_mut(&eid).map_or(None, |pipe| {
             pipe.recv(ctx); 
@@ -54,6 +61,7 @@ impl PipeCollection {
         })
     }
 
+    /// Closes and removes all the pipes, typically from `Protocol::close`.
     pub fn close_all(&mut self, ctx: &mut Context) {
         for (_, pipe) in self.pipes.drain() {
             pipe.close(ctx);
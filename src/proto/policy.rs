@@ -4,6 +4,9 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
+//! Distribution policies shared by the protocols.
+
+/// Sends a message to every ready pipe.
 pub mod broadcast {
 
     use std::collections::HashSet;
@@ -13,6 +16,7 @@ pub mod broadcast {
     use core::context::Context;
     use proto::pipes::PipeCollection;
 
+    /// Sends the message to all the ready pipes, which are no longer ready afterwards.
     pub fn send_to_all(
         bc: &mut HashSet<EndpointId>, 
         pipes: &mut PipeCollection,
@@ -23,6 +27,7 @@ pub mod broadcast {
             pipes.get_mut(&id).map(|pipe| pipe.send(ctx, msg.clone()));
         }
     }
+    /// Same as `send_to_all` but skips one pipe, which stays ready.
     pub fn send_to_all_except(
         bc: &mut HashSet<EndpointId>, 
         pipes: &mut PipeCollection,
@@ -37,6 +42,7 @@ pub mod broadcast {
     }
 }
 
+/// Receives from each ready pipe in turn.
 pub mod fair_queue {
 
     use core::EndpointId;
@@ -44,11 +50,13 @@ pub mod fair_queue {
     use proto::priolist::Priolist;
     use proto::pipes::PipeCollection;
 
+    /// Starts receiving on the next ready pipe, returns the pipe id or `None` if no pipe is ready.
     pub fn recv(fq: &mut Priolist, pipes: &mut PipeCollection, ctx: &mut Context) -> Option<EndpointId> {
         fq.pop().map_or(None, |eid| pipes.recv_from(ctx, eid))
     }
 }
 
+/// Sends each message to the next ready pipe.
 pub mod load_balancing {
 
     use std::rc::Rc;
@@ -58,6 +66,7 @@ pub mod load_balancing {
     use proto::priolist::Priolist;
     use proto::pipes::PipeCollection;
 
+    /// Sends the message on the next ready pipe, returns the pipe id or `None` if no pipe is ready.
     pub fn send(
         lb: &mut Priolist, 
         pipes: &mut PipeCollection, 
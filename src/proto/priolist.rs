@@ -4,6 +4,8 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
+//! Priority aware selection of the endpoint to send to or receive from.
+
 /*
 PROTOCOL    |   SEND          |   RECV
 -------------------------------------------
@@ -51,6 +53,8 @@ use std::ops::Range;
 
 use core::EndpointId;
 
+/// Round robin among the active endpoints having the highest priority.
+/// Priorities go from 1, the highest, to 16, the lowest.
 pub struct Priolist {
     items: Vec<Item>,
     current: Option<(usize, u8)>
@@ -71,10 +75,12 @@ impl Priolist {
         }
     }
 
+    /// Adds an inactive endpoint with the specified priority.
     pub fn insert(&mut self, id: EndpointId, prio: u8) {
         self.items.push(Item::new(id, prio))
     }
 
+    /// Forgets the endpoint.
     pub fn remove(&mut self, id: &EndpointId) {
         if let Some(index) = self.find_by_id_in_all(id) {
             self.remove_at_index(index);
@@ -82,14 +88,19 @@ impl Priolist {
     }
 
     fn remove_at_index(&mut self, index: usize) {
-        let item = self.items.swap_remove(index);
-        let priority = item.priority;
+        let last = self.len() - 1;
 
-        if self.current == Some((index, priority)) {
-            self.compute_next(index, priority);
+        self.items.swap_remove(index);
+
+        // The last item has been moved to the removed index
+        match self.current {
+            Some((cur_idx, cur_prio)) if cur_idx == index => self.compute_next(index, cur_prio),
+            Some((cur_idx, cur_prio)) if cur_idx == last => self.set_current(index, cur_prio),
+            _ => {}
         }
     }
 
+    /// Marks the endpoint as ready, typically from `on_send_ready` or `on_recv_ready`.
     pub fn activate(&mut self, id: &EndpointId) {
         if let Some(index) = self.find_by_id_in_all(id) {
             self.activate_at_index(index);
@@ -124,6 +135,7 @@ impl Priolist {
         self.items[index].active = active;
     }
 
+    /// Returns the next endpoint to use and deactivates it.
     pub fn pop(&mut self) -> Option<EndpointId> {
         if let Some((index, priority)) = self.current.take() {
             self.set_index_active(index, false);
@@ -135,10 +147,12 @@ impl Priolist {
         }
     }
 
+    /// Whether at least one endpoint is active.
     pub fn peek(&self) -> bool {
         self.current.is_some()
     }
 
+    /// Marks the endpoint as not ready.
     pub fn deactivate(&mut self, id: &EndpointId) {
         if let Some(index) = self.find_by_id_in_all(id) {
             self.deactivate_at_index(index);
@@ -486,4 +500,18 @@ mod tests {
         priolist.deactivate(&three);
         assert_eq!(Some(four), priolist.pop());
     }
+
+    #[test]
+    fn remove_keeps_track_of_the_moved_next() {
+        let mut priolist = Priolist::new();
+        let first = EndpointId::from(0);
+        let second = EndpointId::from(1);
+
+        priolist.insert(first, 8);
+        priolist.insert(second, 8);
+        priolist.activate(&second);
+        priolist.remove(&first);
+        assert_eq!(Some(second), priolist.pop());
+        assert_eq!(None, priolist.pop());
+    }
 }
\ No newline at end of file
@@ -5,6 +5,7 @@
 // This file may not be copied, modified, or distributed except according to those terms.
 
 use std::collections::HashSet;
+use std::mem;
 use std::rc::Rc;
 use std::sync::mpsc::Sender;
 
@@ -441,39 +442,10 @@ impl Inner {
     }
 
     fn raw_msg_to_msg(&self, raw_msg: Message, eid: EndpointId) -> Option<Message> {
-        let (mut header, mut body) = raw_msg.split();
-        let mut hops = 0;
-        let mut eid_bytes: [u8; 4] = [0; 4];
-        let eid_usize: usize = eid.into();
-
-        BigEndian::write_u32(&mut eid_bytes[0..4], eid_usize as u32);
-
-        header.reserve(4);
-        header.extend_from_slice(&eid_bytes[..]);
-
-        loop {
-            if hops >= self.ttl {
-                return None;
-            }
-            hops += 1;
-
-            if body.len() < 4 {
-                return None;
-            }
-
-            let tail = body.split_off(4);
-            header.reserve(4);
-            header.extend_from_slice(&body);
-
-            let position = header.len() - 4;
-            if header[position] & 0x80 != 0 {
-                return Some(Message::from_header_and_body(header, tail));
-            }
-            body = tail;
-        }
+        decode(raw_msg, eid, self.ttl)
     }
-    fn msg_to_raw_msg(&self, msg: Message) -> Option<(Message, EndpointId)> {
-        let (mut header, body) = msg.split();
+    fn msg_to_raw_msg(&self, mut msg: Message) -> Option<(Message, EndpointId)> {
+        let mut header = mem::replace(&mut msg.header, Vec::new());
 
         if !self.is_device_item {
             let backtrace = self.get_backtrace();
@@ -489,8 +461,9 @@ impl Inner {
         let tail = header.split_off(4);
         let eid_u32 = BigEndian::read_u32(&header);
         let eid = EndpointId::from(eid_u32 as usize);
-        
-        Some((Message::from_header_and_body(tail, body), eid))
+
+        msg.header = tail;
+        Some((msg, eid))
     }
     fn set_backtrace(&mut self, bt: &[u8]) {
         self.backtrace.clear();
@@ -506,3 +479,41 @@ impl Inner {
         self.pipes.close_all(ctx)
     }
 }
+
+/// Moves the backtrace from the body of a received survey to its header,
+/// the id of the pipe it was received from being pushed first.
+/// Returns `None` if the backtrace is incomplete or has more than `ttl` hops.
+#[doc(hidden)]
+pub fn decode(raw_msg: Message, eid: EndpointId, ttl: u8) -> Option<Message> {
+    let origin = raw_msg.get_origin();
+    let (mut header, mut body) = raw_msg.split();
+    let mut hops = 0;
+    let mut eid_bytes: [u8; 4] = [0; 4];
+    let eid_usize: usize = eid.into();
+
+    BigEndian::write_u32(&mut eid_bytes[0..4], eid_usize as u32);
+
+    header.reserve(4);
+    header.extend_from_slice(&eid_bytes[..]);
+
+    loop {
+        if hops >= ttl {
+            return None;
+        }
+        hops += 1;
+
+        if body.len() < 4 {
+            return None;
+        }
+
+        let tail = body.split_off(4);
+        header.reserve(4);
+        header.extend_from_slice(&body);
+
+        let position = header.len() - 4;
+        if header[position] & 0x80 != 0 {
+            return Some(Message::from_header_and_body(header, tail).with_origin(origin));
+        }
+        body = tail;
+    }
+}
@@ -11,7 +11,7 @@ use std::io;
 
 use core::{EndpointId, Message};
 use core::socket::{Protocol, Reply};
-use core::config::ConfigOption;
+use core::config::{ConfigOption, ConfigOptionKind};
 use core::endpoint::Pipe;
 use core::context::Context;
 use super::priolist::Priolist;
@@ -147,6 +147,12 @@ impl Protocol for Sub {
             _ => Err(invalid_input_io_error("option not supported"))
         }
     }
+    fn get_option(&self, kind: ConfigOptionKind) -> io::Result<ConfigOption> {
+        match kind {
+            ConfigOptionKind::Subscriptions => Ok(ConfigOption::Subscriptions(self.inner.subscriptions())),
+            _ => Err(invalid_input_io_error("option not supported"))
+        }
+    }
     fn close(&mut self, ctx: &mut Context) {
         self.inner.close(ctx)
     }
@@ -306,6 +312,10 @@ impl Inner {
         self.subscriptions.remove(&subscription.into_bytes());
     }
 
+    fn subscriptions(&self) -> Vec<String> {
+        self.subscriptions.iter().map(|s| String::from_utf8_lossy(s).into_owned()).collect()
+    }
+
     fn accept(&self, msg: &Message) -> bool {
         let payload = msg.get_body();
         
@@ -332,7 +342,7 @@ mod tests {
     use core::{EndpointId, Message, Scheduled};
     use core::socket::{Protocol, Reply};
     use core::context::{Event};
-    use core::config::ConfigOption;
+    use core::config::{ConfigOption, ConfigOptionKind};
     use core::tests::*;
 
     use super::*;
@@ -392,6 +402,23 @@ mod tests {
         assert_eq!(Event::CanRecv(true), raised_evts[2]);
     }
 
+    #[test]
+    fn subscriptions_can_be_read_back() {
+        let (tx, _) = mpsc::channel();
+        let mut sub = Sub::from(tx);
+        let _ = sub.set_option(ConfigOption::Subscribe(String::from("A")));
+        let _ = sub.set_option(ConfigOption::Subscribe(String::from("B")));
+        let _ = sub.set_option(ConfigOption::Unsubscribe(String::from("A")));
+
+        let opt = sub.get_option(ConfigOptionKind::Subscriptions).expect("Subscriptions should be readable");
+        let subscriptions = match opt {
+            ConfigOption::Subscriptions(x) => x,
+            _ => Vec::new()
+        };
+
+        assert_eq!(vec![String::from("B")], subscriptions);
+    }
+
     /*//#[test]
     fn when_recv_ack_event_is_raised_if_there_is_another_pipe_ready() {
         let (tx, _) = mpsc::channel();
@@ -16,7 +16,7 @@ use byteorder::*;
 
 use core::{EndpointId, Message};
 use core::socket::{Protocol, Reply};
-use core::config::ConfigOption;
+use core::config::{ConfigOption, ConfigOptionKind};
 use core::endpoint::Pipe;
 use core::context::{Context, Schedulable};
 use super::priolist::Priolist;
@@ -161,6 +161,12 @@ impl Protocol for Surveyor {
             _ => Err(invalid_input_io_error("option not supported"))
         }
     }
+    fn get_option(&self, kind: ConfigOptionKind) -> io::Result<ConfigOption> {
+        match kind {
+            ConfigOptionKind::SurveyDeadline => Ok(ConfigOption::SurveyDeadline(self.inner.deadline)),
+            _ => Err(invalid_input_io_error("option not supported"))
+        }
+    }
     fn on_timer_tick(&mut self, ctx: &mut Context, task: Schedulable) {
         if let Schedulable::SurveyCancel = task {
             self.apply(ctx, |s, ctx, inner| s.on_survey_timeout(ctx, inner))
@@ -481,11 +487,15 @@ fn encode(msg: Message, survey_id: u32) -> Message {
     raw_msg
 }
 
-fn decode(raw_msg: Message) -> Option<(Message, u32)> {
+/// Moves the survey id from the body of a received message to its header.
+/// Returns the message and the id, or `None` if the body is too short to hold one.
+#[doc(hidden)]
+pub fn decode(raw_msg: Message) -> Option<(Message, u32)> {
     if raw_msg.get_body().len() < 4 {
         return None;
     }
 
+    let origin = raw_msg.get_origin();
     let (mut header, mut payload) = raw_msg.split();
     let body = payload.split_off(4);
     let survey_id = BigEndian::read_u32(&payload);
@@ -496,7 +506,7 @@ fn decode(raw_msg: Message) -> Option<(Message, u32)> {
         header.extend_from_slice(&payload);
     }
 
-    Some((Message::from_header_and_body(header, body), survey_id))
+    Some((Message::from_header_and_body(header, body).with_origin(origin), survey_id))
 }
 
 /*****************************************************************************/
@@ -11,19 +11,20 @@ use std::io;
 use std::time::Duration;
 
 use mio::{Evented, Token, Ready, PollOpt};
-use mio::timer::{Timer, Timeout};
 
 use core::context;
 use core::device;
 use core::probe;
 use core::network::Network;
-use core::{BuildIdHasher, SocketId, EndpointId, DeviceId, ProbeId, Message, EndpointTmpl, Scheduled};
+use core::{BuildIdHasher, SocketId, EndpointId, DeviceId, ProbeId, Message, EndpointTmpl, PeerCred, Scheduled};
+use core::pool::BufferPool;
 use transport::{Transport, Destination};
 use transport::endpoint::*;
 use transport::pipe;
 use transport::acceptor;
 use super::bus::EventLoopBus;
 use super::{Signal, Task};
+use super::clock::Clock;
 use sequence::Sequence;
 use io_error::*;
 use super::event_loop::EventLoop;
@@ -38,8 +39,7 @@ pub struct SocketEventLoopContext<'a> {
     socket_id: SocketId,
     signal_tx: &'a mut EventLoopBus<Signal>,
     endpoints: &'a mut EndpointCollection,
-    schedule: &'a mut Schedule,
-    timer: &'a mut Timer<Task>
+    clock: &'a mut Clock
 }
 
 pub struct EndpointEventLoopContext<'a, 'b> {
@@ -57,8 +57,7 @@ pub struct DeviceEventLoopContext<'a> {
 pub struct ProbeEventLoopContext<'a> {
     probe_id: ProbeId,
     signal_tx: &'a mut EventLoopBus<Signal>,
-    schedule: &'a mut Schedule,
-    timer: &'a mut Timer<Task>
+    clock: &'a mut Clock
 }
 
 pub struct PipeController {
@@ -76,15 +75,11 @@ pub struct AcceptorController {
 pub struct EndpointCollection {
     ids: Sequence,
     transports: HashMap<String, Box<Transport + Send>, BuildIdHasher>,
+    buffer_pool: BufferPool,
     pipes: HashMap<EndpointId, PipeController, BuildIdHasher>,
     acceptors: HashMap<EndpointId, AcceptorController, BuildIdHasher>
 }
 
-pub struct Schedule {
-    ids: Sequence,
-    items: HashMap<Scheduled, Timeout, BuildIdHasher>
-}
-
 impl Registrar for EventLoop {
     fn register(&mut self, io: &Evented, tok: Token, interest: Ready, opt: PollOpt) -> io::Result<()> {
         self.register(io, tok, interest, opt)
@@ -129,6 +124,18 @@ impl PipeController {
             registrar: registrar
         }
     }
+
+    fn local_addr(&self) -> Option<String> {
+        self.pipe.local_addr()
+    }
+
+    fn peer_addr(&self) -> Option<String> {
+        self.pipe.peer_addr()
+    }
+
+    fn peer_cred(&self) -> Option<PeerCred> {
+        self.pipe.peer_cred()
+    }
 }
 
 impl AcceptorController {
@@ -142,11 +149,17 @@ impl AcceptorController {
         let mut ctx = self.create_context(registrar, signal_bus);
 
         match cmd {
-            acceptor::Command::Open  => self.acceptor.open(&mut ctx),
-            acceptor::Command::Close => self.acceptor.close(&mut ctx),
+            acceptor::Command::Open   => self.acceptor.open(&mut ctx),
+            acceptor::Command::Close  => self.acceptor.close(&mut ctx),
+            acceptor::Command::Pause  => self.acceptor.pause(&mut ctx),
+            acceptor::Command::Resume => self.acceptor.resume(&mut ctx)
         }
     }
 
+    fn local_addr(&self) -> Option<String> {
+        self.acceptor.local_addr()
+    }
+
     fn create_context<'a, 'b>(&self, registrar: &'b mut Registrar, signal_bus: &'a mut EventLoopBus<Signal>) -> EndpointEventLoopContext<'a, 'b> {
         EndpointEventLoopContext {
             socket_id: self.socket_id,
@@ -158,10 +171,11 @@ impl AcceptorController {
 }
 
 impl EndpointCollection {
-    pub fn new(seq: Sequence, transports: HashMap<String, Box<Transport + Send>, BuildIdHasher>) -> EndpointCollection {
+    pub fn new(seq: Sequence, transports: HashMap<String, Box<Transport + Send>, BuildIdHasher>, buffer_pool: BufferPool) -> EndpointCollection {
         EndpointCollection {
             ids: seq,
             transports: transports,
+            buffer_pool: buffer_pool,
             pipes: HashMap::default(),
             acceptors: HashMap::default()
         }
@@ -177,6 +191,10 @@ impl EndpointCollection {
         self.pipes.get_mut(&eid)
     }
 
+    fn get_pipe(&self, eid: EndpointId) -> Option<&PipeController> {
+        self.pipes.get(&eid)
+    }
+
     pub fn insert_pipe(&mut self, sid: SocketId, pipe: Box<pipe::Pipe>) -> EndpointId {
         let eid = EndpointId::from(self.ids.next());
         
@@ -203,6 +221,10 @@ impl EndpointCollection {
         self.acceptors.get_mut(&eid)
     }
 
+    fn get_acceptor(&self, eid: EndpointId) -> Option<&AcceptorController> {
+        self.acceptors.get(&eid)
+    }
+
     fn insert_acceptor(&mut self, sid: SocketId, acceptor: Box<acceptor::Acceptor>) -> EndpointId {
         let eid = EndpointId::from(self.ids.next());
 
@@ -222,23 +244,6 @@ impl EndpointCollection {
     }
 }
 
-impl Schedule {
-    pub fn new(seq: Sequence) -> Schedule {
-        Schedule { 
-            ids: seq,
-            items: HashMap::default() 
-        }
-    }
-    fn insert(&mut self, handle: Timeout) -> Scheduled {
-        let scheduled = Scheduled::from(self.ids.next()); 
-        self.items.insert(scheduled, handle);
-        scheduled
-    }
-    fn remove(&mut self, scheduled: Scheduled) -> Option<Timeout> {
-        self.items.remove(&scheduled)
-    }
-}
-
 /*****************************************************************************/
 /*                                                                           */
 /* Socket context                                                            */
@@ -250,14 +255,12 @@ impl<'a> SocketEventLoopContext<'a> {
         sid: SocketId,
         tx: &'a mut EventLoopBus<Signal>,
         eps: &'a mut EndpointCollection,
-        sched: &'a mut Schedule,
-        timer: &'a mut Timer<Task>) -> SocketEventLoopContext<'a> {
+        clock: &'a mut Clock) -> SocketEventLoopContext<'a> {
         SocketEventLoopContext {
             socket_id: sid,
             signal_tx: tx,
             endpoints: eps,
-            schedule: sched,
-            timer: timer
+            clock: clock
         }
     }
 
@@ -301,7 +304,9 @@ impl<'a> SocketEventLoopContext<'a> {
             addr: addr,
             pids: tmpl.pids,
             tcp_no_delay: tmpl.spec.desc.tcp_no_delay,
+            tcp_keepalive: tmpl.spec.desc.tcp_keepalive,
             recv_max_size: tmpl.spec.desc.recv_max_size,
+            buffer_pool: &self.endpoints.buffer_pool
         };
 
         transport.connect(&dest)
@@ -321,7 +326,9 @@ impl<'a> SocketEventLoopContext<'a> {
             addr: addr,
             pids: tmpl.pids,
             tcp_no_delay: tmpl.spec.desc.tcp_no_delay,
+            tcp_keepalive: tmpl.spec.desc.tcp_keepalive,
             recv_max_size: tmpl.spec.desc.recv_max_size,
+            buffer_pool: &self.endpoints.buffer_pool
         };
 
         transport.bind(&dest)
@@ -366,27 +373,41 @@ impl<'a> Network for SocketEventLoopContext<'a> {
             self.send_acceptor_cmd(endpoint_id, acceptor::Command::Close)
         }
     }
+    fn pause(&mut self, endpoint_id: EndpointId) {
+        self.send_acceptor_cmd(endpoint_id, acceptor::Command::Pause)
+    }
+    fn resume(&mut self, endpoint_id: EndpointId) {
+        self.send_acceptor_cmd(endpoint_id, acceptor::Command::Resume)
+    }
     fn send(&mut self, endpoint_id: EndpointId, msg: Rc<Message>) {
         self.send_pipe_cmd(endpoint_id, pipe::Command::Send(msg));
     }
     fn recv(&mut self, endpoint_id: EndpointId) {
         self.send_pipe_cmd(endpoint_id, pipe::Command::Recv);
     }
+    fn local_addr(&self, endpoint_id: EndpointId, remote: bool) -> Option<String> {
+        if remote {
+            self.endpoints.get_pipe(endpoint_id).and_then(|pipe| pipe.local_addr())
+        } else {
+            self.endpoints.get_acceptor(endpoint_id).and_then(|acceptor| acceptor.local_addr())
+        }
+    }
+    fn peer_addr(&self, endpoint_id: EndpointId) -> Option<String> {
+        self.endpoints.get_pipe(endpoint_id).and_then(|pipe| pipe.peer_addr())
+    }
+    fn peer_cred(&self, endpoint_id: EndpointId) -> Option<PeerCred> {
+        self.endpoints.get_pipe(endpoint_id).and_then(|pipe| pipe.peer_cred())
+    }
 
 }
 
 impl<'a> context::Scheduler for SocketEventLoopContext<'a> {
     fn schedule(&mut self, schedulable: context::Schedulable, delay: Duration) -> io::Result<Scheduled> {
         let task = Task::Socket(self.socket_id, schedulable);
-        let handle = try!(self.timer.set_timeout(delay, task).map_err(from_timer_error));
-        let scheduled = self.schedule.insert(handle);
-        
-        Ok(scheduled)
+        self.clock.schedule(task, delay)
     }
     fn cancel(&mut self, scheduled: Scheduled) {
-        if let Some(handle) = self.schedule.remove(scheduled) {
-            self.timer.cancel_timeout(&handle);
-        }
+        self.clock.cancel(scheduled);
     }
 }
 
@@ -496,13 +517,11 @@ impl<'a> ProbeEventLoopContext<'a> {
     pub fn new(
         id: ProbeId,
         tx: &'a mut EventLoopBus<Signal>,
-        sched: &'a mut Schedule,
-        timer: &'a mut Timer<Task>) -> ProbeEventLoopContext<'a> {
+        clock: &'a mut Clock) -> ProbeEventLoopContext<'a> {
         ProbeEventLoopContext {
             probe_id: id,
             signal_tx: tx,
-            schedule: sched,
-            timer: timer
+            clock: clock
         }
     }
 }
@@ -524,15 +543,10 @@ impl<'a> probe::Context for ProbeEventLoopContext<'a> {
 impl<'a> probe::Scheduler for ProbeEventLoopContext<'a> {
     fn schedule(&mut self, schedulable: probe::Schedulable, delay: Duration) -> io::Result<Scheduled> {
         let task = Task::Probe(self.probe_id, schedulable);
-        let handle = try!(self.timer.set_timeout(delay, task).map_err(from_timer_error));
-        let scheduled = self.schedule.insert(handle);
-        
-        Ok(scheduled)
+        self.clock.schedule(task, delay)
     }
     fn cancel(&mut self, scheduled: Scheduled) {
-        if let Some(handle) = self.schedule.remove(scheduled) {
-            self.timer.cancel_timeout(&handle);
-        }
+        self.clock.cancel(scheduled);
     }
 }
 
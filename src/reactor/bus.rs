@@ -4,26 +4,45 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
-use std::cell::RefCell;
 use std::io::Result;
 use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
 
 use mio::{Registration, SetReadiness, Evented, Poll, Token, Ready, PollOpt};
 
-use io_error::*;
-
 pub struct EventLoopBus<T> {
     queue: VecDeque<T>,
-    registration: RefCell<Option<Registration>>,
-    readiness: RefCell<Option<SetReadiness>>
+    shared: Option<Arc<Mutex<VecDeque<T>>>>,
+    registration: Registration,
+    readiness: SetReadiness
+}
+
+/// Sends items to an `EventLoopBus` from another thread.
+pub struct EventLoopBusSender<T> {
+    queue: Arc<Mutex<VecDeque<T>>>,
+    readiness: SetReadiness
 }
 
 impl<T> EventLoopBus<T> {
     pub fn new() -> EventLoopBus<T> {
+        let (registration, readiness) = Registration::new2();
+
         EventLoopBus {
             queue: VecDeque::new(),
-            registration: RefCell::new(None),
-            readiness: RefCell::new(None)
+            shared: None,
+            registration: registration,
+            readiness: readiness
+        }
+    }
+
+    /// Creates a sender that can be moved to another thread.
+    /// Items sent this way are received after the ones sent locally.
+    pub fn sender(&mut self) -> EventLoopBusSender<T> {
+        let shared = self.shared.get_or_insert_with(|| Arc::new(Mutex::new(VecDeque::new())));
+
+        EventLoopBusSender {
+            queue: shared.clone(),
+            readiness: self.readiness.clone()
         }
     }
 
@@ -36,52 +55,66 @@ impl<T> EventLoopBus<T> {
     }
 
     pub fn recv(&mut self) -> Option<T> {
-        if self.queue.len() == 1 {
-            self.set_readiness(Ready::empty());
+        match self.shared {
+            Some(ref shared) => {
+                let mut shared = lock(shared);
+                let item = self.queue.pop_front().or_else(|| shared.pop_front());
+
+                // clear the readiness while holding the lock so that a concurrent send can't be missed
+                if self.queue.is_empty() && shared.is_empty() {
+                    let _ = self.readiness.set_readiness(Ready::empty());
+                }
+
+                item
+            },
+            None => {
+                if self.queue.len() == 1 {
+                    self.set_readiness(Ready::empty());
+                }
+
+                self.queue.pop_front()
+            }
         }
-
-        self.queue.pop_front()
     }
 
     fn set_readiness(&mut self, events: Ready) {
-        if let Some(ref readiness) = *self.readiness.borrow_mut() {
-            let _ = readiness.set_readiness(events);
-        }
+        let _ = self.readiness.set_readiness(events);
     }
 }
 
-impl<T> Evented for EventLoopBus<T> {
-    fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> Result<()> {
-        if self.registration.borrow().is_some() {
-            return Err(other_io_error("bus already registered"));
-        }
+impl<T> EventLoopBusSender<T> {
+    pub fn send(&self, t: T) {
+        let mut queue = lock(&self.queue);
 
-        let (registration, readiness) = Registration::new2();
+        queue.push_back(t);
 
-        try!(poll.register(&registration, token, interest, opts));
+        let _ = self.readiness.set_readiness(Ready::readable());
+    }
+}
 
-        if self.queue.is_empty() == false {
-            let _ = readiness.set_readiness(Ready::readable());
+impl<T> Clone for EventLoopBusSender<T> {
+    fn clone(&self) -> EventLoopBusSender<T> {
+        EventLoopBusSender {
+            queue: self.queue.clone(),
+            readiness: self.readiness.clone()
         }
+    }
+}
 
-        *self.registration.borrow_mut() = Some(registration);
-        *self.readiness.borrow_mut() = Some(readiness);
+fn lock<T>(queue: &Mutex<VecDeque<T>>) -> MutexGuard<VecDeque<T>> {
+    queue.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
 
-        Ok(())
+impl<T> Evented for EventLoopBus<T> {
+    fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> Result<()> {
+        Evented::register(&self.registration, poll, token, interest, opts)
     }
 
     fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> Result<()> {
-        match *self.registration.borrow_mut() {
-            Some(ref registration) => Evented::reregister(registration, poll, token, interest, opts),
-            None => Err(other_io_error("bus not registered")),
-        }
+        Evented::reregister(&self.registration, poll, token, interest, opts)
     }
 
     fn deregister(&self, poll: &Poll) -> Result<()> {
-        match *self.registration.borrow_mut() {
-            Some(ref registration) => Evented::deregister(registration, poll),
-            None => Err(other_io_error("bus not registered")),
-        }
+        Evented::deregister(&self.registration, poll)
     }
-
 }
@@ -0,0 +1,303 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use mio::{Evented, Poll, Token, Ready, PollOpt, Registration, SetReadiness};
+use mio::timer::{Timer, Timeout, Builder};
+
+use core::{BuildIdHasher, Scheduled};
+use super::{Task, Config};
+use sequence::Sequence;
+use io_error::*;
+
+/// Source of the timer events of a dispatcher,
+/// driven by the system clock unless the session was given a virtual clock.
+pub enum Clock {
+    System(SystemTimer),
+    Virtual(VirtualTimer)
+}
+
+impl Clock {
+    pub fn new(config: &Config) -> Clock {
+        match config.clock {
+            Some(ref clock) => Clock::Virtual(VirtualTimer::new(clock.clone())),
+            None => Clock::System(SystemTimer::new(config))
+        }
+    }
+
+    pub fn schedule(&mut self, task: Task, delay: Duration) -> io::Result<Scheduled> {
+        match *self {
+            Clock::System(ref mut timer) => timer.schedule(task, delay),
+            Clock::Virtual(ref mut timer) => timer.schedule(task, delay)
+        }
+    }
+
+    pub fn cancel(&mut self, scheduled: Scheduled) {
+        match *self {
+            Clock::System(ref mut timer) => timer.cancel(scheduled),
+            Clock::Virtual(ref mut timer) => timer.cancel(scheduled)
+        }
+    }
+
+    /// Returns the next expired task, if any.
+    pub fn poll(&mut self) -> Option<Task> {
+        match *self {
+            Clock::System(ref mut timer) => timer.poll(),
+            Clock::Virtual(ref mut timer) => timer.poll()
+        }
+    }
+
+    fn evented(&self) -> &Evented {
+        match *self {
+            Clock::System(ref timer) => &timer.timer,
+            Clock::Virtual(ref timer) => &timer.registration
+        }
+    }
+}
+
+impl Evented for Clock {
+    fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        self.evented().register(poll, token, interest, opts)
+    }
+    fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        self.evented().reregister(poll, token, interest, opts)
+    }
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        self.evented().deregister(poll)
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* System timer                                                              */
+/*                                                                           */
+/*****************************************************************************/
+
+pub struct SystemTimer {
+    timer: Timer<Task>,
+    ids: Sequence,
+    items: HashMap<Scheduled, Timeout, BuildIdHasher>
+}
+
+impl SystemTimer {
+    fn new(config: &Config) -> SystemTimer {
+        let timer = Builder::default().
+            tick_duration(config.timer_tick).
+            num_slots(config.timer_slots).
+            capacity(config.timer_capacity).
+            build();
+
+        SystemTimer {
+            timer: timer,
+            ids: Sequence::new(),
+            items: HashMap::default()
+        }
+    }
+
+    fn schedule(&mut self, task: Task, delay: Duration) -> io::Result<Scheduled> {
+        let handle = try!(self.timer.set_timeout(delay, task).map_err(from_timer_error));
+        let scheduled = Scheduled::from(self.ids.next());
+
+        self.items.insert(scheduled, handle);
+        Ok(scheduled)
+    }
+
+    fn cancel(&mut self, scheduled: Scheduled) {
+        if let Some(handle) = self.items.remove(&scheduled) {
+            self.timer.cancel_timeout(&handle);
+        }
+    }
+
+    fn poll(&mut self) -> Option<Task> {
+        self.timer.poll()
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Virtual clock                                                             */
+/*                                                                           */
+/*****************************************************************************/
+
+/// A clock that only moves forward when told to, for deterministic tests.
+///
+/// A session built [with a virtual clock](struct.SessionBuilder.html#method.with_virtual_clock)
+/// schedules all its timeouts, such as send and recv timeouts, resend intervals,
+/// survey deadlines and reconnect intervals, against this clock instead of the system one.
+/// They expire when the clock is [advanced](#method.advance) past their deadline,
+/// in deadline order, and never otherwise.
+/// Clones share the same time, so one clock can drive several sessions.
+#[derive(Clone, Default)]
+pub struct VirtualClock {
+    time: Arc<Mutex<VirtualTime>>
+}
+
+#[derive(Default)]
+struct VirtualTime {
+    elapsed: Duration,
+    wakers: Vec<SetReadiness>
+}
+
+impl VirtualClock {
+    /// Creates a clock at time zero.
+    pub fn new() -> VirtualClock {
+        VirtualClock::default()
+    }
+
+    /// Returns the time elapsed since the clock was created.
+    pub fn elapsed(&self) -> Duration {
+        self.lock().elapsed
+    }
+
+    /// Moves the clock forward, waking up the sessions having timeouts expired by the move.
+    /// The expired timeouts are processed asynchronously by the I/O threads,
+    /// or by the next call to [Driver::run_once](struct.Driver.html#method.run_once)
+    /// or [Driver::run_until_idle](struct.Driver.html#method.run_until_idle).
+    pub fn advance(&self, delay: Duration) {
+        let mut time = self.lock();
+
+        time.elapsed += delay;
+        for waker in &time.wakers {
+            let _ = waker.set_readiness(Ready::readable());
+        }
+    }
+
+    fn subscribe(&self) -> (Registration, SetReadiness) {
+        let (registration, set_readiness) = Registration::new2();
+
+        self.lock().wakers.push(set_readiness.clone());
+        (registration, set_readiness)
+    }
+
+    fn lock(&self) -> ::std::sync::MutexGuard<VirtualTime> {
+        self.time.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl fmt::Debug for VirtualClock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "VirtualClock({:?})", self.elapsed())
+    }
+}
+
+pub struct VirtualTimer {
+    clock: VirtualClock,
+    registration: Registration,
+    set_readiness: SetReadiness,
+    ids: Sequence,
+    deadlines: HashMap<Scheduled, (Duration, usize), BuildIdHasher>,
+    tasks: BTreeMap<(Duration, usize), Task>
+}
+
+impl VirtualTimer {
+    fn new(clock: VirtualClock) -> VirtualTimer {
+        let (registration, set_readiness) = clock.subscribe();
+
+        VirtualTimer {
+            clock: clock,
+            registration: registration,
+            set_readiness: set_readiness,
+            ids: Sequence::new(),
+            deadlines: HashMap::default(),
+            tasks: BTreeMap::new()
+        }
+    }
+
+    fn schedule(&mut self, task: Task, delay: Duration) -> io::Result<Scheduled> {
+        let id = self.ids.next();
+        let key = (self.clock.elapsed() + delay, id);
+        let scheduled = Scheduled::from(id);
+
+        self.deadlines.insert(scheduled, key);
+        self.tasks.insert(key, task);
+
+        if self.has_expired_task() {
+            try!(self.set_readiness.set_readiness(Ready::readable()));
+        }
+
+        Ok(scheduled)
+    }
+
+    fn cancel(&mut self, scheduled: Scheduled) {
+        if let Some(key) = self.deadlines.remove(&scheduled) {
+            self.tasks.remove(&key);
+        }
+    }
+
+    fn poll(&mut self) -> Option<Task> {
+        if let Some(key) = self.next_expired_key() {
+            self.deadlines.remove(&Scheduled::from(key.1));
+            return self.tasks.remove(&key);
+        }
+
+        // the clock may be advanced by another thread in the meantime,
+        // so check again once the readiness is cleared
+        let _ = self.set_readiness.set_readiness(Ready::empty());
+        if self.has_expired_task() {
+            let _ = self.set_readiness.set_readiness(Ready::readable());
+        }
+
+        None
+    }
+
+    fn next_expired_key(&self) -> Option<(Duration, usize)> {
+        let now = self.clock.elapsed();
+
+        self.tasks.keys().next().cloned().and_then(|key| if key.0 <= now { Some(key) } else { None })
+    }
+
+    fn has_expired_task(&self) -> bool {
+        self.next_expired_key().is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use core::SocketId;
+    use core::context::Schedulable;
+    use reactor::Task;
+    use super::*;
+
+    fn task(id: usize) -> Task {
+        Task::Socket(SocketId::from(id), Schedulable::SendTimeout)
+    }
+
+    fn socket_of(task: Option<Task>) -> Option<usize> {
+        match task {
+            Some(Task::Socket(id, _)) => Some(id.into()),
+            _ => None
+        }
+    }
+
+    #[test]
+    fn virtual_timer_expires_tasks_in_deadline_order_when_advanced() {
+        let clock = VirtualClock::new();
+        let mut timer = VirtualTimer::new(clock.clone());
+
+        timer.schedule(task(1), Duration::from_secs(2)).unwrap();
+        timer.schedule(task(2), Duration::from_secs(1)).unwrap();
+        let cancelled = timer.schedule(task(3), Duration::from_secs(1)).unwrap();
+        timer.cancel(cancelled);
+
+        assert_eq!(None, socket_of(timer.poll()));
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(Some(2), socket_of(timer.poll()));
+        assert_eq!(None, socket_of(timer.poll()));
+
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(Some(1), socket_of(timer.poll()));
+        assert_eq!(None, socket_of(timer.poll()));
+        assert_eq!(Duration::from_secs(6), clock.elapsed());
+    }
+}
@@ -6,21 +6,24 @@
 
 use std::collections::HashMap;
 use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
 use std::io;
+use std::mem;
 use std::time::Duration;
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
 
 use mio::{Token, Ready, PollOpt};
-use mio::timer::{Timer, Builder};
-use mio::channel::{Receiver};
 
 use core::{BuildIdHasher, SocketId, EndpointId, DeviceId, ProbeId, session, socket, context, endpoint, device, probe};
-use transport::{Transport, pipe, acceptor};
-use super::{Signal, Request, Task};
+use transport::{Transport, Destination, pipe, acceptor};
+use super::{Signal, Request, Relay, RemoteCmd, Task, Config, shard_of};
 use super::event_loop::{EventLoop, EventHandler};
-use super::bus::EventLoopBus;
+use super::bus::{EventLoopBus, EventLoopBusSender};
+use super::clock::Clock;
+use super::mailbox::Inbox;
 use super::adapter::{
     EndpointCollection, 
-    Schedule, 
     SocketEventLoopContext, 
     DeviceEventLoopContext,
     ProbeEventLoopContext };
@@ -29,49 +32,129 @@ use sequence::Sequence;
 const CHANNEL_TOKEN: Token = Token(::std::usize::MAX - 1);
 const BUS_TOKEN: Token     = Token(::std::usize::MAX - 2);
 const TIMER_TOKEN: Token   = Token(::std::usize::MAX - 3);
+const RELAY_TOKEN: Token   = Token(::std::usize::MAX - 4);
+
+/// Position of a dispatcher among the ones of a session, and the means to reach the others.
+pub struct Shard {
+    index: usize,
+    relay: EventLoopBus<Relay>,
+    peers: Vec<EventLoopBusSender<Relay>>
+}
+
+impl Shard {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+/// Creates the shards of a session running the specified number of dispatchers.
+pub fn create_shards(count: usize) -> Vec<Shard> {
+    let mut relays: Vec<EventLoopBus<Relay>> = (0..count).map(|_| EventLoopBus::new()).collect();
+    let peers: Vec<EventLoopBusSender<Relay>> = relays.iter_mut().map(|relay| relay.sender()).collect();
+
+    relays.into_iter().enumerate().map(|(index, relay)| Shard {
+        index: index,
+        relay: relay,
+        peers: peers.clone()
+    }).collect()
+}
+
+/// Gives each dispatcher its own view of the transports, 
+/// sharing them behind a lock when there are several dispatchers.
+pub fn share_transports(
+    transports: HashMap<String, Box<Transport + Send>, BuildIdHasher>, 
+    count: usize) -> Vec<HashMap<String, Box<Transport + Send>, BuildIdHasher>> {
+
+    if count == 1 {
+        return vec![transports];
+    }
+
+    let shared: Vec<(String, Arc<Mutex<Box<Transport + Send>>>)> = transports.
+        into_iter().
+        map(|(scheme, transport)| (scheme, Arc::new(Mutex::new(transport)))).
+        collect();
+
+    (0..count).map(|_| shared.iter().map(|&(ref scheme, ref transport)| {
+        let transport: Box<Transport + Send> = Box::new(SharedTransport(transport.clone()));
+
+        (scheme.clone(), transport)
+    }).collect()).collect()
+}
+
+struct SharedTransport(Arc<Mutex<Box<Transport + Send>>>);
+
+impl Transport for SharedTransport {
+    fn connect(&self, dest: &Destination) -> io::Result<Box<pipe::Pipe>> {
+        self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).connect(dest)
+    }
+    fn bind(&self, dest: &Destination) -> io::Result<Box<acceptor::Acceptor>> {
+        self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).bind(dest)
+    }
+}
+
+/// A dispatcher hosting a device or a probe linked to a socket owned by this dispatcher.
+struct RemoteLink {
+    shard: usize,
+    wake: bool
+}
 
 pub struct Dispatcher {
     // request inputs
-    channel: Receiver<Request>,
+    channel: Inbox<Request>,
     bus: EventLoopBus<Signal>,
-    timer: Timer<Task>,
+    clock: Clock,
+    relay: EventLoopBus<Relay>,
+    event_capacity: usize,
+
+    // other dispatchers of the session
+    shard: usize,
+    peers: Vec<EventLoopBusSender<Relay>>,
+    links: HashMap<SocketId, Vec<RemoteLink>, BuildIdHasher>,
+
+    // sockets whose receive batch was put on hold since the last poll, and before it
+    held_batches: Vec<SocketId>,
+    polled_batches: Vec<SocketId>,
 
     // request handlers
     sockets: session::Session,
-    endpoints: EndpointCollection,
-    schedule: Schedule
+    endpoints: EndpointCollection
 }
 
 impl Dispatcher {
     pub fn dispatch(
         transports: HashMap<String, Box<Transport + Send>, BuildIdHasher>,
-        rx: Receiver<Request>,
-        tx: Sender<session::Reply>) -> io::Result<()> {
+        rx: Inbox<Request>,
+        tx: Sender<session::Reply>,
+        config: Config,
+        shard: Shard) -> io::Result<()> {
 
-        let mut dispatcher = Dispatcher::new(transports, rx, tx);
+        let mut dispatcher = Dispatcher::new(transports, rx, tx, config, shard);
 
         dispatcher.run()
     }
     pub fn new(
         transports: HashMap<String, Box<Transport + Send>, BuildIdHasher>,
-        rx: Receiver<Request>, 
-        tx: Sender<session::Reply>) -> Dispatcher {
+        rx: Inbox<Request>, 
+        tx: Sender<session::Reply>,
+        config: Config,
+        shard: Shard) -> Dispatcher {
 
-        let id_seq = Sequence::new();
-        let timeout_eq = Sequence::new();
-        let clock = Builder::default().
-            tick_duration(Duration::from_millis(25)).
-            num_slots(1_024).
-            capacity(8_192).
-            build();
+        let id_seq = Sequence::with_step(shard.index, shard.peers.len());
+        let clock = Clock::new(&config);
 
         Dispatcher {
             channel: rx,
             bus: EventLoopBus::new(),
-            timer: clock,
+            clock: clock,
+            relay: shard.relay,
+            event_capacity: config.event_capacity,
+            shard: shard.index,
+            peers: shard.peers,
+            links: HashMap::default(),
+            held_batches: Vec::new(),
+            polled_batches: Vec::new(),
             sockets: session::Session::new(id_seq.clone(), tx),
-            endpoints: EndpointCollection::new(id_seq.clone(), transports),
-            schedule: Schedule::new(timeout_eq)
+            endpoints: EndpointCollection::new(id_seq.clone(), transports, config.buffer_pool.clone())
         }
 
     }
@@ -83,15 +166,22 @@ impl Dispatcher {
 /*****************************************************************************/
 
     pub fn run(&mut self) -> io::Result<()> {
-        let mut event_loop = try!(EventLoop::new());
+        let mut event_loop = try!(self.create_event_loop());
+
+        event_loop.run(self)
+    }
+
+    fn create_event_loop(&self) -> io::Result<EventLoop> {
+        let mut event_loop = try!(EventLoop::new(self.event_capacity));
         let interest = Ready::readable();
         let opt = PollOpt::edge();
 
         try!(event_loop.register(&self.channel, CHANNEL_TOKEN, interest, opt));
         try!(event_loop.register(&self.bus, BUS_TOKEN, interest, opt));
-        try!(event_loop.register(&self.timer, TIMER_TOKEN, interest, opt));
+        try!(event_loop.register(&self.clock, TIMER_TOKEN, interest, opt));
+        try!(event_loop.register(&self.relay, RELAY_TOKEN, interest, opt));
 
-        event_loop.run(self)
+        Ok(event_loop)
     }
 
 /*****************************************************************************/
@@ -101,7 +191,7 @@ impl Dispatcher {
 /*****************************************************************************/
 
     fn process_channel(&mut self, el: &mut EventLoop) {
-        while let Ok(req) = self.channel.try_recv() {
+        while let Some(req) = self.channel.recv() {
             self.process_request(el, req);
         }
     }
@@ -111,10 +201,15 @@ impl Dispatcher {
         }
     }
     fn process_timer(&mut self, el: &mut EventLoop) {
-        while let Some(timeout) = self.timer.poll() {
+        while let Some(timeout) = self.clock.poll() {
             self.process_tick(el, timeout);
         }
     }
+    fn process_relay(&mut self, el: &mut EventLoop) {
+        while let Some(relay) = self.relay.recv() {
+            self.process_relay_signal(el, relay);
+        }
+    }
 
 /*****************************************************************************/
 /*                                                                           */
@@ -154,11 +249,20 @@ impl Dispatcher {
     }
 
     fn process_socket_task(&mut self, sid: SocketId, task: context::Schedulable) {
+        self.apply_on_socket_task(sid, task);
+        self.run_forwarder_link(sid);
+    }
+
+    fn apply_on_socket_task(&mut self, sid: SocketId, task: context::Schedulable) {
         match task {
             context::Schedulable::Reconnect(eid, spec) => self.apply_on_socket(sid, |socket, ctx| socket.reconnect(ctx, eid, spec)),
             context::Schedulable::Rebind(eid, spec)    => self.apply_on_socket(sid, |socket, ctx| socket.rebind(ctx, eid, spec)),
             context::Schedulable::SendTimeout          => self.apply_on_socket(sid, |socket, ctx| socket.on_send_timeout(ctx)),
             context::Schedulable::RecvTimeout          => self.apply_on_socket(sid, |socket, ctx| socket.on_recv_timeout(ctx)),
+            context::Schedulable::LivenessCheck        => self.apply_on_socket(sid, |socket, ctx| socket.on_liveness_check(ctx)),
+            context::Schedulable::ConnectTimeout(eid)  => self.apply_on_socket(sid, |socket, ctx| socket.on_connect_timeout(ctx, eid)),
+            context::Schedulable::HandshakeTimeout(eid) => self.apply_on_socket(sid, |socket, ctx| socket.on_handshake_timeout(ctx, eid)),
+            context::Schedulable::ResumeAccept(eid)    => self.apply_on_socket(sid, |socket, ctx| socket.on_accept_backoff_elapsed(ctx, eid)),
             other                                      => self.apply_on_socket(sid, |socket, ctx| socket.on_timer_tick(ctx, other))
         }
     }
@@ -201,11 +305,27 @@ impl Dispatcher {
         match request {
             session::Request::CreateSocket(ctor) => self.sockets.add_socket(ctor),
             session::Request::CreateDevice(l, r) => {
-                self.apply_on_socket(l, |socket, ctx| socket.on_device_plugged(ctx));
-                self.apply_on_socket(r, |socket, ctx| socket.on_device_plugged(ctx));
+                self.link_socket(l, false);
+                self.link_socket(r, false);
+                self.send_socket_cmd(l, RemoteCmd::Plug);
+                self.send_socket_cmd(r, RemoteCmd::Plug);
                 self.sockets.add_device(l, r);
             },
-            session::Request::CreateProbe(poll_opts) => self.sockets.add_probe(poll_opts),
+            session::Request::SpawnDevice(l, l_rx, r, r_rx) => {
+                self.link_socket(l, true);
+                self.link_socket(r, true);
+                self.send_socket_cmd(l, RemoteCmd::Plug);
+                self.send_socket_cmd(r, RemoteCmd::Plug);
+                self.sockets.add_forwarder(l, l_rx, r, r_rx);
+                self.send_socket_cmd(l, RemoteCmd::Poll);
+                self.send_socket_cmd(r, RemoteCmd::Poll);
+            },
+            session::Request::CreateProbe(poll_opts) => {
+                for poll_opt in &poll_opts {
+                    self.link_socket(poll_opt.sid, false);
+                }
+                self.sockets.add_probe(poll_opts);
+            },
             session::Request::Shutdown => el.shutdown()
         }
     }
@@ -215,9 +335,13 @@ impl Dispatcher {
             socket::Request::Bind(url)        => self.apply_on_socket(id, |socket, ctx| socket.bind(ctx, url)),
             socket::Request::Send(msg, false) => self.apply_on_socket(id, |socket, ctx| socket.send(ctx, msg)),
             socket::Request::Send(msg, true)  => self.apply_on_socket(id, |socket, ctx| socket.try_send(ctx, msg)),
+            socket::Request::SendBatch(msgs)  => self.apply_on_socket(id, |socket, ctx| socket.send_batch(ctx, msgs)),
             socket::Request::Recv(false)      => self.apply_on_socket(id, |socket, ctx| socket.recv(ctx)),
             socket::Request::Recv(true)       => self.apply_on_socket(id, |socket, ctx| socket.try_recv(ctx)),
+            socket::Request::RecvWithInfo     => self.apply_on_socket(id, |socket, ctx| socket.recv_with_info(ctx)),
+            socket::Request::RecvBatch(max, timeout) => self.apply_on_socket(id, |socket, ctx| socket.recv_batch(ctx, max, timeout)),
             socket::Request::SetOption(x)     => self.apply_on_socket(id, |socket, ctx| socket.set_option(ctx, x)),
+            socket::Request::GetOption(x)     => self.apply_on_socket(id, |socket, ctx| socket.get_option(ctx, x)),
             socket::Request::Close            => self.apply_on_socket(id, |socket, ctx| socket.close(ctx)),
         }
     }
@@ -231,14 +355,47 @@ impl Dispatcher {
         });
     }
     fn process_device_request(&mut self, _: &mut EventLoop, id: DeviceId, request: device::Request) {
-        if let device::Request::Check = request { 
-            self.apply_on_device(id, |device, ctx| device.check(ctx)) 
+        match request {
+            device::Request::Check       => self.apply_on_device(id, |device, ctx| device.check(ctx)),
+            device::Request::GetCounters => self.apply_on_forwarder(id, |forwarder| forwarder.report_counters()),
+            device::Request::Close       => self.close_device(id)
+        }
+    }
+    fn close_device(&mut self, id: DeviceId) {
+        let mut linked = Vec::new();
+
+        if let Some(forwarder) = self.sockets.get_forwarder_mut(id) {
+            linked.push(*forwarder.get_left_id());
+            linked.push(*forwarder.get_right_id());
+
+            if let Some(action) = forwarder.stop() {
+                self.process_forwarder_action(action);
+            }
+        }
+        if let Some(device) = self.sockets.get_device_mut(id) {
+            linked.push(*device.get_left_id());
+            linked.push(*device.get_right_id());
+        }
+        self.sockets.remove_forwarder(id);
+        self.sockets.remove_device(id);
+
+        for sid in linked {
+            self.unlink_socket(sid);
         }
     }
     fn process_probe_request(&mut self, _: &mut EventLoop, id: ProbeId, request: probe::Request) {
         match request {
             probe::Request::Poll(timeout) => self.apply_on_probe(id, |probe, ctx| probe.poll(ctx, timeout)) ,
-            probe::Request::Close => self.sockets.remove_probe(id)
+            probe::Request::Close => self.close_probe(id)
+        }
+    }
+    fn close_probe(&mut self, id: ProbeId) {
+        let linked = self.sockets.get_probe_mut(id).map(|probe| probe.get_socket_ids());
+
+        self.sockets.remove_probe(id);
+
+        for sid in linked.unwrap_or_default() {
+            self.unlink_socket(sid);
         }
     }
 
@@ -259,7 +416,7 @@ impl Dispatcher {
     }
     fn process_socket_cmd(&mut self, _: &mut EventLoop, id: SocketId, cmd: context::Command) {
         match cmd {
-            context::Command::Poll => self.apply_on_socket(id, |socket, ctx| socket.poll(ctx)),
+            context::Command::Poll => self.send_socket_cmd(id, RemoteCmd::Poll),
         }
     }
 
@@ -269,7 +426,12 @@ impl Dispatcher {
 /*                                                                           */
 /*****************************************************************************/
     fn process_pipe_evt(&mut self, _: &mut EventLoop, sid: SocketId, eid: EndpointId, evt: pipe::Event) {
+        self.apply_pipe_evt(sid, eid, evt);
+        self.run_forwarder_link(sid);
+    }
+    fn apply_pipe_evt(&mut self, sid: SocketId, eid: EndpointId, evt: pipe::Event) {
         match evt {
+            pipe::Event::Connected     => self.apply_on_socket(sid, |socket, ctx| socket.on_pipe_connected(ctx, eid)),
             pipe::Event::Opened        => self.apply_on_socket(sid, |socket, ctx| socket.on_pipe_opened(ctx, eid)),
             pipe::Event::CanSend(x)    => self.apply_on_socket(sid, |socket, ctx| socket.on_send_ready(ctx, eid, x)),
             pipe::Event::Sent          => self.apply_on_socket(sid, |socket, ctx| socket.on_send_ack(ctx, eid)),
@@ -281,6 +443,10 @@ impl Dispatcher {
     }
     fn process_acceptor_evt(&mut self, _: &mut EventLoop, sid: SocketId, aid: EndpointId, evt: acceptor::Event) {
         match evt {
+            // Running out of file descriptors does not make the listener unusable
+            acceptor::Event::Error(ref e) if acceptor::is_transient_error(e) => {
+                self.apply_on_socket(sid, |socket, ctx| socket.on_transient_acceptor_error(ctx, aid, e))
+            },
             // Maybe the controller should be removed from the endpoint collection
             acceptor::Event::Error(e) => self.apply_on_socket(sid, |socket, ctx| socket.on_acceptor_error(ctx, aid, e)),
             acceptor::Event::Accepted(pipes) => {
@@ -297,13 +463,122 @@ impl Dispatcher {
         match evt {
             context::Event::CanRecv(x) => {
                 self.apply_on_device_link(sid, |device| device.on_socket_can_recv(sid, x));
+                self.apply_on_forwarder_link(sid, |forwarder| forwarder.on_socket_can_recv(sid, x));
                 self.apply_on_probe_link(sid, |probe, ctx| probe.on_socket_can_recv(ctx, sid, x));
+                self.run_forwarder_link(sid);
+                self.relay_socket_evt(sid, evt);
             },
             context::Event::CanSend(x) => {
+                if x {
+                    self.apply_on_socket(sid, |socket, ctx| socket.resume_send_batch(ctx));
+                }
                 self.apply_on_probe_link(sid, |probe, ctx| probe.on_socket_can_send(ctx, sid, x));
+                self.relay_socket_evt(sid, evt);
+            },
+            context::Event::Closed => {
+                self.sockets.remove_socket(sid);
+                self.links.remove(&sid);
+            }
+        }
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* process relayed signals                                                   */
+/*                                                                           */
+/*****************************************************************************/
+    fn process_relay_signal(&mut self, el: &mut EventLoop, relay: Relay) {
+        match relay {
+            Relay::Link(sid, shard, wake)  => self.add_remote_link(sid, shard, wake),
+            Relay::Unlink(sid, shard)      => self.remove_remote_link(sid, shard),
+            Relay::SocketCmd(sid, cmd)     => {
+                self.apply_socket_cmd(sid, cmd);
+                self.run_forwarder_link(sid);
+            },
+            Relay::SocketEvt(sid, evt)     => self.process_socket_evt(el, sid, evt),
+            Relay::Wake(sid)               => self.run_forwarder_link(sid)
+        }
+    }
+
+    fn is_local(&self, sid: SocketId) -> bool {
+        shard_of(sid, self.peers.len()) == self.shard
+    }
+
+    fn send_relay(&self, sid: SocketId, relay: Relay) {
+        self.peers[shard_of(sid, self.peers.len())].send(relay);
+    }
+
+    // Called by the dispatcher hosting a device or a probe, 
+    // so that the events of a socket owned by another dispatcher are relayed.
+    fn link_socket(&mut self, sid: SocketId, wake: bool) {
+        if !self.is_local(sid) {
+            let relay = Relay::Link(sid, self.shard, wake);
+
+            self.send_relay(sid, relay);
+        }
+    }
+
+    fn unlink_socket(&mut self, sid: SocketId) {
+        if !self.is_local(sid) {
+            let relay = Relay::Unlink(sid, self.shard);
+
+            self.send_relay(sid, relay);
+        }
+    }
 
+    fn add_remote_link(&mut self, sid: SocketId, shard: usize, wake: bool) {
+        let link = RemoteLink { shard: shard, wake: wake };
+
+        self.links.entry(sid).or_insert_with(Vec::new).push(link);
+    }
+
+    fn remove_remote_link(&mut self, sid: SocketId, shard: usize) {
+        let is_empty = match self.links.get_mut(&sid) {
+            Some(links) => {
+                if let Some(pos) = links.iter().position(|link| link.shard == shard) {
+                    links.remove(pos);
+                }
+                links.is_empty()
             },
-            context::Event::Closed => self.sockets.remove_socket(sid)
+            None => false
+        };
+
+        if is_empty {
+            self.links.remove(&sid);
+        }
+    }
+
+    fn relay_socket_evt(&self, sid: SocketId, evt: context::Event) {
+        if let Some(links) = self.links.get(&sid) {
+            for link in links {
+                self.peers[link.shard].send(Relay::SocketEvt(sid, evt));
+            }
+        }
+    }
+
+    fn wake_remote_links(&self, sid: SocketId) {
+        if let Some(links) = self.links.get(&sid) {
+            for link in links.iter().filter(|link| link.wake) {
+                self.peers[link.shard].send(Relay::Wake(sid));
+            }
+        }
+    }
+
+    fn send_socket_cmd(&mut self, sid: SocketId, cmd: RemoteCmd) {
+        if self.is_local(sid) {
+            self.apply_socket_cmd(sid, cmd);
+        } else {
+            self.send_relay(sid, Relay::SocketCmd(sid, cmd));
+        }
+    }
+
+    fn apply_socket_cmd(&mut self, sid: SocketId, cmd: RemoteCmd) {
+        match cmd {
+            RemoteCmd::Poll      => self.apply_on_socket(sid, |socket, ctx| socket.poll(ctx)),
+            RemoteCmd::Plug      => self.apply_on_socket(sid, |socket, ctx| socket.on_device_plugged(ctx)),
+            RemoteCmd::Recv      => self.apply_on_socket(sid, |socket, ctx| socket.recv(ctx)),
+            RemoteCmd::Send(msg) => self.apply_on_socket(sid, |socket, ctx| socket.send(ctx, msg)),
+            RemoteCmd::Close     => self.apply_on_socket(sid, |socket, ctx| socket.close(ctx))
         }
     }
 
@@ -314,13 +589,35 @@ impl Dispatcher {
                 id,
                 &mut self.bus,
                 &mut self.endpoints,
-                &mut self.schedule,
-                &mut self.timer);
+                &mut self.clock);
+
+            let was_on_hold = socket.has_recv_batch_on_hold();
 
             f(socket, &mut ctx);
+            socket.forward_replies(&mut ctx);
+
+            if !was_on_hold && socket.has_recv_batch_on_hold() {
+                self.held_batches.push(id);
+            }
         }
     }
 
+    // A batch put on hold gets one more poll, that does not wait, to take the messages
+    // already readable. It is flushed after that poll, unless it brought it another message.
+    fn flush_recv_batches(&mut self) -> bool {
+        let polled = mem::replace(&mut self.polled_batches, Vec::new());
+
+        for sid in polled {
+            if !self.held_batches.contains(&sid) {
+                self.apply_on_socket(sid, |socket, ctx| socket.flush_recv_batch(ctx));
+            }
+        }
+
+        mem::swap(&mut self.polled_batches, &mut self.held_batches);
+
+        !self.polled_batches.is_empty()
+    }
+
     fn apply_on_device<F>(&mut self, id: DeviceId, f: F) 
     where F : FnOnce(&mut device::Device, &mut DeviceEventLoopContext) {
         if let Some(device) = self.sockets.get_device_mut(id) {
@@ -336,14 +633,61 @@ impl Dispatcher {
         }
     }
 
+    fn apply_on_forwarder<F>(&mut self, id: DeviceId, f: F) 
+    where F : FnOnce(&mut device::Forwarder) {
+        if let Some(forwarder) = self.sockets.get_forwarder_mut(id) {
+            f(forwarder);
+        }
+    }
+
+    fn apply_on_forwarder_link<F>(&mut self, id: SocketId, f: F) 
+    where F : FnOnce(&mut device::Forwarder) {
+        if let Some((_, forwarder)) = self.sockets.find_forwarder_mut(id) {
+            f(forwarder);
+        }
+    }
+
+    // Moves messages between the sockets linked by a forwarder, if any,
+    // until both sides have to wait for some i/o to happen.
+    // When the forwarder is hosted by another dispatcher, it is woken up instead.
+    fn run_forwarder_link(&mut self, id: SocketId) {
+        if self.sockets.find_forwarder_mut(id).is_none() {
+            self.wake_remote_links(id);
+            return;
+        }
+
+        loop {
+            let action = match self.sockets.find_forwarder_mut(id) {
+                Some((_, forwarder)) => forwarder.next_action(),
+                None => None
+            };
+
+            match action {
+                Some(action) => self.process_forwarder_action(action),
+                None => break
+            }
+        }
+    }
+
+    fn process_forwarder_action(&mut self, action: device::Action) {
+        match action {
+            device::Action::Poll(sid)      => self.send_socket_cmd(sid, RemoteCmd::Poll),
+            device::Action::Recv(sid)      => self.send_socket_cmd(sid, RemoteCmd::Recv),
+            device::Action::Send(sid, msg) => self.send_socket_cmd(sid, RemoteCmd::Send(msg)),
+            device::Action::Close(l, r)    => {
+                self.send_socket_cmd(l, RemoteCmd::Close);
+                self.send_socket_cmd(r, RemoteCmd::Close);
+            }
+        }
+    }
+
     fn apply_on_probe<F>(&mut self, id: ProbeId, f: F) 
     where F : FnOnce(&mut probe::Probe, &mut ProbeEventLoopContext) {
         if let Some(probe) = self.sockets.get_probe_mut(id) {
             let mut ctx = ProbeEventLoopContext::new(
                 id,
                 &mut self.bus,
-                &mut self.schedule,
-                &mut self.timer);
+                &mut self.clock);
             f(probe, &mut ctx);
         }
     }
@@ -354,20 +698,76 @@ impl Dispatcher {
             let mut ctx = ProbeEventLoopContext::new(
                 *pid,
                 &mut self.bus,
-                &mut self.schedule,
-                &mut self.timer);
+                &mut self.clock);
             f(probe, &mut ctx);
         }
     }
 }
 
+/// A dispatcher whose event loop is run by the caller instead of a dedicated thread.
+/// The dispatcher is dropped on shutdown, just like when the I/O thread exits.
+pub struct ManualDispatcher {
+    dispatcher: Option<Dispatcher>,
+    event_loop: EventLoop
+}
+
+impl ManualDispatcher {
+    pub fn new(
+        transports: HashMap<String, Box<Transport + Send>, BuildIdHasher>,
+        rx: Inbox<Request>,
+        tx: Sender<session::Reply>,
+        config: Config,
+        shard: Shard) -> io::Result<ManualDispatcher> {
+
+        let dispatcher = Dispatcher::new(transports, rx, tx, config, shard);
+        let event_loop = try!(dispatcher.create_event_loop());
+
+        Ok(ManualDispatcher {
+            dispatcher: Some(dispatcher),
+            event_loop: event_loop
+        })
+    }
+
+    pub fn run_once(&mut self, timeout: Option<Duration>) -> io::Result<usize> {
+        let res = match self.dispatcher {
+            Some(ref mut dispatcher) => self.event_loop.run_once(dispatcher, timeout),
+            None => Ok(0)
+        };
+
+        if !self.event_loop.is_running() {
+            self.dispatcher = None;
+        }
+
+        res
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.dispatcher.is_some()
+    }
+}
+
+#[cfg(unix)]
+impl AsRawFd for ManualDispatcher {
+    fn as_raw_fd(&self) -> RawFd {
+        self.event_loop.as_raw_fd()
+    }
+}
+
 impl EventHandler for Dispatcher {
     fn handle(&mut self, el: &mut EventLoop, token: Token, events: Ready) {
         match token {
             CHANNEL_TOKEN => self.process_channel(el),
             BUS_TOKEN     => self.process_bus(el),
             TIMER_TOKEN   => self.process_timer(el),
+            RELAY_TOKEN   => self.process_relay(el),
             _             => self.process_io(el, token, events)
         }
     }
+
+    // The signals raised by the pipes that were just polled are processed right away,
+    // so that a receive batch on hold can take the messages they announce.
+    fn handled(&mut self, el: &mut EventLoop) -> bool {
+        self.process_bus(el);
+        self.flush_recv_batches()
+    }
 }
@@ -5,27 +5,34 @@
 // This file may not be copied, modified, or distributed except according to those terms.
 
 use std::io;
+use std::time::Duration;
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
 
 use mio::{Poll, Token, Ready, Events, Evented, PollOpt};
 
 pub trait EventHandler {
     fn handle(&mut self, el: &mut EventLoop, token: Token, events: Ready);
+    /// Called once the events of a poll are handled, returns whether the next poll must not wait.
+    fn handled(&mut self, _: &mut EventLoop) -> bool { false }
 }
 
 pub struct EventLoop {
     events_poller: Poll,
     events: Events,
-    running: bool
+    running: bool,
+    busy: bool
 }
 
 impl EventLoop {
-    pub fn new() -> io::Result<EventLoop> {
-        let evts = Events::with_capacity(1024);
+    pub fn new(event_capacity: usize) -> io::Result<EventLoop> {
+        let evts = Events::with_capacity(event_capacity);
         let poll = try!(Poll::new());
         let event_loop = EventLoop {
             events_poller: poll,
             events: evts,
-            running: false
+            running: true,
+            busy: false
         };
 
         Ok(event_loop)
@@ -35,18 +42,24 @@ impl EventLoop {
         self.running = false;
     }
 
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
     pub fn run<H: EventHandler>(&mut self, event_handler: &mut H) -> io::Result<()> {
         self.running = true;
 
         while self.running {
-            try!(self.run_once(event_handler));
+            try!(self.run_once(event_handler, None));
         }
 
         Ok(())
     }
 
-    pub fn run_once<H: EventHandler>(&mut self, event_handler: &mut H) -> io::Result<()> {
-        let event_count = match self.poll_events() {
+    /// Returns the number of events processed.
+    pub fn run_once<H: EventHandler>(&mut self, event_handler: &mut H, timeout: Option<Duration>) -> io::Result<usize> {
+        let timeout = if self.busy { Some(Duration::from_millis(0)) } else { timeout };
+        let event_count = match self.poll_events(timeout) {
             Ok(count) => count,
             Err(err) => {
                 if err.kind() == io::ErrorKind::Interrupted {
@@ -58,12 +71,13 @@ impl EventLoop {
         };
 
         self.process_events(event_handler, event_count);
+        self.busy = event_handler.handled(self);
 
-        Ok(())
+        Ok(event_count)
     }
 
-    fn poll_events(&mut self) -> io::Result<usize> {
-        self.events_poller.poll(&mut self.events, None)
+    fn poll_events(&mut self, timeout: Option<Duration>) -> io::Result<usize> {
+        self.events_poller.poll(&mut self.events, timeout)
     }
 
     fn process_events<H: EventHandler>(&mut self, event_handler: &mut H, count: usize) {
@@ -88,3 +102,10 @@ impl EventLoop {
         self.events_poller.deregister(io)
     }
 }
+
+#[cfg(unix)]
+impl AsRawFd for EventLoop {
+    fn as_raw_fd(&self) -> RawFd {
+        self.events_poller.as_raw_fd()
+    }
+}
@@ -0,0 +1,403 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+//! Carries the requests of the facade to a dispatcher.
+//! Each socket, device and probe sends its requests to its own mailbox, a lock-free stack.
+//! The first request posted to an empty mailbox schedules it on the inbox of the dispatcher,
+//! and the first mailbox scheduled while the dispatcher is busy or asleep wakes it up.
+//! On linux, the wake up is a write to an eventfd polled by the event loop,
+//! so that posting a request costs a single syscall at most.
+
+use std::collections::VecDeque;
+use std::io::Result;
+use std::ptr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+
+use mio::{Evented, Poll, Token, Ready, PollOpt};
+
+use io_error::*;
+
+/// Sends items to the inbox of a dispatcher. Clones share the same queue.
+pub struct Mailbox<T> {
+    queue: Arc<Queue<T>>,
+    inbox: Arc<Shared<T>>
+}
+
+/// Receives the items of all the mailboxes created for a dispatcher,
+/// in the order they were posted for each mailbox.
+pub struct Inbox<T> {
+    shared: Arc<Shared<T>>,
+    listener: waker::Listener,
+    items: VecDeque<T>
+}
+
+struct Queue<T> {
+    items: Stack<T>,
+    scheduled: AtomicBool
+}
+
+struct Shared<T> {
+    scheduled: Stack<Arc<Queue<T>>>,
+    notified: AtomicBool,
+    closed: AtomicBool,
+    waker: waker::Waker
+}
+
+/// Creates a mailbox and the inbox receiving its items.
+/// Other mailboxes for the same inbox are created with `Mailbox::sibling`.
+pub fn channel<T>() -> Result<(Mailbox<T>, Inbox<T>)> {
+    let (waker, listener) = try!(waker::pair());
+    let shared = Arc::new(Shared {
+        scheduled: Stack::new(),
+        notified: AtomicBool::new(false),
+        closed: AtomicBool::new(false),
+        waker: waker
+    });
+    let inbox = Inbox {
+        shared: shared.clone(),
+        listener: listener,
+        items: VecDeque::new()
+    };
+
+    Ok((Mailbox::new(shared), inbox))
+}
+
+impl<T> Mailbox<T> {
+    fn new(inbox: Arc<Shared<T>>) -> Mailbox<T> {
+        let queue = Queue {
+            items: Stack::new(),
+            scheduled: AtomicBool::new(false)
+        };
+
+        Mailbox {
+            queue: Arc::new(queue),
+            inbox: inbox
+        }
+    }
+
+    /// Creates another mailbox, with its own queue, sending to the same inbox.
+    pub fn sibling(&self) -> Mailbox<T> {
+        Mailbox::new(self.inbox.clone())
+    }
+
+    pub fn send(&self, t: T) -> Result<()> {
+        if self.inbox.closed.load(Ordering::SeqCst) {
+            return Err(other_io_error("channel closed"));
+        }
+
+        self.queue.items.push(t);
+
+        if self.queue.scheduled.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        self.inbox.scheduled.push(self.queue.clone());
+
+        // the inbox may have been dropped before seeing the queue, which would then never be released
+        if self.inbox.closed.load(Ordering::SeqCst) {
+            self.inbox.scheduled.take_all();
+            return Err(other_io_error("channel closed"));
+        }
+
+        if !self.inbox.notified.swap(true, Ordering::SeqCst) {
+            self.inbox.waker.wake();
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> Clone for Mailbox<T> {
+    fn clone(&self) -> Mailbox<T> {
+        Mailbox {
+            queue: self.queue.clone(),
+            inbox: self.inbox.clone()
+        }
+    }
+}
+
+impl<T> Inbox<T> {
+    pub fn recv(&mut self) -> Option<T> {
+        if self.items.is_empty() {
+            self.fetch();
+        }
+
+        self.items.pop_front()
+    }
+
+    fn fetch(&mut self) {
+        // the notification is acknowledged before looking at the queues,
+        // a mailbox scheduled after that will wake the dispatcher up again
+        if self.shared.notified.swap(false, Ordering::SeqCst) {
+            self.listener.reset();
+        }
+
+        for queue in self.shared.scheduled.take_all() {
+            queue.scheduled.store(false, Ordering::SeqCst);
+            self.items.extend(queue.items.take_all());
+        }
+    }
+}
+
+impl<T> Drop for Inbox<T> {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::SeqCst);
+        self.shared.scheduled.take_all();
+    }
+}
+
+impl<T> Evented for Inbox<T> {
+    fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> Result<()> {
+        self.listener.register(poll, token, interest, opts)
+    }
+
+    fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> Result<()> {
+        self.listener.reregister(poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &Poll) -> Result<()> {
+        self.listener.deregister(poll)
+    }
+}
+
+/// Treiber stack, items are pushed one at a time by any thread,
+/// and taken all at once, so that a node is never read by a thread once another one has popped it.
+struct Stack<T> {
+    head: AtomicPtr<Node<T>>
+}
+
+struct Node<T> {
+    item: T,
+    next: *mut Node<T>
+}
+
+unsafe impl<T: Send> Send for Stack<T> {}
+unsafe impl<T: Send> Sync for Stack<T> {}
+
+impl<T> Stack<T> {
+    fn new() -> Stack<T> {
+        Stack {
+            head: AtomicPtr::new(ptr::null_mut())
+        }
+    }
+
+    fn push(&self, item: T) {
+        let node = Box::into_raw(Box::new(Node {
+            item: item,
+            next: ptr::null_mut()
+        }));
+        let mut head = self.head.load(Ordering::Relaxed);
+
+        loop {
+            unsafe { (*node).next = head; }
+
+            match self.head.compare_exchange_weak(head, node, Ordering::SeqCst, Ordering::Relaxed) {
+                Ok(_) => return,
+                Err(actual) => head = actual
+            }
+        }
+    }
+
+    /// Returns the items in the order they were pushed.
+    fn take_all(&self) -> Vec<T> {
+        let mut node = self.head.swap(ptr::null_mut(), Ordering::SeqCst);
+        let mut items = Vec::new();
+
+        while !node.is_null() {
+            let boxed = unsafe { Box::from_raw(node) };
+
+            node = boxed.next;
+            items.push(boxed.item);
+        }
+
+        items.reverse();
+        items
+    }
+}
+
+impl<T> Drop for Stack<T> {
+    fn drop(&mut self) {
+        self.take_all();
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod waker {
+    use std::io::{Error, Result};
+    use std::mem;
+    use std::sync::Arc;
+    use std::os::unix::io::RawFd;
+
+    use libc;
+    use mio::{Evented, Poll, Token, Ready, PollOpt};
+    use mio::unix::EventedFd;
+
+    struct EventFd(RawFd);
+
+    pub struct Waker(Arc<EventFd>);
+
+    pub struct Listener(Arc<EventFd>);
+
+    pub fn pair() -> Result<(Waker, Listener)> {
+        let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        let event_fd = Arc::new(EventFd(fd));
+
+        Ok((Waker(event_fd.clone()), Listener(event_fd)))
+    }
+
+    impl Waker {
+        // the counter can't overflow since the listener resets it before the next wake up
+        pub fn wake(&self) {
+            let value = 1u64;
+
+            unsafe {
+                libc::write(
+                    (self.0).0,
+                    &value as *const u64 as *const libc::c_void,
+                    mem::size_of::<u64>());
+            }
+        }
+    }
+
+    impl Listener {
+        pub fn reset(&self) {
+            let mut value = 0u64;
+
+            unsafe {
+                libc::read(
+                    (self.0).0,
+                    &mut value as *mut u64 as *mut libc::c_void,
+                    mem::size_of::<u64>());
+            }
+        }
+    }
+
+    impl Drop for EventFd {
+        fn drop(&mut self) {
+            unsafe { libc::close(self.0); }
+        }
+    }
+
+    impl Evented for Listener {
+        fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> Result<()> {
+            EventedFd(&(self.0).0).register(poll, token, interest, opts)
+        }
+
+        fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> Result<()> {
+            EventedFd(&(self.0).0).reregister(poll, token, interest, opts)
+        }
+
+        fn deregister(&self, poll: &Poll) -> Result<()> {
+            EventedFd(&(self.0).0).deregister(poll)
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod waker {
+    use std::io::Result;
+
+    use mio::{Registration, SetReadiness, Evented, Poll, Token, Ready, PollOpt};
+
+    pub struct Waker(SetReadiness);
+
+    pub struct Listener(Registration, SetReadiness);
+
+    pub fn pair() -> Result<(Waker, Listener)> {
+        let (registration, readiness) = Registration::new2();
+
+        Ok((Waker(readiness.clone()), Listener(registration, readiness)))
+    }
+
+    impl Waker {
+        pub fn wake(&self) {
+            let _ = self.0.set_readiness(Ready::readable());
+        }
+    }
+
+    impl Listener {
+        pub fn reset(&self) {
+            let _ = self.1.set_readiness(Ready::empty());
+        }
+    }
+
+    impl Evented for Listener {
+        fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> Result<()> {
+            Evented::register(&self.0, poll, token, interest, opts)
+        }
+
+        fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> Result<()> {
+            Evented::reregister(&self.0, poll, token, interest, opts)
+        }
+
+        fn deregister(&self, poll: &Poll) -> Result<()> {
+            Evented::deregister(&self.0, poll)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn items_of_a_mailbox_are_received_in_order() {
+        let (mailbox, mut inbox) = channel().unwrap();
+        let sibling = mailbox.sibling();
+
+        mailbox.send(1).unwrap();
+        sibling.send(10).unwrap();
+        mailbox.send(2).unwrap();
+
+        assert_eq!(Some(1), inbox.recv());
+        assert_eq!(Some(2), inbox.recv());
+        assert_eq!(Some(10), inbox.recv());
+        assert_eq!(None, inbox.recv());
+
+        sibling.send(11).unwrap();
+        assert_eq!(Some(11), inbox.recv());
+        assert_eq!(None, inbox.recv());
+    }
+
+    #[test]
+    fn items_sent_from_several_threads_are_all_received() {
+        let (mailbox, mut inbox) = channel().unwrap();
+        let senders: Vec<_> = (0..4).map(|i| {
+            let mailbox = if i % 2 == 0 { mailbox.sibling() } else { mailbox.clone() };
+
+            thread::spawn(move || for x in 0..1000 { mailbox.send(x).unwrap(); })
+        }).collect();
+
+        for sender in senders {
+            sender.join().unwrap();
+        }
+
+        let mut count = 0;
+        while inbox.recv().is_some() {
+            count += 1;
+        }
+
+        assert_eq!(4000, count);
+    }
+
+    #[test]
+    fn send_fails_once_the_inbox_is_dropped() {
+        let (mailbox, inbox) = channel().unwrap();
+
+        drop(inbox);
+
+        assert!(mailbox.send(1).is_err());
+        assert!(mailbox.sibling().send(2).is_err());
+    }
+}
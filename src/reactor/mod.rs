@@ -5,11 +5,16 @@
 // This file may not be copied, modified, or distributed except according to those terms.
 
 pub mod dispatcher;
+pub mod clock;
+pub mod mailbox;
 mod event_loop;
 mod bus;
 mod adapter;
 
-use core::{SocketId, EndpointId, DeviceId, ProbeId, context, session, socket, endpoint, device, probe};
+use std::time::Duration;
+
+use core::{SocketId, EndpointId, DeviceId, ProbeId, Message, context, session, socket, endpoint, device, probe};
+use core::pool::BufferPool;
 use transport::{pipe, acceptor};
 
 /// Commands and events flowing between the controller and transport or core components.
@@ -32,7 +37,63 @@ pub enum Request {
     Probe(ProbeId, probe::Request)
 }
 
+/// Signals flowing between the dispatchers of a session running several I/O threads.
+/// A socket is owned by a single dispatcher, but a device or a probe 
+/// can be linked to sockets owned by other dispatchers.
+pub enum Relay {
+    /// Asks the owner of a socket to forward its events to the given dispatcher, 
+    /// and to wake it up when the socket replies if the flag is set.
+    Link(SocketId, usize, bool),
+    Unlink(SocketId, usize),
+    /// Asks the owner of a socket to apply a command on it
+    SocketCmd(SocketId, RemoteCmd),
+    /// Forwards an event of a linked socket
+    SocketEvt(SocketId, context::Event),
+    /// Notifies that a linked socket may have replied
+    Wake(SocketId)
+}
+
+/// Commands a device or a probe can apply on a socket owned by another dispatcher.
+pub enum RemoteCmd {
+    Poll,
+    Plug,
+    Recv,
+    Send(Message),
+    Close
+}
+
+/// Returns the index of the dispatcher owning the socket, device or probe with the given id.
+/// Each dispatcher allocates its ids with a step equal to the number of dispatchers.
+pub fn shard_of<T : Into<usize>>(id: T, shards: usize) -> usize {
+    id.into() % shards
+}
+
 pub enum Task {
     Socket(SocketId, context::Schedulable),
     Probe(ProbeId, probe::Schedulable)
 }
+
+/// Tuning of the event loop and timer wheel driven by the I/O thread,
+/// and the virtual clock replacing the timer wheel in tests.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub timer_tick: Duration,
+    pub timer_slots: usize,
+    pub timer_capacity: usize,
+    pub event_capacity: usize,
+    pub clock: Option<clock::VirtualClock>,
+    pub buffer_pool: BufferPool
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            timer_tick: Duration::from_millis(25),
+            timer_slots: 1_024,
+            timer_capacity: 8_192,
+            event_capacity: 1_024,
+            clock: None,
+            buffer_pool: BufferPool::default()
+        }
+    }
+}
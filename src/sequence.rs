@@ -9,18 +9,25 @@ use std::rc::Rc;
 
 #[derive(Clone)]
 pub struct Sequence {
-    value: Rc<Cell<usize>>
+    value: Rc<Cell<usize>>,
+    step: usize
 }
 
 impl Sequence {
     pub fn new() -> Sequence {
-        Sequence { value: Rc::new(Cell::new(0)) }
+        Sequence::with_step(0, 1)
+    }
+
+    /// Creates a sequence yielding `first`, `first + step`, `first + 2 * step` ...
+    /// so that several sequences can share the same id space without overlapping.
+    pub fn with_step(first: usize, step: usize) -> Sequence {
+        Sequence { value: Rc::new(Cell::new(first)), step: step }
     }
 
     pub fn next(&self) -> usize {
         let id = self.value.get();
 
-        self.value.set(id + 1);
+        self.value.set(id + self.step);
         id
     }
 }
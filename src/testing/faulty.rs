@@ -0,0 +1,597 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+//! Fault injection for chaos testing.
+//!
+//! [`Faulty`](struct.Faulty.html) wraps any transport and makes its connections misbehave:
+//! connections can be refused, stalled, handshakes corrupted, and each sent message can be delayed,
+//! dropped, truncated or can cause a disconnection. Accepting can also fail as if the process
+//! had run out of file descriptors.
+//! All the decisions come from a random generator initialized with a seed,
+//! so a failing scenario can be replayed by reusing the seed.
+//! What the transport went through can be checked with its [`FaultyStats`](struct.FaultyStats.html).
+//!
+//! ```no_run
+//! use scaproust::*;
+//! use scaproust::testing::faulty::Faulty;
+//!
+//! let session = SessionBuilder::new().
+//!     with("tcp", Tcp).
+//!     with("faulty", Faulty::new(Tcp, 42).with_drop(0.1).with_truncation(0.05)).
+//!     build().unwrap();
+//! // sockets can now connect to or bind "faulty://127.0.0.1:5454"
+//! ```
+
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use mio::{Evented, Ready, PollOpt, Registration, SetReadiness};
+
+use core::{Message, PeerCred};
+use transport::{Transport, Destination};
+use transport::endpoint::EndpointRegistrar;
+use transport::pipe;
+use transport::pipe::Pipe;
+use transport::acceptor;
+use transport::acceptor::Acceptor;
+
+/// Transport wrapper injecting failures into the connections of the wrapped transport.
+///
+/// Probabilities are between 0 and 1, and are all 0 by default.
+/// Addresses are given unchanged to the wrapped transport.
+pub struct Faulty<T : Transport> {
+    inner: T,
+    seed: Cell<u64>,
+    faults: Faults,
+    stats: FaultyStats
+}
+
+#[derive(Clone, Debug, Default)]
+struct Faults {
+    connect_failure: f64,
+    connect_stall: f64,
+    handshake_stall: f64,
+    handshake_corruption: f64,
+    accept_failures: usize,
+    delay: Option<Duration>,
+    drop: f64,
+    truncation: f64,
+    disconnect: f64
+}
+
+impl<T : Transport> Faulty<T> {
+    /// Wraps the transport, the seed determines the sequence of injected failures.
+    pub fn new(inner: T, seed: u64) -> Faulty<T> {
+        Faulty {
+            inner: inner,
+            seed: Cell::new(seed),
+            faults: Faults::default(),
+            stats: FaultyStats::default()
+        }
+    }
+
+    /// Returns the counters of the transport, they are shared with the returned value
+    /// so they can still be read once the transport is given to a session.
+    pub fn stats(&self) -> FaultyStats {
+        self.stats.clone()
+    }
+
+    /// Sets the probability that a connection attempt is refused.
+    pub fn with_connect_failure(mut self, probability: f64) -> Faulty<T> {
+        self.faults.connect_failure = probability;
+        self
+    }
+
+    /// Sets the probability that a connection attempt never completes, without failing either.
+    pub fn with_connect_stall(mut self, probability: f64) -> Faulty<T> {
+        self.faults.connect_stall = probability;
+        self
+    }
+
+    /// Sets the probability that a connection is established but never completes its handshake.
+    pub fn with_handshake_stall(mut self, probability: f64) -> Faulty<T> {
+        self.faults.handshake_stall = probability;
+        self
+    }
+
+    /// Sets the probability that a connection fails its handshake, whatever the peer sends.
+    pub fn with_handshake_corruption(mut self, probability: f64) -> Faulty<T> {
+        self.faults.handshake_corruption = probability;
+        self
+    }
+
+    /// Delays each sent message by the specified duration before it is written.
+    pub fn with_delay(mut self, delay: Duration) -> Faulty<T> {
+        self.faults.delay = Some(delay);
+        self
+    }
+
+    /// Sets the probability that a sent message is reported as sent but never written.
+    pub fn with_drop(mut self, probability: f64) -> Faulty<T> {
+        self.faults.drop = probability;
+        self
+    }
+
+    /// Sets the probability that the body of a sent message is cut short.
+    pub fn with_truncation(mut self, probability: f64) -> Faulty<T> {
+        self.faults.truncation = probability;
+        self
+    }
+
+    /// Sets the probability that sending a message breaks the connection instead.
+    pub fn with_disconnect(mut self, probability: f64) -> Faulty<T> {
+        self.faults.disconnect = probability;
+        self
+    }
+
+    /// Makes the first accepts of each listener fail as if the process had run out of file descriptors.
+    pub fn with_accept_failures(mut self, count: usize) -> Faulty<T> {
+        self.faults.accept_failures = count;
+        self
+    }
+
+    fn next_rng(&self) -> Rng {
+        let mut rng = Rng(self.seed.get());
+        let seed = rng.next_u64();
+
+        self.seed.set(rng.0);
+        Rng(seed)
+    }
+}
+
+impl<T : Transport> Transport for Faulty<T> {
+    fn connect(&self, dest: &Destination) -> io::Result<Box<Pipe>> {
+        let mut rng = self.next_rng();
+
+        self.stats.connects.fetch_add(1, Ordering::SeqCst);
+
+        if rng.chance(self.faults.connect_failure) {
+            return Err(io::Error::new(io::ErrorKind::ConnectionRefused, "injected connect failure"));
+        }
+
+        let pipe = try!(self.inner.connect(dest));
+
+        Ok(Box::new(FaultyPipe::new(pipe, self.faults.clone(), rng)))
+    }
+
+    fn bind(&self, dest: &Destination) -> io::Result<Box<Acceptor>> {
+        self.stats.binds.fetch_add(1, Ordering::SeqCst);
+
+        let acceptor = try!(self.inner.bind(dest));
+
+        Ok(Box::new(FaultyAcceptor {
+            inner: acceptor,
+            faults: self.faults.clone(),
+            rng: self.next_rng(),
+            accept_failures: self.faults.accept_failures,
+            stats: self.stats.clone()
+        }))
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* FaultyStats                                                               */
+/*                                                                           */
+/*****************************************************************************/
+
+/// Counts what a [`Faulty`](struct.Faulty.html) transport went through.
+#[derive(Clone, Debug, Default)]
+pub struct FaultyStats {
+    connects: Arc<AtomicUsize>,
+    binds: Arc<AtomicUsize>,
+    accept_failures: Arc<AtomicUsize>
+}
+
+impl FaultyStats {
+    /// Number of connection attempts, including the refused ones.
+    pub fn connects(&self) -> usize {
+        self.connects.load(Ordering::SeqCst)
+    }
+
+    /// Number of times an address was bound.
+    pub fn binds(&self) -> usize {
+        self.binds.load(Ordering::SeqCst)
+    }
+
+    /// Number of accepts that were made to fail.
+    pub fn accept_failures(&self) -> usize {
+        self.accept_failures.load(Ordering::SeqCst)
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Rng                                                                       */
+/*                                                                           */
+/*****************************************************************************/
+
+// splitmix64, good enough to pick failures and accepts any seed
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        if probability <= 0f64 {
+            return false;
+        }
+
+        let sample = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+
+        sample < probability
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* FaultyAcceptor                                                            */
+/*                                                                           */
+/*****************************************************************************/
+
+struct FaultyAcceptor {
+    inner: Box<Acceptor>,
+    faults: Faults,
+    rng: Rng,
+    accept_failures: usize,
+    stats: FaultyStats
+}
+
+impl FaultyAcceptor {
+    fn forward<F>(&mut self, ctx: &mut acceptor::Context, f: F) where F : FnOnce(&mut Acceptor, &mut acceptor::Context) {
+        let events = {
+            let mut interceptor = AcceptorInterceptor { inner: ctx, events: Vec::new() };
+
+            f(&mut *self.inner, &mut interceptor);
+            interceptor.events
+        };
+
+        for evt in events {
+            match evt {
+                acceptor::Event::Accepted(pipes) => {
+                    let pipes = pipes.into_iter().map(|pipe| self.wrap(pipe)).collect();
+
+                    ctx.raise(acceptor::Event::Accepted(pipes));
+                },
+                other => ctx.raise(other)
+            }
+        }
+    }
+
+    fn wrap(&mut self, pipe: Box<Pipe>) -> Box<Pipe> {
+        let seed = self.rng.next_u64();
+
+        Box::new(FaultyPipe::new(pipe, self.faults.clone(), Rng(seed)))
+    }
+}
+
+impl Acceptor for FaultyAcceptor {
+    fn ready(&mut self, ctx: &mut acceptor::Context, events: Ready) {
+        if events.is_readable() && self.accept_failures > 0 {
+            self.accept_failures -= 1;
+            self.stats.accept_failures.fetch_add(1, Ordering::SeqCst);
+            return ctx.raise(acceptor::Event::Error(exhaustion_error()));
+        }
+
+        self.forward(ctx, |acceptor, ctx| acceptor.ready(ctx, events));
+    }
+
+    fn open(&mut self, ctx: &mut acceptor::Context) {
+        self.forward(ctx, |acceptor, ctx| acceptor.open(ctx));
+    }
+
+    fn close(&mut self, ctx: &mut acceptor::Context) {
+        self.forward(ctx, |acceptor, ctx| acceptor.close(ctx));
+    }
+
+    fn pause(&mut self, ctx: &mut acceptor::Context) {
+        self.forward(ctx, |acceptor, ctx| acceptor.pause(ctx));
+    }
+
+    fn resume(&mut self, ctx: &mut acceptor::Context) {
+        self.forward(ctx, |acceptor, ctx| acceptor.resume(ctx));
+    }
+
+    fn local_addr(&self) -> Option<String> {
+        self.inner.local_addr()
+    }
+}
+
+#[cfg(unix)]
+fn exhaustion_error() -> io::Error {
+    use libc;
+
+    io::Error::from_raw_os_error(libc::EMFILE)
+}
+
+#[cfg(windows)]
+fn exhaustion_error() -> io::Error {
+    const WSAEMFILE: i32 = 10024;
+
+    io::Error::from_raw_os_error(WSAEMFILE)
+}
+
+struct AcceptorInterceptor<'a> {
+    inner: &'a mut acceptor::Context,
+    events: Vec<acceptor::Event>
+}
+
+impl<'a> EndpointRegistrar for AcceptorInterceptor<'a> {
+    fn register(&mut self, io: &Evented, interest: Ready, opt: PollOpt) {
+        self.inner.register(io, interest, opt);
+    }
+    fn reregister(&mut self, io: &Evented, interest: Ready, opt: PollOpt) {
+        self.inner.reregister(io, interest, opt);
+    }
+    fn deregister(&mut self, io: &Evented) {
+        self.inner.deregister(io);
+    }
+}
+
+impl<'a> acceptor::Context for AcceptorInterceptor<'a> {
+    fn raise(&mut self, evt: acceptor::Event) {
+        self.events.push(evt);
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* FaultyPipe                                                                */
+/*                                                                           */
+/*****************************************************************************/
+
+// A stalled pipe never opens the wrapped one, it only waits to be closed.
+#[derive(Clone, Copy, PartialEq)]
+enum Stall {
+    Connect,
+    Handshake
+}
+
+struct FaultyPipe {
+    inner: Box<Pipe>,
+    faults: Faults,
+    rng: Rng,
+    stall: Option<Stall>,
+    corrupt_handshake: bool,
+    wakeup: Option<(Registration, SetReadiness)>,
+    delayed: VecDeque<(Instant, Rc<Message>)>,
+    failed: bool
+}
+
+impl FaultyPipe {
+    fn new(inner: Box<Pipe>, faults: Faults, mut rng: Rng) -> FaultyPipe {
+        let stall = if rng.chance(faults.connect_stall) {
+            Some(Stall::Connect)
+        } else if rng.chance(faults.handshake_stall) {
+            Some(Stall::Handshake)
+        } else {
+            None
+        };
+        let corrupt_handshake = rng.chance(faults.handshake_corruption);
+
+        FaultyPipe {
+            inner: inner,
+            faults: faults,
+            rng: rng,
+            stall: stall,
+            corrupt_handshake: corrupt_handshake,
+            wakeup: None,
+            delayed: VecDeque::new(),
+            failed: false
+        }
+    }
+
+    fn forward<F>(&mut self, ctx: &mut pipe::Context, f: F) where F : FnOnce(&mut Pipe, &mut pipe::Context) {
+        let events = {
+            let mut interceptor = PipeInterceptor { inner: ctx, events: Vec::new() };
+
+            f(&mut *self.inner, &mut interceptor);
+            interceptor.events
+        };
+
+        for evt in events {
+            match evt {
+                pipe::Event::Closed => ctx.raise(pipe::Event::Closed),
+                _ if self.failed => continue,
+                pipe::Event::Opened if self.corrupt_handshake => {
+                    self.fail(ctx, io::ErrorKind::InvalidData, "injected handshake corruption");
+                },
+                other => ctx.raise(other)
+            }
+        }
+    }
+
+    // The pipe stays open until the socket reacts to the error by closing it.
+    fn fail(&mut self, ctx: &mut pipe::Context, kind: io::ErrorKind, reason: &'static str) {
+        self.failed = true;
+        self.delayed.clear();
+        ctx.raise(pipe::Event::Error(io::Error::new(kind, reason)));
+    }
+
+    fn truncate(&mut self, msg: Rc<Message>) -> Rc<Message> {
+        if msg.get_body().is_empty() {
+            return msg;
+        }
+
+        let len = self.rng.below(msg.get_body().len());
+        let header = msg.get_header().to_vec();
+        let body = msg.get_body()[..len].to_vec();
+
+        Rc::new(Message::from_header_and_body(header, body))
+    }
+
+    fn delay(&mut self, msg: Rc<Message>, delay: Duration) {
+        if let Some((_, ref set_readiness)) = self.wakeup {
+            let set_readiness = set_readiness.clone();
+
+            thread::spawn(move || {
+                thread::sleep(delay);
+                let _ = set_readiness.set_readiness(Ready::readable());
+            });
+        }
+
+        self.delayed.push_back((Instant::now() + delay, msg));
+    }
+
+    fn send_delayed(&mut self, ctx: &mut pipe::Context) -> bool {
+        let now = Instant::now();
+        let mut expired = false;
+
+        while self.delayed.front().map_or(false, |&(deadline, _)| deadline <= now) {
+            if let Some((_, msg)) = self.delayed.pop_front() {
+                self.forward(ctx, |pipe, ctx| pipe.send(ctx, msg));
+                expired = true;
+            }
+        }
+
+        if let Some((_, ref set_readiness)) = self.wakeup {
+            if expired && self.delayed.is_empty() {
+                let _ = set_readiness.set_readiness(Ready::empty());
+            }
+        }
+
+        expired
+    }
+}
+
+impl Pipe for FaultyPipe {
+    fn ready(&mut self, ctx: &mut pipe::Context, events: Ready) {
+        if self.stall.is_some() {
+            return;
+        }
+
+        // The wake-up readiness must not reach the wrapped pipe,
+        // its connection is registered level-triggered so nothing is lost.
+        if !self.send_delayed(ctx) {
+            self.forward(ctx, |pipe, ctx| pipe.ready(ctx, events));
+        }
+    }
+
+    fn open(&mut self, ctx: &mut pipe::Context) {
+        match self.stall {
+            Some(Stall::Connect) => return,
+            Some(Stall::Handshake) => return ctx.raise(pipe::Event::Connected),
+            None => {}
+        }
+
+        if self.faults.delay.is_some() {
+            let (registration, set_readiness) = Registration::new2();
+
+            ctx.register(&registration, Ready::readable(), PollOpt::edge());
+            self.wakeup = Some((registration, set_readiness));
+        }
+
+        self.forward(ctx, |pipe, ctx| pipe.open(ctx));
+    }
+
+    fn close(&mut self, ctx: &mut pipe::Context) {
+        if self.stall.is_some() {
+            return ctx.raise(pipe::Event::Closed);
+        }
+
+        if let Some((registration, _)) = self.wakeup.take() {
+            ctx.deregister(&registration);
+        }
+
+        self.delayed.clear();
+        self.forward(ctx, |pipe, ctx| pipe.close(ctx));
+    }
+
+    fn send(&mut self, ctx: &mut pipe::Context, msg: Rc<Message>) {
+        if self.failed || self.stall.is_some() {
+            return;
+        }
+
+        if self.rng.chance(self.faults.disconnect) {
+            return self.fail(ctx, io::ErrorKind::ConnectionReset, "injected disconnection");
+        }
+
+        if self.rng.chance(self.faults.drop) {
+            ctx.raise(pipe::Event::Sent);
+            ctx.raise(pipe::Event::CanSend(true));
+            return;
+        }
+
+        let msg = if self.rng.chance(self.faults.truncation) {
+            self.truncate(msg)
+        } else {
+            msg
+        };
+
+        if let Some(delay) = self.faults.delay {
+            return self.delay(msg, delay);
+        }
+
+        self.forward(ctx, |pipe, ctx| pipe.send(ctx, msg));
+    }
+
+    fn recv(&mut self, ctx: &mut pipe::Context) {
+        if self.failed || self.stall.is_some() {
+            return;
+        }
+
+        self.forward(ctx, |pipe, ctx| pipe.recv(ctx));
+    }
+
+    fn local_addr(&self) -> Option<String> {
+        self.inner.local_addr()
+    }
+
+    fn peer_addr(&self) -> Option<String> {
+        self.inner.peer_addr()
+    }
+
+    fn peer_cred(&self) -> Option<PeerCred> {
+        self.inner.peer_cred()
+    }
+}
+
+struct PipeInterceptor<'a> {
+    inner: &'a mut pipe::Context,
+    events: Vec<pipe::Event>
+}
+
+impl<'a> fmt::Debug for PipeInterceptor<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl<'a> EndpointRegistrar for PipeInterceptor<'a> {
+    fn register(&mut self, io: &Evented, interest: Ready, opt: PollOpt) {
+        self.inner.register(io, interest, opt);
+    }
+    fn reregister(&mut self, io: &Evented, interest: Ready, opt: PollOpt) {
+        self.inner.reregister(io, interest, opt);
+    }
+    fn deregister(&mut self, io: &Evented) {
+        self.inner.deregister(io);
+    }
+}
+
+impl<'a> pipe::Context for PipeInterceptor<'a> {
+    fn raise(&mut self, evt: pipe::Event) {
+        self.events.push(evt);
+    }
+}
@@ -0,0 +1,138 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+//! Entry points of the fuzz targets found in the `fuzz` directory of the repository.
+//!
+//! Each function feeds arbitrary bytes to one of the parsers handling data received from the network,
+//! and must return without panicking whatever the input.
+//! The stream parsers are fed in chunks, with a `WouldBlock` error between each of them,
+//! as a non-blocking socket would; the size of the chunks is given by the first byte of the input.
+
+use std::cmp;
+use std::io;
+
+use core::{EndpointId, Message};
+use proto::{REQ, REP};
+use proto::{req, rep, surv, resp};
+use transport::stream;
+use transport::ipc;
+use transport::async::stub::{recv_and_check_handshake, HandshakeBuffer};
+
+/// Parses the input as a sequence of tcp framed messages.
+pub fn stream_recv(data: &[u8]) {
+    let mut stream = ChunkedStream::new(data);
+    let mut operation = stream::recv::RecvOperation::new(u64::max_value());
+
+    loop {
+        match operation.run(&mut stream) {
+            Ok(Some(_)) => operation = stream::recv::RecvOperation::new(u64::max_value()),
+            Ok(None) => continue,
+            Err(_) => return
+        }
+    }
+}
+
+/// Parses the input as a sequence of ipc framed messages.
+pub fn ipc_recv(data: &[u8]) {
+    let mut stream = ChunkedStream::new(data);
+    let mut operation = ipc::recv::RecvOperation::new(u64::max_value());
+
+    loop {
+        match operation.run(&mut stream) {
+            Ok(Some(_)) => operation = ipc::recv::RecvOperation::new(u64::max_value()),
+            Ok(None) => continue,
+            Err(_) => return
+        }
+    }
+}
+
+/// Parses the input as the handshake of a peer.
+pub fn handshake(data: &[u8]) {
+    let mut stream = ChunkedStream::new(data);
+    let mut received = HandshakeBuffer::default();
+
+    loop {
+        match recv_and_check_handshake(&mut stream, (REQ, REP), &mut received) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+            _ => return
+        }
+    }
+}
+
+/// Parses the input as a request received by a req socket in raw mode, that is, a reply.
+pub fn req_decode(data: &[u8]) {
+    if let Some((msg, _)) = req::decode(Message::from_body(data.to_vec())) {
+        assert_eq!(data.len(), msg.len());
+    }
+}
+
+/// Parses the input as a message received by a surveyor, that is, a response.
+pub fn surv_decode(data: &[u8]) {
+    if let Some((msg, _)) = surv::decode(Message::from_body(data.to_vec())) {
+        assert_eq!(data.len(), msg.len());
+    }
+}
+
+/// Parses the input as a request received by a rep socket, the first byte giving the ttl.
+pub fn rep_backtrace(data: &[u8]) {
+    if let Some((&ttl, body)) = data.split_first() {
+        if let Some(msg) = rep::decode(Message::from_body(body.to_vec()), EndpointId::from(1), ttl) {
+            assert_eq!(body.len() + 4, msg.len());
+        }
+    }
+}
+
+/// Parses the input as a survey received by a respondent socket, the first byte giving the ttl.
+pub fn resp_backtrace(data: &[u8]) {
+    if let Some((&ttl, body)) = data.split_first() {
+        if let Some(msg) = resp::decode(Message::from_body(body.to_vec()), EndpointId::from(1), ttl) {
+            assert_eq!(body.len() + 4, msg.len());
+        }
+    }
+}
+
+/// Non-blocking reader delivering its content in chunks of 1 to `max_chunk` bytes,
+/// and reporting the end of the stream once everything has been read.
+struct ChunkedStream<'a> {
+    data: &'a [u8],
+    max_chunk: usize,
+    next_chunk: usize,
+    blocked: bool
+}
+
+impl<'a> ChunkedStream<'a> {
+    fn new(data: &'a [u8]) -> ChunkedStream<'a> {
+        let (max_chunk, data) = match data.split_first() {
+            Some((&first, rest)) => (first as usize + 1, rest),
+            None => (1, data)
+        };
+
+        ChunkedStream {
+            data: data,
+            max_chunk: max_chunk,
+            next_chunk: 1,
+            blocked: false
+        }
+    }
+}
+
+impl<'a> io::Read for ChunkedStream<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.blocked {
+            self.blocked = false;
+            return Err(io::Error::new(io::ErrorKind::WouldBlock, "chunk boundary"));
+        }
+
+        let len = cmp::min(cmp::min(buf.len(), self.next_chunk), self.data.len());
+
+        buf[..len].copy_from_slice(&self.data[..len]);
+        self.data = &self.data[len..];
+        self.next_chunk = self.next_chunk % self.max_chunk + 1;
+        self.blocked = len > 0;
+
+        Ok(len)
+    }
+}
@@ -0,0 +1,595 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+//! Test doubles to validate a [`Protocol`](../core/socket/trait.Protocol.html) without any I/O.
+//!
+//! [`RecordingContext`](struct.RecordingContext.html) records what a protocol asks the pipes and the scheduler to do,
+//! while [`ProtocolHarness`](struct.ProtocolHarness.html) drives a protocol like the socket runtime would
+//! and panics as soon as the protocol breaks one of the rules of the extension API:
+//!
+//! - the last `CanSend`/`CanRecv` event raised must match `is_send_ready`/`is_recv_ready`,
+//! - a pipe can only be sent to, or received from, after it has been reported ready,
+//!   and only once until the operation is acknowledged,
+//! - each `send` and `recv` gets at most one reply, and its timeout is cancelled when the reply is not an expiration.
+//!
+//! The [`transport`](transport/index.html) module does the same for transports,
+//! and [`faulty`](faulty/index.html) injects failures in the connections of a real transport.
+//! [`fuzzing`](fuzzing/index.html) holds the entry points of the fuzz targets.
+
+pub mod transport;
+pub mod faulty;
+pub mod fuzzing;
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io;
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use core::{SocketId, EndpointId, Message, EndpointTmpl, EndpointDesc, PeerCred, Scheduled};
+use core::endpoint::Pipe;
+use core::context::{Context, Scheduler, Schedulable, Event};
+use core::network::Network;
+use core::socket::{Protocol, Reply};
+use io_error::*;
+
+/// Creates a pipe that is not backed by any connection, with the default priorities.
+pub fn new_pipe(eid: EndpointId) -> Pipe {
+    new_pipe_with_priorities(eid, 8, 8)
+}
+
+/// Creates a pipe that is not backed by any connection, with the specified priorities.
+pub fn new_pipe_with_priorities(eid: EndpointId, send_priority: u8, recv_priority: u8) -> Pipe {
+    let desc = EndpointDesc {
+        send_priority: send_priority,
+        recv_priority: recv_priority,
+        tcp_no_delay: false,
+        tcp_keepalive: None,
+        recv_max_size: 1024 * 1024,
+        max_connections: None
+    };
+
+    Pipe::new_accepted(eid, desc)
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* RecordingContext                                                          */
+/*                                                                           */
+/*****************************************************************************/
+
+/// `Context` implementation recording every call made by a protocol.
+pub struct RecordingContext {
+    sent: Vec<(EndpointId, Rc<Message>)>,
+    recv_requests: Vec<EndpointId>,
+    closed: Vec<EndpointId>,
+    events: Vec<Event>,
+    timers: Vec<(Scheduled, Duration, Schedulable)>,
+    cancelled: Vec<Scheduled>,
+    next_timer: usize,
+    schedule_fails: bool
+}
+
+impl Default for RecordingContext {
+    fn default() -> RecordingContext {
+        RecordingContext::new()
+    }
+}
+
+impl RecordingContext {
+    /// Creates an empty context.
+    pub fn new() -> RecordingContext {
+        RecordingContext {
+            sent: Vec::new(),
+            recv_requests: Vec::new(),
+            closed: Vec::new(),
+            events: Vec::new(),
+            timers: Vec::new(),
+            cancelled: Vec::new(),
+            next_timer: 1,
+            schedule_fails: false
+        }
+    }
+
+    /// Messages sent, with the pipe they were sent to, in call order.
+    pub fn sent(&self) -> &[(EndpointId, Rc<Message>)] {
+        &self.sent
+    }
+
+    /// Pipes a message was requested from, in call order.
+    pub fn recv_requests(&self) -> &[EndpointId] {
+        &self.recv_requests
+    }
+
+    /// Pipes that have been closed, in call order.
+    pub fn closed(&self) -> &[EndpointId] {
+        &self.closed
+    }
+
+    /// Events raised by the protocol, in call order.
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// Timers scheduled and not yet delivered, cancelled ones included.
+    pub fn timers(&self) -> &[(Scheduled, Duration, Schedulable)] {
+        &self.timers
+    }
+
+    /// Timers cancelled by the protocol, in call order.
+    pub fn cancelled(&self) -> &[Scheduled] {
+        &self.cancelled
+    }
+
+    /// Whether the protocol cancelled the timer.
+    pub fn is_cancelled(&self, scheduled: Scheduled) -> bool {
+        self.cancelled.contains(&scheduled)
+    }
+
+    /// Makes the next calls to `schedule` fail, or succeed again.
+    pub fn set_schedule_fails(&mut self, fails: bool) {
+        self.schedule_fails = fails;
+    }
+
+    /// Forgets everything recorded so far, pending timers excepted.
+    pub fn clear(&mut self) {
+        self.sent.clear();
+        self.recv_requests.clear();
+        self.closed.clear();
+        self.events.clear();
+        self.cancelled.clear();
+    }
+
+    fn next_scheduled(&mut self) -> Scheduled {
+        let scheduled = Scheduled::from(self.next_timer);
+
+        self.next_timer += 1;
+        scheduled
+    }
+
+    fn take_timer(&mut self, scheduled: Scheduled) -> Option<Schedulable> {
+        self.timers.iter().
+            position(|timer| timer.0 == scheduled).
+            map(|index| self.timers.remove(index).2)
+    }
+}
+
+impl fmt::Debug for RecordingContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RecordingContext")
+    }
+}
+
+impl Network for RecordingContext {
+    fn connect(&mut self, _: SocketId, _: &EndpointTmpl) -> io::Result<EndpointId> {
+        Err(other_io_error("connect is not supported by RecordingContext"))
+    }
+    fn reconnect(&mut self, _: SocketId, _: EndpointId, _: &EndpointTmpl) -> io::Result<()> {
+        Err(other_io_error("reconnect is not supported by RecordingContext"))
+    }
+    fn bind(&mut self, _: SocketId, _: &EndpointTmpl) -> io::Result<EndpointId> {
+        Err(other_io_error("bind is not supported by RecordingContext"))
+    }
+    fn rebind(&mut self, _: SocketId, _: EndpointId, _: &EndpointTmpl) -> io::Result<()> {
+        Err(other_io_error("rebind is not supported by RecordingContext"))
+    }
+    fn open(&mut self, _: EndpointId, _: bool) {
+    }
+    fn close(&mut self, eid: EndpointId, _: bool) {
+        self.closed.push(eid)
+    }
+    fn pause(&mut self, _: EndpointId) {
+    }
+    fn resume(&mut self, _: EndpointId) {
+    }
+    fn send(&mut self, eid: EndpointId, msg: Rc<Message>) {
+        self.sent.push((eid, msg))
+    }
+    fn recv(&mut self, eid: EndpointId) {
+        self.recv_requests.push(eid)
+    }
+    fn local_addr(&self, _: EndpointId, _: bool) -> Option<String> {
+        None
+    }
+    fn peer_addr(&self, _: EndpointId) -> Option<String> {
+        None
+    }
+    fn peer_cred(&self, _: EndpointId) -> Option<PeerCred> {
+        None
+    }
+}
+
+impl Scheduler for RecordingContext {
+    fn schedule(&mut self, schedulable: Schedulable, delay: Duration) -> io::Result<Scheduled> {
+        if self.schedule_fails {
+            return Err(other_io_error("schedule failure requested by the test"));
+        }
+
+        let scheduled = self.next_scheduled();
+
+        self.timers.push((scheduled, delay, schedulable));
+        Ok(scheduled)
+    }
+    fn cancel(&mut self, scheduled: Scheduled) {
+        self.cancelled.push(scheduled)
+    }
+}
+
+impl Context for RecordingContext {
+    fn raise(&mut self, evt: Event) {
+        self.events.push(evt)
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* ProtocolHarness                                                           */
+/*                                                                           */
+/*****************************************************************************/
+
+#[derive(Default)]
+struct PipeState {
+    can_send: bool,
+    sending: bool,
+    can_recv: bool,
+    receiving: bool
+}
+
+#[derive(Default)]
+struct Operation {
+    pending: bool,
+    timeout: Option<Scheduled>
+}
+
+/// Drives a protocol through the same sequences of calls as a socket, checking the rules of the extension API after each step.
+pub struct ProtocolHarness<P : Protocol> {
+    protocol: P,
+    ctx: RecordingContext,
+    reply_rx: mpsc::Receiver<Reply>,
+    replies: VecDeque<Reply>,
+    pipes: HashMap<EndpointId, PipeState>,
+    send_op: Operation,
+    recv_op: Operation,
+    can_send: bool,
+    can_recv: bool,
+    seen_sent: usize,
+    seen_recv_requests: usize,
+    seen_events: usize
+}
+
+impl<P : Protocol + From<mpsc::Sender<Reply>>> ProtocolHarness<P> {
+    /// Creates the protocol, and polls its initial readiness like a new socket does.
+    pub fn new() -> ProtocolHarness<P> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        let protocol = P::from(reply_tx);
+        let can_send = protocol.is_send_ready();
+        let can_recv = protocol.is_recv_ready();
+
+        ProtocolHarness {
+            protocol: protocol,
+            ctx: RecordingContext::new(),
+            reply_rx: reply_rx,
+            replies: VecDeque::new(),
+            pipes: HashMap::new(),
+            send_op: Operation::default(),
+            recv_op: Operation::default(),
+            can_send: can_send,
+            can_recv: can_recv,
+            seen_sent: 0,
+            seen_recv_requests: 0,
+            seen_events: 0
+        }
+    }
+}
+
+impl<P : Protocol> ProtocolHarness<P> {
+
+    /// The protocol under test.
+    pub fn protocol(&self) -> &P {
+        &self.protocol
+    }
+
+    /// The protocol under test, to change its options for example.
+    pub fn protocol_mut(&mut self) -> &mut P {
+        &mut self.protocol
+    }
+
+    /// What the protocol did so far.
+    pub fn context(&self) -> &RecordingContext {
+        &self.ctx
+    }
+
+    /// Adds a virtual pipe with the default priorities.
+    pub fn add_pipe(&mut self, eid: EndpointId) {
+        self.add_pipe_with_priorities(eid, 8, 8)
+    }
+
+    /// Adds a virtual pipe with the specified priorities.
+    pub fn add_pipe_with_priorities(&mut self, eid: EndpointId, send_priority: u8, recv_priority: u8) {
+        assert!(!self.pipes.contains_key(&eid), "pipe {:?} has already been added", eid);
+
+        let pipe = new_pipe_with_priorities(eid, send_priority, recv_priority);
+
+        self.pipes.insert(eid, PipeState::default());
+        self.protocol.add_pipe(&mut self.ctx, eid, pipe);
+        self.verify();
+    }
+
+    /// Removes the pipe, the protocol must give it back.
+    pub fn remove_pipe(&mut self, eid: EndpointId) {
+        assert!(self.pipes.remove(&eid).is_some(), "pipe {:?} has not been added", eid);
+
+        let pipe = self.protocol.remove_pipe(&mut self.ctx, eid);
+
+        assert!(pipe.is_some(), "protocol did not give pipe {:?} back", eid);
+        self.verify();
+    }
+
+    /// Asks the protocol to send a message, without timeout.
+    pub fn send(&mut self, msg: Message) {
+        self.start_send(msg, None)
+    }
+
+    /// Asks the protocol to send a message, and returns the timeout handed to the protocol.
+    pub fn send_with_timeout(&mut self, msg: Message) -> Scheduled {
+        let timeout = self.ctx.next_scheduled();
+
+        self.start_send(msg, Some(timeout));
+        timeout
+    }
+
+    fn start_send(&mut self, msg: Message, timeout: Option<Scheduled>) {
+        assert!(!self.send_op.pending, "a send operation is already pending");
+
+        self.send_op = Operation { pending: true, timeout: timeout };
+        self.protocol.send(&mut self.ctx, msg, timeout);
+        self.verify();
+    }
+
+    /// Acknowledges the message the protocol sent to the pipe.
+    pub fn send_ack(&mut self, eid: EndpointId) {
+        {
+            let pipe = self.pipe_mut(eid);
+            assert!(pipe.sending, "no message has been sent to pipe {:?}", eid);
+            pipe.sending = false;
+        }
+        self.protocol.on_send_ack(&mut self.ctx, eid);
+        self.verify();
+    }
+
+    /// Expires the pending send operation.
+    pub fn send_timeout(&mut self) {
+        self.protocol.on_send_timeout(&mut self.ctx);
+        self.verify_expiration(true);
+    }
+
+    /// Reports the pipe as ready to send a message.
+    pub fn send_ready(&mut self, eid: EndpointId) {
+        self.pipe_mut(eid).can_send = true;
+        self.protocol.on_send_ready(&mut self.ctx, eid);
+        self.verify();
+    }
+
+    /// Reports the pipe as no longer ready to send a message.
+    pub fn send_not_ready(&mut self, eid: EndpointId) {
+        self.pipe_mut(eid).can_send = false;
+        self.protocol.on_send_not_ready(&mut self.ctx, eid);
+        self.verify();
+    }
+
+    /// Asks the protocol to receive a message, without timeout.
+    pub fn recv(&mut self) {
+        self.start_recv(None)
+    }
+
+    /// Asks the protocol to receive a message, and returns the timeout handed to the protocol.
+    pub fn recv_with_timeout(&mut self) -> Scheduled {
+        let timeout = self.ctx.next_scheduled();
+
+        self.start_recv(Some(timeout));
+        timeout
+    }
+
+    fn start_recv(&mut self, timeout: Option<Scheduled>) {
+        assert!(!self.recv_op.pending, "a recv operation is already pending");
+
+        self.recv_op = Operation { pending: true, timeout: timeout };
+        self.protocol.recv(&mut self.ctx, timeout);
+        self.verify();
+    }
+
+    /// Delivers a message from the pipe the protocol requested it from.
+    pub fn recv_ack(&mut self, eid: EndpointId, msg: Message) {
+        {
+            let pipe = self.pipe_mut(eid);
+            assert!(pipe.receiving, "no message has been requested from pipe {:?}", eid);
+            pipe.receiving = false;
+        }
+        self.protocol.on_recv_ack(&mut self.ctx, eid, msg);
+        self.verify();
+    }
+
+    /// Expires the pending recv operation.
+    pub fn recv_timeout(&mut self) {
+        self.protocol.on_recv_timeout(&mut self.ctx);
+        self.verify_expiration(false);
+    }
+
+    /// Reports the pipe as having a message available.
+    pub fn recv_ready(&mut self, eid: EndpointId) {
+        self.pipe_mut(eid).can_recv = true;
+        self.protocol.on_recv_ready(&mut self.ctx, eid);
+        self.verify();
+    }
+
+    /// Reports the pipe as no longer having a message available.
+    pub fn recv_not_ready(&mut self, eid: EndpointId) {
+        self.pipe_mut(eid).can_recv = false;
+        self.protocol.on_recv_not_ready(&mut self.ctx, eid);
+        self.verify();
+    }
+
+    /// Delivers a timer the protocol scheduled, it must not have been cancelled.
+    /// `SendTimeout` and `RecvTimeout` are routed to the dedicated callbacks, like the socket does.
+    pub fn fire_timer(&mut self, scheduled: Scheduled) {
+        assert!(!self.ctx.is_cancelled(scheduled), "timer {:?} has been cancelled", scheduled);
+
+        match self.ctx.take_timer(scheduled) {
+            Some(Schedulable::SendTimeout) => self.send_timeout(),
+            Some(Schedulable::RecvTimeout) => self.recv_timeout(),
+            Some(other) => {
+                self.protocol.on_timer_tick(&mut self.ctx, other);
+                self.verify();
+            },
+            None => panic!("timer {:?} has not been scheduled", scheduled)
+        }
+    }
+
+    /// Closes the protocol, all its pipes must be closed.
+    pub fn close(&mut self) {
+        self.protocol.close(&mut self.ctx);
+
+        for eid in self.pipes.keys() {
+            assert!(self.ctx.closed.contains(eid), "pipe {:?} has not been closed", eid);
+        }
+        self.pipes.clear();
+    }
+
+    /// Returns the oldest reply not yet consumed.
+    pub fn take_reply(&mut self) -> Option<Reply> {
+        self.replies.pop_front()
+    }
+
+    /// Consumes a reply, which must acknowledge a send.
+    pub fn expect_send_reply(&mut self) {
+        match self.take_reply() {
+            Some(Reply::Send) => {},
+            Some(Reply::Err(e)) => panic!("expected send reply, got error {}", e),
+            Some(_) => panic!("expected send reply, got another reply"),
+            None => panic!("expected send reply, got nothing")
+        }
+    }
+
+    /// Consumes a reply, which must carry a received message.
+    pub fn expect_recv_reply(&mut self) -> Message {
+        match self.take_reply() {
+            Some(Reply::Recv(msg)) => msg,
+            Some(Reply::Err(e)) => panic!("expected recv reply, got error {}", e),
+            Some(_) => panic!("expected recv reply, got another reply"),
+            None => panic!("expected recv reply, got nothing")
+        }
+    }
+
+    /// Consumes a reply, which must be an error of the specified kind.
+    pub fn expect_err_reply(&mut self, kind: io::ErrorKind) -> io::Error {
+        match self.take_reply() {
+            Some(Reply::Err(e)) => {
+                assert_eq!(kind, e.kind());
+                e
+            },
+            Some(_) => panic!("expected {:?} error, got another reply", kind),
+            None => panic!("expected {:?} error, got nothing", kind)
+        }
+    }
+
+    /// Checks no reply is waiting to be consumed.
+    pub fn expect_no_reply(&self) {
+        assert!(self.replies.is_empty(), "expected no reply, got {}", self.replies.len());
+    }
+
+    /// Readiness the socket would report to the user, according to the events raised so far.
+    pub fn readiness(&self) -> (bool, bool) {
+        (self.can_send, self.can_recv)
+    }
+
+    fn pipe_mut(&mut self, eid: EndpointId) -> &mut PipeState {
+        self.pipes.get_mut(&eid).unwrap_or_else(|| panic!("pipe {:?} has not been added", eid))
+    }
+
+    fn verify(&mut self) {
+        self.verify_pipe_operations();
+        self.verify_events();
+        self.verify_replies(None);
+    }
+
+    fn verify_expiration(&mut self, send: bool) {
+        self.verify_pipe_operations();
+        self.verify_events();
+        self.verify_replies(Some(send));
+    }
+
+    fn verify_pipe_operations(&mut self) {
+        for &(eid, _) in &self.ctx.sent[self.seen_sent..] {
+            let pipe = self.pipes.get_mut(&eid).unwrap_or_else(|| panic!("message sent to unknown pipe {:?}", eid));
+
+            assert!(pipe.can_send, "message sent to pipe {:?} which is not ready", eid);
+            assert!(!pipe.sending, "message sent to pipe {:?} before the previous one was acknowledged", eid);
+            pipe.can_send = false;
+            pipe.sending = true;
+        }
+        for &eid in &self.ctx.recv_requests[self.seen_recv_requests..] {
+            let pipe = self.pipes.get_mut(&eid).unwrap_or_else(|| panic!("message requested from unknown pipe {:?}", eid));
+
+            assert!(pipe.can_recv, "message requested from pipe {:?} which is not ready", eid);
+            assert!(!pipe.receiving, "message requested from pipe {:?} before the previous one was delivered", eid);
+            pipe.can_recv = false;
+            pipe.receiving = true;
+        }
+
+        self.seen_sent = self.ctx.sent.len();
+        self.seen_recv_requests = self.ctx.recv_requests.len();
+    }
+
+    fn verify_events(&mut self) {
+        for evt in &self.ctx.events[self.seen_events..] {
+            match *evt {
+                Event::CanSend(x) => self.can_send = x,
+                Event::CanRecv(x) => self.can_recv = x,
+                Event::Closed => {}
+            }
+        }
+        self.seen_events = self.ctx.events.len();
+
+        assert_eq!(self.protocol.is_send_ready(), self.can_send, "send readiness does not match the last CanSend event");
+        assert_eq!(self.protocol.is_recv_ready(), self.can_recv, "recv readiness does not match the last CanRecv event");
+    }
+
+    // `expired` tells which operation, if any, has just expired: true for send, false for recv.
+    fn verify_replies(&mut self, expired: Option<bool>) {
+        while let Ok(reply) = self.reply_rx.try_recv() {
+            match reply {
+                Reply::Send => Self::complete(&mut self.send_op, &self.ctx, "send"),
+                Reply::Recv(_) => Self::complete(&mut self.recv_op, &self.ctx, "recv"),
+                Reply::Err(ref e) if e.kind() == io::ErrorKind::TimedOut => match expired {
+                    Some(true) => self.send_op = Operation::default(),
+                    Some(false) => self.recv_op = Operation::default(),
+                    None => {}
+                },
+                Reply::Err(_) => {
+                    if self.send_op.pending && !self.recv_op.pending {
+                        Self::complete(&mut self.send_op, &self.ctx, "send");
+                    } else if self.recv_op.pending && !self.send_op.pending {
+                        Self::complete(&mut self.recv_op, &self.ctx, "recv");
+                    }
+                },
+                _ => {}
+            }
+
+            self.replies.push_back(reply);
+        }
+    }
+
+    fn complete(op: &mut Operation, ctx: &RecordingContext, name: &str) {
+        assert!(op.pending, "replied to a {} operation that was not requested", name);
+
+        if let Some(timeout) = op.timeout {
+            assert!(ctx.is_cancelled(timeout), "{} timeout was not cancelled after the reply", name);
+        }
+
+        *op = Operation::default();
+    }
+}
@@ -0,0 +1,568 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+//! Conformance checks for [`Transport`](../../transport/trait.Transport.html) implementations.
+//!
+//! [`TransportHarness`](struct.TransportHarness.html) drives the pipes and acceptors created by a transport
+//! with its own poller, recording the events they raise.
+//! The `check_*` functions use it to verify the behavior expected from every transport,
+//! and panic with a description of the first deviation.
+//! Each check binds to the specified address, so it must be available and valid for the transport.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use mio::{Evented, Events, Poll, PollOpt, Ready, Token};
+
+use core::Message;
+use core::pool::BufferPool;
+use transport::{Transport, Destination};
+use transport::endpoint::EndpointRegistrar;
+use transport::pipe::{self, Pipe};
+use transport::acceptor::{self, Acceptor};
+
+/// Protocol ids used by the checks, those of the pair protocol.
+pub const PIDS: (u16, u16) = (16, 16);
+
+/// Maximum time a check waits for the transport to make progress.
+pub const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+const BIG_MSG_LEN: usize = 32 * 1024 * 1024;
+
+/// Identifies a pipe driven by a [`TransportHarness`](struct.TransportHarness.html).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PipeId(usize);
+
+/// Identifies an acceptor driven by a [`TransportHarness`](struct.TransportHarness.html).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AcceptorId(usize);
+
+struct PipeSlot {
+    pipe: Option<Box<Pipe>>,
+    events: Vec<pipe::Event>,
+    can_send: bool,
+    can_recv: bool
+}
+
+struct AcceptorSlot {
+    acceptor: Option<Box<Acceptor>>,
+    accepted: Vec<PipeId>,
+    errors: Vec<io::Error>
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* TransportHarness                                                          */
+/*                                                                           */
+/*****************************************************************************/
+
+/// Runs the endpoints created by a transport outside of any session.
+pub struct TransportHarness {
+    transport: Box<Transport>,
+    poll: Poll,
+    pipes: HashMap<usize, PipeSlot>,
+    acceptors: HashMap<usize, AcceptorSlot>,
+    buffer_pool: BufferPool,
+    next_token: usize
+}
+
+impl TransportHarness {
+    /// Creates a harness with its own poller.
+    pub fn new<T : Transport + 'static>(transport: T) -> io::Result<TransportHarness> {
+        let poll = try!(Poll::new());
+
+        Ok(TransportHarness {
+            transport: Box::new(transport),
+            poll: poll,
+            pipes: HashMap::new(),
+            acceptors: HashMap::new(),
+            buffer_pool: BufferPool::default(),
+            next_token: 1
+        })
+    }
+
+    /// Binds the transport to the address and opens the acceptor, accepted pipes are opened automatically.
+    pub fn bind(&mut self, addr: &str, pids: (u16, u16), recv_max_size: u64) -> io::Result<AcceptorId> {
+        let acceptor = {
+            let dest = destination(addr, pids, recv_max_size, &self.buffer_pool);
+            try!(self.transport.bind(&dest))
+        };
+        let token = self.next_token();
+        let mut slot = AcceptorSlot { acceptor: Some(acceptor), accepted: Vec::new(), errors: Vec::new() };
+        let accepted = {
+            let mut ctx = AcceptorContext::new(&self.poll, token, &mut slot.errors);
+            if let Some(ref mut acceptor) = slot.acceptor {
+                acceptor.open(&mut ctx);
+            }
+            ctx.accepted
+        };
+
+        self.acceptors.insert(token, slot);
+        self.add_accepted_pipes(token, accepted);
+        Ok(AcceptorId(token))
+    }
+
+    /// Connects the transport to the address and opens the pipe.
+    pub fn connect(&mut self, addr: &str, pids: (u16, u16), recv_max_size: u64) -> io::Result<PipeId> {
+        let pipe = {
+            let dest = destination(addr, pids, recv_max_size, &self.buffer_pool);
+            try!(self.transport.connect(&dest))
+        };
+
+        Ok(self.add_pipe(pipe))
+    }
+
+    /// Address the acceptor is listening on, as reported by the acceptor.
+    pub fn local_addr(&self, id: AcceptorId) -> Option<String> {
+        self.acceptors.get(&id.0).and_then(|slot| slot.acceptor.as_ref()).and_then(|acceptor| acceptor.local_addr())
+    }
+
+    /// Pipes created by the acceptor so far.
+    pub fn accepted(&self, id: AcceptorId) -> &[PipeId] {
+        &self.acceptor_slot(id).accepted
+    }
+
+    /// Errors raised by the acceptor so far.
+    pub fn acceptor_errors(&self, id: AcceptorId) -> &[io::Error] {
+        &self.acceptor_slot(id).errors
+    }
+
+    /// Closes the acceptor and drops it.
+    pub fn close_acceptor(&mut self, id: AcceptorId) {
+        let slot = self.acceptors.get_mut(&id.0).expect("unknown acceptor");
+
+        if let Some(mut acceptor) = slot.acceptor.take() {
+            let mut ctx = AcceptorContext::new(&self.poll, id.0, &mut slot.errors);
+            acceptor.close(&mut ctx);
+        }
+    }
+
+    /// Starts sending a message on the pipe, which is not ready to send until it says so again.
+    pub fn send(&mut self, id: PipeId, msg: Message) {
+        self.apply_on_pipe(id, |pipe, ctx| {
+            *ctx.can_send = false;
+            pipe.send(ctx, Rc::new(msg))
+        })
+    }
+
+    /// Starts receiving a message on the pipe, which is not ready to recv until it says so again.
+    pub fn recv(&mut self, id: PipeId) {
+        self.apply_on_pipe(id, |pipe, ctx| {
+            *ctx.can_recv = false;
+            pipe.recv(ctx)
+        })
+    }
+
+    /// Closes the pipe and drops it, which releases the underlying connection.
+    pub fn close(&mut self, id: PipeId) {
+        self.apply_on_pipe(id, |pipe, ctx| pipe.close(ctx));
+        self.pipes.get_mut(&id.0).map(|slot| slot.pipe.take());
+    }
+
+    /// Events raised by the pipe so far.
+    pub fn events(&self, id: PipeId) -> &[pipe::Event] {
+        &self.pipe_slot(id).events
+    }
+
+    /// Whether the pipe has completed the handshake.
+    pub fn is_opened(&self, id: PipeId) -> bool {
+        self.events(id).iter().any(|evt| match *evt { pipe::Event::Opened => true, _ => false })
+    }
+
+    /// Whether the pipe raised an error or has been closed.
+    pub fn is_dead(&self, id: PipeId) -> bool {
+        self.events(id).iter().any(|evt| match *evt { pipe::Event::Error(_) | pipe::Event::Closed => true, _ => false })
+    }
+
+    /// First error raised by the pipe.
+    pub fn error(&self, id: PipeId) -> Option<&io::Error> {
+        self.events(id).iter().filter_map(|evt| match *evt { pipe::Event::Error(ref e) => Some(e), _ => None }).next()
+    }
+
+    /// Whether a message can be sent, according to the `CanSend` events and the sends since.
+    pub fn can_send(&self, id: PipeId) -> bool {
+        self.pipe_slot(id).can_send
+    }
+
+    /// Whether a message can be received, according to the `CanRecv` events and the recvs since.
+    pub fn can_recv(&self, id: PipeId) -> bool {
+        self.pipe_slot(id).can_recv
+    }
+
+    /// Number of messages the pipe reported as sent.
+    pub fn sent_count(&self, id: PipeId) -> usize {
+        self.events(id).iter().filter(|evt| match **evt { pipe::Event::Sent => true, _ => false }).count()
+    }
+
+    /// Messages received by the pipe so far.
+    pub fn received(&self, id: PipeId) -> Vec<&Message> {
+        self.events(id).iter().filter_map(|evt| match *evt { pipe::Event::Received(ref msg) => Some(msg), _ => None }).collect()
+    }
+
+    /// Waits for readiness events once, and dispatches them to the endpoints.
+    pub fn run_once(&mut self, timeout: Duration) -> io::Result<()> {
+        let mut events = Events::with_capacity(256);
+
+        try!(self.poll.poll(&mut events, Some(timeout)));
+
+        let ready: Vec<(usize, Ready)> = events.iter().map(|evt| (evt.token().0, evt.readiness())).collect();
+
+        for (token, readiness) in ready {
+            if self.pipes.contains_key(&token) {
+                self.apply_on_pipe(PipeId(token), |pipe, ctx| pipe.ready(ctx, readiness));
+            } else if self.acceptors.contains_key(&token) {
+                self.acceptor_ready(token, readiness);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs until the condition is met, returns false if it is still not met after the timeout.
+    pub fn run_until<F>(&mut self, timeout: Duration, condition: F) -> bool where F : Fn(&TransportHarness) -> bool {
+        let deadline = Instant::now() + timeout;
+
+        while !condition(self) {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            if self.run_once(deadline - now).is_err() {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    fn next_token(&mut self) -> usize {
+        let token = self.next_token;
+
+        self.next_token += 1;
+        token
+    }
+
+    fn add_pipe(&mut self, pipe: Box<Pipe>) -> PipeId {
+        let token = self.next_token();
+
+        self.pipes.insert(token, PipeSlot { pipe: Some(pipe), events: Vec::new(), can_send: false, can_recv: false });
+        self.apply_on_pipe(PipeId(token), |pipe, ctx| pipe.open(ctx));
+        PipeId(token)
+    }
+
+    fn add_accepted_pipes(&mut self, token: usize, pipes: Vec<Box<Pipe>>) {
+        for pipe in pipes {
+            let id = self.add_pipe(pipe);
+
+            self.acceptors.get_mut(&token).map(|slot| slot.accepted.push(id));
+        }
+    }
+
+    fn acceptor_ready(&mut self, token: usize, readiness: Ready) {
+        let accepted = {
+            let slot = self.acceptors.get_mut(&token).expect("unknown acceptor");
+            let mut ctx = AcceptorContext::new(&self.poll, token, &mut slot.errors);
+
+            if let Some(ref mut acceptor) = slot.acceptor {
+                acceptor.ready(&mut ctx, readiness);
+            }
+            ctx.accepted
+        };
+
+        self.add_accepted_pipes(token, accepted);
+    }
+
+    fn apply_on_pipe<F>(&mut self, id: PipeId, f: F) where F : FnOnce(&mut Box<Pipe>, &mut PipeContext) {
+        let slot = self.pipes.get_mut(&id.0).expect("unknown pipe");
+        let mut ctx = PipeContext {
+            poll: &self.poll,
+            token: id.0,
+            events: &mut slot.events,
+            can_send: &mut slot.can_send,
+            can_recv: &mut slot.can_recv
+        };
+
+        if let Some(ref mut pipe) = slot.pipe {
+            f(pipe, &mut ctx);
+        }
+    }
+
+    fn pipe_slot(&self, id: PipeId) -> &PipeSlot {
+        self.pipes.get(&id.0).expect("unknown pipe")
+    }
+
+    fn acceptor_slot(&self, id: AcceptorId) -> &AcceptorSlot {
+        self.acceptors.get(&id.0).expect("unknown acceptor")
+    }
+}
+
+fn destination<'a>(addr: &'a str, pids: (u16, u16), recv_max_size: u64, buffer_pool: &'a BufferPool) -> Destination<'a> {
+    Destination {
+        addr: addr,
+        pids: pids,
+        tcp_no_delay: false,
+        tcp_keepalive: None,
+        recv_max_size: recv_max_size,
+        buffer_pool: buffer_pool
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Contexts                                                                  */
+/*                                                                           */
+/*****************************************************************************/
+
+fn register(poll: &Poll, token: usize, io: &Evented, interest: Ready, opt: PollOpt) {
+    if let Err(e) = poll.register(io, Token(token), interest, opt) {
+        error!("[{}] register failed: {}", token, e);
+    }
+}
+
+fn reregister(poll: &Poll, token: usize, io: &Evented, interest: Ready, opt: PollOpt) {
+    if let Err(e) = poll.reregister(io, Token(token), interest, opt) {
+        error!("[{}] reregister failed: {}", token, e);
+    }
+}
+
+fn deregister(poll: &Poll, token: usize, io: &Evented) {
+    if let Err(e) = poll.deregister(io) {
+        error!("[{}] deregister failed: {}", token, e);
+    }
+}
+
+struct PipeContext<'a> {
+    poll: &'a Poll,
+    token: usize,
+    events: &'a mut Vec<pipe::Event>,
+    can_send: &'a mut bool,
+    can_recv: &'a mut bool
+}
+
+impl<'a> fmt::Debug for PipeContext<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TransportHarness pipe {}", self.token)
+    }
+}
+
+impl<'a> EndpointRegistrar for PipeContext<'a> {
+    fn register(&mut self, io: &Evented, interest: Ready, opt: PollOpt) {
+        register(self.poll, self.token, io, interest, opt)
+    }
+    fn reregister(&mut self, io: &Evented, interest: Ready, opt: PollOpt) {
+        reregister(self.poll, self.token, io, interest, opt)
+    }
+    fn deregister(&mut self, io: &Evented) {
+        deregister(self.poll, self.token, io)
+    }
+}
+
+impl<'a> pipe::Context for PipeContext<'a> {
+    fn raise(&mut self, evt: pipe::Event) {
+        match evt {
+            pipe::Event::CanSend(x) => *self.can_send = x,
+            pipe::Event::CanRecv(x) => *self.can_recv = x,
+            _ => {}
+        }
+        self.events.push(evt)
+    }
+}
+
+struct AcceptorContext<'a> {
+    poll: &'a Poll,
+    token: usize,
+    errors: &'a mut Vec<io::Error>,
+    accepted: Vec<Box<Pipe>>
+}
+
+impl<'a> AcceptorContext<'a> {
+    fn new(poll: &'a Poll, token: usize, errors: &'a mut Vec<io::Error>) -> AcceptorContext<'a> {
+        AcceptorContext {
+            poll: poll,
+            token: token,
+            errors: errors,
+            accepted: Vec::new()
+        }
+    }
+}
+
+impl<'a> EndpointRegistrar for AcceptorContext<'a> {
+    fn register(&mut self, io: &Evented, interest: Ready, opt: PollOpt) {
+        register(self.poll, self.token, io, interest, opt)
+    }
+    fn reregister(&mut self, io: &Evented, interest: Ready, opt: PollOpt) {
+        reregister(self.poll, self.token, io, interest, opt)
+    }
+    fn deregister(&mut self, io: &Evented) {
+        deregister(self.poll, self.token, io)
+    }
+}
+
+impl<'a> acceptor::Context for AcceptorContext<'a> {
+    fn raise(&mut self, evt: acceptor::Event) {
+        match evt {
+            acceptor::Event::Accepted(pipes) => self.accepted.extend(pipes),
+            acceptor::Event::Error(e) => self.errors.push(e),
+            _ => {}
+        }
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Checks                                                                    */
+/*                                                                           */
+/*****************************************************************************/
+
+/// Runs all the checks, each one with a new transport and a new address.
+pub fn check_all<T, F, A>(make_transport: F, mut next_addr: A) where
+    T : Transport + 'static,
+    F : Fn() -> T,
+    A : FnMut() -> String
+{
+    check_connect_and_bind(make_transport(), &next_addr());
+    check_send_and_recv(make_transport(), &next_addr());
+    check_handshake_mismatch(make_transport(), &next_addr());
+    check_partial_reads_and_writes(make_transport(), &next_addr());
+    check_recv_max_size(make_transport(), &next_addr());
+    check_peer_close_mid_message(make_transport(), &next_addr());
+    check_accept_burst(make_transport(), &next_addr());
+}
+
+fn start<T : Transport + 'static>(transport: T, addr: &str, pids: (u16, u16), recv_max_size: u64) -> (TransportHarness, AcceptorId, String) {
+    let mut harness = TransportHarness::new(transport).expect("failed to create harness");
+    let acceptor = harness.bind(addr, pids, recv_max_size).expect("bind failed");
+    let local_addr = harness.local_addr(acceptor).unwrap_or_else(|| addr.to_owned());
+
+    (harness, acceptor, local_addr)
+}
+
+fn open_pair<T : Transport + 'static>(transport: T, addr: &str, recv_max_size: u64) -> (TransportHarness, PipeId, PipeId) {
+    let (mut harness, acceptor, local_addr) = start(transport, addr, PIDS, recv_max_size);
+    let client = harness.connect(&local_addr, PIDS, recv_max_size).expect("connect failed");
+    let opened = harness.run_until(CHECK_TIMEOUT, |h| {
+        h.accepted(acceptor).len() == 1 && h.is_opened(h.accepted(acceptor)[0]) && h.is_opened(client)
+    });
+
+    assert!(opened, "connection was not established");
+
+    let server = harness.accepted(acceptor)[0];
+    (harness, client, server)
+}
+
+fn transfer(harness: &mut TransportHarness, from: PipeId, to: PipeId, msg: Message) -> Vec<u8> {
+    assert!(harness.run_until(CHECK_TIMEOUT, |h| h.can_send(from)), "pipe did not become ready to send");
+    harness.send(from, msg);
+    assert!(harness.run_until(CHECK_TIMEOUT, |h| h.can_recv(to) || h.received(to).len() > 0), "pipe did not become ready to recv");
+
+    let received_before = harness.received(to).len();
+    harness.recv(to);
+    let completed = harness.run_until(CHECK_TIMEOUT, |h| h.received(to).len() > received_before && h.sent_count(from) > 0);
+
+    assert!(completed, "message was not transferred, errors: {:?} / {:?}", harness.error(from), harness.error(to));
+    harness.received(to)[received_before].get_body().to_vec()
+}
+
+/// The acceptor reports its address, and a connection to it completes the handshake on both sides.
+pub fn check_connect_and_bind<T : Transport + 'static>(transport: T, addr: &str) {
+    let (harness, client, server) = open_pair(transport, addr, 1024);
+
+    assert!(harness.error(client).is_none(), "client pipe raised an error");
+    assert!(harness.error(server).is_none(), "server pipe raised an error");
+}
+
+/// A message, header included, is delivered in both directions, and so is an empty one.
+pub fn check_send_and_recv<T : Transport + 'static>(transport: T, addr: &str) {
+    let (mut harness, client, server) = open_pair(transport, addr, 1024);
+    let request = transfer(&mut harness, client, server, Message::from_header_and_body(vec![1, 2], vec![65, 66, 67]));
+
+    assert_eq!(vec![1, 2, 65, 66, 67], request);
+
+    let reply = transfer(&mut harness, server, client, Message::from_body(vec![67, 66, 65]));
+
+    assert_eq!(vec![67, 66, 65], reply);
+
+    let empty = transfer(&mut harness, client, server, Message::new());
+
+    assert!(empty.is_empty(), "empty message was received with content");
+}
+
+/// Peers with incompatible protocols never open the connection.
+pub fn check_handshake_mismatch<T : Transport + 'static>(transport: T, addr: &str) {
+    let (mut harness, acceptor, local_addr) = start(transport, addr, (80, 81), 1024);
+    let client = harness.connect(&local_addr, (80, 81), 1024).expect("connect failed");
+    let rejected = harness.run_until(CHECK_TIMEOUT, |h| {
+        h.is_dead(client) && h.accepted(acceptor).iter().all(|id| h.is_dead(*id))
+    });
+
+    assert!(rejected, "mismatched handshake did not fail");
+    assert!(!harness.is_opened(client), "client pipe was opened despite the mismatch");
+    for id in harness.accepted(acceptor) {
+        assert!(!harness.is_opened(*id), "server pipe was opened despite the mismatch");
+    }
+}
+
+/// A message much larger than the system buffers is written and read in several steps.
+pub fn check_partial_reads_and_writes<T : Transport + 'static>(transport: T, addr: &str) {
+    let (mut harness, client, server) = open_pair(transport, addr, BIG_MSG_LEN as u64);
+    let body: Vec<u8> = (0..BIG_MSG_LEN).map(|i| i as u8).collect();
+    let received = transfer(&mut harness, client, server, Message::from_body(body.clone()));
+
+    assert!(received == body, "large message was corrupted");
+}
+
+/// A message larger than the receiver `recv_max_size` is rejected with an error.
+pub fn check_recv_max_size<T : Transport + 'static>(transport: T, addr: &str) {
+    let (mut harness, client, server) = open_pair(transport, addr, 1024);
+
+    assert!(harness.run_until(CHECK_TIMEOUT, |h| h.can_send(client)), "pipe did not become ready to send");
+    harness.send(client, Message::from_body(vec![0; 4096]));
+    assert!(harness.run_until(CHECK_TIMEOUT, |h| h.can_recv(server)), "pipe did not become ready to recv");
+    harness.recv(server);
+
+    let rejected = harness.run_until(CHECK_TIMEOUT, |h| h.error(server).is_some() || h.received(server).len() > 0);
+
+    assert!(rejected, "oversized message neither received nor rejected");
+    assert!(harness.received(server).is_empty(), "oversized message was received");
+}
+
+/// A peer disconnecting while a message is being transferred causes an error, not a truncated message.
+pub fn check_peer_close_mid_message<T : Transport + 'static>(transport: T, addr: &str) {
+    let (mut harness, client, server) = open_pair(transport, addr, BIG_MSG_LEN as u64);
+
+    assert!(harness.run_until(CHECK_TIMEOUT, |h| h.can_send(client)), "pipe did not become ready to send");
+    harness.send(client, Message::from_body(vec![0; BIG_MSG_LEN]));
+    assert!(harness.sent_count(client) == 0, "message is too small to be interrupted");
+    harness.close(client);
+
+    assert!(harness.run_until(CHECK_TIMEOUT, |h| h.can_recv(server) || h.is_dead(server)), "pipe did not become ready to recv");
+    if !harness.is_dead(server) {
+        harness.recv(server);
+    }
+
+    let failed = harness.run_until(CHECK_TIMEOUT, |h| h.is_dead(server) || h.received(server).len() > 0);
+
+    assert!(failed, "interrupted message neither received nor failed");
+    assert!(harness.received(server).is_empty(), "interrupted message was received");
+}
+
+/// Many simultaneous connections are all accepted and opened.
+pub fn check_accept_burst<T : Transport + 'static>(transport: T, addr: &str) {
+    let count = 32;
+    let (mut harness, acceptor, local_addr) = start(transport, addr, PIDS, 1024);
+    let clients: Vec<PipeId> = (0..count).map(|_| harness.connect(&local_addr, PIDS, 1024).expect("connect failed")).collect();
+    let opened = harness.run_until(CHECK_TIMEOUT, |h| {
+        h.accepted(acceptor).len() == count &&
+        h.accepted(acceptor).iter().all(|id| h.is_opened(*id)) &&
+        clients.iter().all(|id| h.is_opened(*id))
+    });
+
+    assert!(opened, "{} connections out of {} were accepted", harness.accepted(acceptor).len(), count);
+    assert!(harness.acceptor_errors(acceptor).is_empty(), "acceptor raised errors");
+}
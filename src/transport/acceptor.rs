@@ -13,7 +13,9 @@ use transport::pipe::Pipe;
 
 pub enum Command {
     Open,
-    Close
+    Close,
+    Pause,
+    Resume
 }
 
 pub enum Event {
@@ -27,8 +29,46 @@ pub trait Acceptor {
     fn ready(&mut self, ctx: &mut Context, events: Ready);
     fn open(&mut self, ctx: &mut Context);
     fn close(&mut self, ctx: &mut Context);
+
+    /// Stops accepting connections without closing the listener,
+    /// incoming connections are left waiting in the OS backlog.
+    fn pause(&mut self, _: &mut Context) {}
+
+    /// Starts accepting connections again after a call to `pause`.
+    fn resume(&mut self, _: &mut Context) {}
+
+    /// Address the acceptor is actually listening on, in the transport's own format.
+    /// When binding to an ephemeral port, this is where the assigned port can be found.
+    fn local_addr(&self) -> Option<String> { None }
 }
 
 pub trait Context : EndpointRegistrar {
     fn raise(&mut self, evt: Event);
 }
+
+/// Tells whether an accept error is caused by a lack of resources, like file descriptors,
+/// that may be released later. In that case the listener is still usable and accepting
+/// should be retried after a while instead of closing it.
+#[cfg(unix)]
+pub fn is_transient_error(err: &io::Error) -> bool {
+    use libc;
+
+    match err.raw_os_error() {
+        Some(libc::EMFILE) | Some(libc::ENFILE) | Some(libc::ENOBUFS) | Some(libc::ENOMEM) => true,
+        _ => false
+    }
+}
+
+/// Tells whether an accept error is caused by a lack of resources, like socket handles,
+/// that may be released later. In that case the listener is still usable and accepting
+/// should be retried after a while instead of closing it.
+#[cfg(windows)]
+pub fn is_transient_error(err: &io::Error) -> bool {
+    const WSAEMFILE: i32 = 10024;
+    const WSAENOBUFS: i32 = 10055;
+
+    match err.raw_os_error() {
+        Some(WSAEMFILE) | Some(WSAENOBUFS) => true,
+        _ => false
+    }
+}
@@ -21,10 +21,15 @@ use io_error::*;
 // because while an operation is in progress, must be finished
 // before being able to start another one. 
 
+// The stream is registered level-triggered, but only for the readiness that can change
+// the state of the pipe: once the pipe is known to be able to send or recv,
+// polling the same readiness again would keep the event loop busy until the protocol uses it.
+
 pub struct Active<S> {
     stub: S,
     can_send_msg: bool,
-    can_recv_msg: bool
+    can_recv_msg: bool,
+    registered: Ready
 }
 
 impl<S : AsyncPipeStub> Active<S> {
@@ -32,7 +37,36 @@ impl<S : AsyncPipeStub> Active<S> {
         Active {
             stub: s,
             can_send_msg: false,
-            can_recv_msg: false
+            can_recv_msg: false,
+            registered: all_interest()
+        }
+    }
+    fn interest(&self) -> Ready {
+        let mut interest = Ready::from(UnixReady::hup() | UnixReady::error());
+
+        if self.can_recv_msg == false || self.stub.has_pending_recv() {
+            interest.insert(Ready::readable());
+        }
+        if self.can_send_msg == false || self.stub.has_pending_send() {
+            interest.insert(Ready::writable());
+        }
+
+        interest
+    }
+    // When the readiness is not polled, its absence from the events tells nothing,
+    // only an error means the pipe can no longer be used.
+    fn is_known_not_writable(&self, events: Ready) -> bool {
+        self.registered.is_writable() || UnixReady::from(events).is_error()
+    }
+    fn is_known_not_readable(&self, events: Ready) -> bool {
+        self.registered.is_readable() || UnixReady::from(events).is_error()
+    }
+    fn resync_interest(&mut self, ctx: &mut Context) {
+        let interest = self.interest();
+
+        if interest != self.registered {
+            self.registered = interest;
+            ctx.reregister(self.stub.deref(), interest, PollOpt::level());
         }
     }
     fn on_send_progress(&mut self, ctx: &mut Context, progress: Result<bool>) -> Result<()> {
@@ -43,7 +77,10 @@ impl<S : AsyncPipeStub> Active<S> {
     }
     fn writable_changed(&mut self, ctx: &mut Context, events: Ready) -> Result<()> {
         if events.is_writable() == false {
-            return Ok(self.change_can_send(ctx, false));
+            if self.is_known_not_writable(events) {
+                self.change_can_send(ctx, false);
+            }
+            return Ok(());
         }
 
         if self.stub.has_pending_send() {
@@ -72,7 +109,10 @@ impl<S : AsyncPipeStub> Active<S> {
     }
     fn readable_changed(&mut self, ctx: &mut Context, events: Ready) -> Result<()> {
         if events.is_readable() == false {
-            return Ok(self.change_can_recv(ctx, false));
+            if self.is_known_not_readable(events) {
+                self.change_can_recv(ctx, false);
+            }
+            return Ok(());
         }
 
         if self.stub.has_pending_recv() {
@@ -93,6 +133,14 @@ impl<S : AsyncPipeStub> Active<S> {
         }
     }
 
+    // Some streams cannot have their hang up reported by the poll, only that they are readable.
+    fn with_peer_hang_up(&self, events: Ready) -> Ready {
+        if events.is_readable() && self.stub.is_closed_by_peer() {
+            events | Ready::from(UnixReady::hup())
+        } else {
+            events
+        }
+    }
     fn hang_up_changed(&mut self, hup: bool) -> Result<()> {
         if hup {
             self.can_send_msg = false;
@@ -104,7 +152,7 @@ impl<S : AsyncPipeStub> Active<S> {
     }
 }
 
-fn interest() -> Ready {
+fn all_interest() -> Ready {
     let interest = Ready::readable() | Ready::writable();
     let unix_interest = UnixReady::from(interest) | UnixReady::hup() | UnixReady::error();
 
@@ -116,7 +164,8 @@ impl<S : AsyncPipeStub + 'static> PipeState<S> for Active<S> {
     fn name(&self) -> &'static str {"Active"}
 
     fn enter(&mut self, ctx: &mut Context) {
-        ctx.reregister(self.stub.deref(), interest(), PollOpt::level());
+        self.registered = self.interest();
+        ctx.reregister(self.stub.deref(), self.registered, PollOpt::level());
         ctx.raise(Event::Opened);
     }
     fn close(self: Box<Self>, ctx: &mut Context) -> Box<PipeState<S>> {
@@ -128,7 +177,7 @@ impl<S : AsyncPipeStub + 'static> PipeState<S> for Active<S> {
         self.can_send_msg = false;
 
         let progress = self.stub.start_send(msg);
-        let res = self.on_send_progress(ctx, progress);
+        let res = self.on_send_progress(ctx, progress).map(|_| self.resync_interest(ctx));
 
         no_transition_if_ok(self, ctx, res)
     }
@@ -136,16 +185,17 @@ impl<S : AsyncPipeStub + 'static> PipeState<S> for Active<S> {
         self.can_recv_msg = false;
 
         let progress = self.stub.start_recv();
-        let res = self.on_recv_progress(ctx, progress);
+        let res = self.on_recv_progress(ctx, progress).map(|_| self.resync_interest(ctx));
 
         no_transition_if_ok(self, ctx, res)
     }
     fn ready(mut self: Box<Self>, ctx: &mut Context, events: Ready) -> Box<PipeState<S>> {
+        let events = self.with_peer_hang_up(events);
         let res = 
             self.readable_changed(ctx, events).and_then(|_|
             self.writable_changed(ctx, events).and_then(|_| 
             self.hang_up_changed(UnixReady::from(events).is_hup()))
-        );
+        ).map(|_| self.resync_interest(ctx));
 
         no_transition_if_ok(self, ctx, res)
     }
@@ -181,7 +231,7 @@ mod tests {
         assert_eq!(0, ctx.get_deregistrations());
 
         let (ref interest, ref poll_opt) = ctx.get_reregistrations()[0];
-        let all = super::interest();
+        let all = super::all_interest();
         let level = mio::PollOpt::level();
 
         assert_eq!(&all, interest);
@@ -422,4 +472,78 @@ mod tests {
 
         assert!(is_error);
     }
+
+    #[test]
+    fn when_readable_and_closed_by_peer_should_transition_to_dead() {
+        let sensor_srv = TestStepStreamSensor::new();
+        let sensor = Rc::new(RefCell::new(sensor_srv));
+        let stub = TestStepStream::with_sensor(sensor.clone());
+        let state = Box::new(Active::new(stub));
+        let mut ctx = TestPipeContext::new();
+
+        sensor.borrow_mut().set_closed_by_peer(true);
+
+        let new_state = state.ready(&mut ctx, mio::Ready::readable());
+        assert_eq!("Dead", new_state.name());
+        assert_eq!(1, ctx.get_raised_events().len());
+
+        let evt = &ctx.get_raised_events()[0];
+        let is_error = match *evt {
+            pipe::Event::Error(_) => true,
+            _ => false,
+        };
+
+        assert!(is_error);
+    }
+
+    #[test]
+    fn readiness_is_not_polled_once_known() {
+        let stub = TestStepStream::new();
+        let mut state = Box::new(Active::new(stub));
+        let mut ctx = TestPipeContext::new();
+        let error = UnixReady::hup() | UnixReady::error();
+
+        state.enter(&mut ctx);
+        let new_state = state.ready(&mut ctx, mio::Ready::writable());
+        assert_eq!(2, ctx.get_reregistrations().len());
+        assert_eq!(mio::Ready::readable() | mio::Ready::from(error), ctx.get_reregistrations()[1].0);
+
+        let new_state = new_state.ready(&mut ctx, mio::Ready::readable());
+        assert_eq!(3, ctx.get_reregistrations().len());
+        assert_eq!(mio::Ready::from(error), ctx.get_reregistrations()[2].0);
+
+        let msg = Rc::new(Message::from_body(vec!(66, 65, 67)));
+        let new_state = new_state.send(&mut ctx, msg);
+        assert_eq!("Active", new_state.name());
+        assert_eq!(4, ctx.get_reregistrations().len());
+        assert_eq!(mio::Ready::writable() | mio::Ready::from(error), ctx.get_reregistrations()[3].0);
+    }
+
+    #[test]
+    fn readiness_loss_is_raised_even_when_not_polled() {
+        let stub = TestStepStream::new();
+        let mut state = Box::new(Active::new(stub));
+        let mut ctx = TestPipeContext::new();
+
+        state.enter(&mut ctx);
+        let new_state = state.ready(&mut ctx, mio::Ready::readable() | mio::Ready::writable());
+        assert_eq!(mio::Ready::from(UnixReady::hup() | UnixReady::error()), ctx.get_reregistrations()[1].0);
+        assert_eq!(3, ctx.get_raised_events().len());
+
+        let new_state = new_state.ready(&mut ctx, mio::Ready::from(UnixReady::error()));
+        assert_eq!("Active", new_state.name());
+        assert_eq!(5, ctx.get_raised_events().len());
+
+        let lost_recv = match ctx.get_raised_events()[3] {
+            pipe::Event::CanRecv(x) => x == false,
+            _ => false,
+        };
+        let lost_send = match ctx.get_raised_events()[4] {
+            pipe::Event::CanSend(x) => x == false,
+            _ => false,
+        };
+
+        assert!(lost_recv);
+        assert!(lost_send);
+    }
 }
\ No newline at end of file
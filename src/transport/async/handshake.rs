@@ -4,7 +4,7 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
-use std::io::Result;
+use std::io::{Result, ErrorKind};
 
 use mio::{Ready, PollOpt};
 
@@ -12,7 +12,7 @@ use transport::async::stub::*;
 use transport::async::state::*;
 use transport::async::active::Active; 
 use transport::async::dead::Dead; 
-use transport::pipe::Context;
+use transport::pipe::{Event, Context};
 
 pub struct HandshakeTx<S : AsyncPipeStub + 'static> {
     stub: S,
@@ -97,6 +97,7 @@ impl<S : AsyncPipeStub + 'static> PipeState<S> for HandshakeRx<S> {
 
     fn enter(&mut self, ctx: &mut Context) {
         ctx.reregister(self.stub.deref(), Ready::readable(), PollOpt::level());
+        ctx.raise(Event::Connected);
     }
     fn close(self: Box<Self>, ctx: &mut Context) -> Box<PipeState<S>> {
         ctx.deregister(self.stub.deref());
@@ -105,9 +106,10 @@ impl<S : AsyncPipeStub + 'static> PipeState<S> for HandshakeRx<S> {
     }
     fn ready(mut self: Box<Self>, ctx: &mut Context, events: Ready) -> Box<PipeState<S>> {
         if events.is_readable() {
-            let res = self.recv_handshake();
-            
-            transition_if_ok::<HandshakeRx<S>, Active<S>, S>(self, ctx, res)
+            match self.recv_handshake() {
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => self,
+                res => transition_if_ok::<HandshakeRx<S>, Active<S>, S>(self, ctx, res)
+            }
         } else {
             self
         }
@@ -121,6 +123,7 @@ mod tests {
 
     use mio;
 
+    use transport::pipe;
     use transport::tests::*;
     use transport::async::state::*;
     use transport::async::tests::*;
@@ -195,6 +198,12 @@ mod tests {
 
         assert_eq!(&all, interest);
         assert_eq!(&edge, poll_opt);
+
+        assert_eq!(1, ctx.get_raised_events().len());
+        match ctx.get_raised_events()[0] {
+            pipe::Event::Connected => {},
+            _ => panic!("entering rx should raise Connected")
+        }
     }
 
     #[test]
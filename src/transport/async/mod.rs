@@ -20,24 +20,37 @@ use std::rc::Rc;
 
 use mio;
 
-use core::Message;
+use core::{Message, PeerCred};
 use transport::*;
 use transport::pipe::*;
 
 use self::stub::AsyncPipeStub;
 use self::state::PipeState;
 
+/// Pipe performing the handshake, then exchanging messages through the stub, until an error occurs.
 pub struct AsyncPipe<S : AsyncPipeStub + 'static> {
 
-    state: Option<Box<PipeState<S>>>
+    state: Option<Box<PipeState<S>>>,
+    local_addr: Option<String>,
+    peer_addr: Option<String>,
+    peer_cred: Option<PeerCred>
 
 }
 
 impl<S : AsyncPipeStub + 'static> AsyncPipe<S> {
+    /// Creates a pipe around a stub, `pids` are the protocol ids found in the destination.
     pub fn new(stub: S, pids: (u16, u16)) -> AsyncPipe<S> {
+        let local_addr = stub.local_addr();
+        let peer_addr = stub.peer_addr();
+        let peer_cred = stub.peer_cred();
         let initial_state = Box::new(initial::Initial::new(stub, pids));
 
-        AsyncPipe { state: Some(initial_state) }
+        AsyncPipe {
+            state: Some(initial_state),
+            local_addr: local_addr,
+            peer_addr: peer_addr,
+            peer_cred: peer_cred
+        }
     }
 
     fn apply<F>(&mut self, ctx: &mut Context, transition: F) where F : FnOnce(Box<PipeState<S>>, &mut Context) -> Box<PipeState<S>> {
@@ -74,4 +87,16 @@ impl<S : AsyncPipeStub> pipe::Pipe for AsyncPipe<S> {
     fn recv(&mut self, ctx: &mut Context) {
         self.apply(ctx, |s, ctx| s.recv(ctx))
     }
+
+    fn local_addr(&self) -> Option<String> {
+        self.local_addr.clone()
+    }
+
+    fn peer_addr(&self) -> Option<String> {
+        self.peer_addr.clone()
+    }
+
+    fn peer_cred(&self) -> Option<PeerCred> {
+        self.peer_cred
+    }
 }
@@ -6,39 +6,64 @@
 
 use std::ops::Deref;
 use std::rc::Rc;
-use std::io::{Result, Read, Write, ErrorKind};
+use std::io::{Result, Read, Write, Error, ErrorKind};
 
 use byteorder::{ BigEndian, ByteOrder };
 
 use mio::Evented;
+use mio::tcp::TcpStream;
+#[cfg(unix)]
+use mio_uds::UnixStream;
+use iovec::IoVec;
 
-use core::Message;
+use core::{Message, PeerCred};
+use proto::ProtocolMismatch;
 use io_error::*;
 
+/// Non-blocking framed connection driven by an [`AsyncPipe`](../struct.AsyncPipe.html).
+/// The `Evented` target is registered in the event loop by the pipe.
 pub trait AsyncPipeStub : Sender + Receiver + Handshake + Deref<Target=Evented> {
     #[cfg(windows)]
     fn read_and_write_void(&mut self);
     #[cfg(windows)]
     fn registered(&mut self) {}
+    fn local_addr(&self) -> Option<String> { None }
+    fn peer_addr(&self) -> Option<String> { None }
+    fn peer_cred(&self) -> Option<PeerCred> { None }
 }
 
+/// Writing side of a stub.
 pub trait Sender {
+    /// Starts writing a message, returns whether it has been fully written.
     fn start_send(&mut self, msg: Rc<Message>) -> Result<bool>;
+    /// Continues writing the pending message when the stream is writable again.
     fn resume_send(&mut self) -> Result<bool>;
+    /// Whether a message has been partially written.
     fn has_pending_send(&self) -> bool;
 }
 
+/// Reading side of a stub.
 pub trait Receiver {
+    /// Starts reading a message, returns it if it could be fully read.
     fn start_recv(&mut self) -> Result<Option<Message>>;
+    /// Continues reading the pending message when the stream is readable again.
     fn resume_recv(&mut self) -> Result<Option<Message>>;
+    /// Whether a message has been partially read.
     fn has_pending_recv(&self) -> bool;
+    /// Whether the peer closed the stream and everything it sent was read,
+    /// for streams whose hang up is not reported by the poll.
+    fn is_closed_by_peer(&self) -> bool { false }
 }
 
+/// Exchange of the protocol ids when the connection is established.
 pub trait Handshake {
+    /// Sends the local protocol id.
     fn send_handshake(&mut self, pids: (u16, u16)) -> Result<()>;
+    /// Receives the remote protocol id and checks it is the expected peer protocol.
     fn recv_handshake(&mut self, pids: (u16, u16)) -> Result<()>;
 }
 
+/// Sends the standard 8 bytes handshake.
 pub fn send_and_check_handshake<T:Write>(stream: &mut T, pids: (u16, u16)) -> Result<()> {
     let (proto_id, _) = pids;
     let handshake = create_handshake(proto_id);
@@ -56,10 +81,25 @@ fn create_handshake(protocol_id: u16) -> [u8; 8] {
     handshake
 }
 
-pub fn recv_and_check_handshake<T:Read>(stream: &mut T, pids: (u16, u16)) -> Result<()> {
-    let mut handshake = [0u8; 8];
+/// Bytes of the standard handshake received so far.
+#[derive(Default)]
+pub struct HandshakeBuffer {
+    bytes: [u8; 8],
+    read: usize
+}
+
+/// Receives the standard 8 bytes handshake and checks the protocol id.
+/// The handshake may be split across several reads, the bytes received so far are kept
+/// in `received` and a `WouldBlock` error is returned until the handshake is complete.
+pub fn recv_and_check_handshake<T:Read>(stream: &mut T, pids: (u16, u16), received: &mut HandshakeBuffer) -> Result<()> {
+    while received.read < 8 {
+        match try!(stream.read_buffer(&mut received.bytes[received.read..])) {
+            0 => return Err(would_block_io_error("handshake is not complete")),
+            n => received.read += n
+        }
+    }
 
-    stream.read(&mut handshake).and_then(|_| check_handshake(pids, &handshake))
+    check_handshake(pids, &received.bytes)
 }
 
 fn check_handshake(pids: (u16, u16), handshake: &[u8; 8]) -> Result<()> {
@@ -67,7 +107,17 @@ fn check_handshake(pids: (u16, u16), handshake: &[u8; 8]) -> Result<()> {
     let expected_handshake = create_handshake(proto_id);
 
     if handshake == &expected_handshake {
-        Ok(())
+        return Ok(());
+    }
+
+    // Only the protocol id differs, the peer speaks SP but not the right protocol
+    if handshake[..4] == expected_handshake[..4] && handshake[6..] == expected_handshake[6..] {
+        let mismatch = ProtocolMismatch {
+            expected: proto_id,
+            received: BigEndian::read_u16(&handshake[4..6])
+        };
+
+        Err(Error::from(mismatch))
     } else {
         Err(invalid_data_io_error("received bad handshake"))
     }
@@ -97,6 +147,25 @@ impl<T:Write> WriteBuffer for T {
 }
 
 
+/// Gathering write, sending several buffers with a single system call.
+pub trait WriteBufs {
+    /// Writes the buffers in order, returns the number of bytes written.
+    fn write_bufs(&mut self, bufs: &[&IoVec]) -> Result<usize>;
+}
+
+impl WriteBufs for TcpStream {
+    fn write_bufs(&mut self, bufs: &[&IoVec]) -> Result<usize> {
+        TcpStream::write_bufs(self, bufs)
+    }
+}
+
+#[cfg(unix)]
+impl WriteBufs for UnixStream {
+    fn write_bufs(&mut self, bufs: &[&IoVec]) -> Result<usize> {
+        UnixStream::write_bufs(self, bufs)
+    }
+}
+
 pub trait ReadBuffer {
     fn read_buffer(&mut self, buffer: &mut [u8]) -> Result<usize>;
 }
@@ -104,6 +173,9 @@ pub trait ReadBuffer {
 impl<T:Read> ReadBuffer for T {
     fn read_buffer(&mut self, buf: &mut [u8]) -> Result<usize> {
         match self.read(buf) {
+            Ok(0) if !buf.is_empty() => {
+                Err(Error::new(ErrorKind::UnexpectedEof, "connection closed by peer"))
+            },
             Ok(x) => {
                 Ok(x)
             },
@@ -117,3 +189,86 @@ impl<T:Read> ReadBuffer for T {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+
+    // Reads the chunks one by one, reporting `WouldBlock` when they are exhausted.
+    struct Chunks(Vec<Vec<u8>>);
+
+    impl Read for Chunks {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            if self.0.is_empty() {
+                return Err(Error::new(ErrorKind::WouldBlock, "try later"));
+            }
+
+            let chunk = self.0.remove(0);
+            buf[..chunk.len()].copy_from_slice(&chunk);
+            Ok(chunk.len())
+        }
+    }
+
+    #[test]
+    fn recv_handshake_split_across_reads() {
+        let mut stream = Chunks(vec![vec![0, 83, 80], vec![0, 0], vec![17, 0, 0]]);
+        let mut received = HandshakeBuffer::default();
+
+        assert!(recv_and_check_handshake(&mut stream, (16, 17), &mut received).is_ok());
+    }
+
+    #[test]
+    fn recv_handshake_of_another_protocol_reports_both_ids() {
+        let mut stream = Chunks(vec![vec![0, 83, 80, 0, 0, 33, 0, 0]]);
+        let mut received = HandshakeBuffer::default();
+        let err = recv_and_check_handshake(&mut stream, (80, 81), &mut received).unwrap_err();
+        let mismatch = ProtocolMismatch::from_io_error(&err).expect("error should be a protocol mismatch");
+
+        assert_eq!(ErrorKind::InvalidData, err.kind());
+        assert_eq!(81, mismatch.expected);
+        assert_eq!(33, mismatch.received);
+        assert_eq!("protocol mismatch: expected a Pull peer (81) but received Sub (33)", err.to_string());
+    }
+
+    #[test]
+    fn recv_garbage_handshake_is_not_a_protocol_mismatch() {
+        let mut stream = Chunks(vec![vec![1, 2, 3, 4, 0, 81, 0, 0]]);
+        let mut received = HandshakeBuffer::default();
+        let err = recv_and_check_handshake(&mut stream, (80, 81), &mut received).unwrap_err();
+
+        assert_eq!(ErrorKind::InvalidData, err.kind());
+        assert!(ProtocolMismatch::from_io_error(&err).is_none());
+    }
+
+    #[test]
+    fn recv_handshake_waits_for_missing_bytes() {
+        let mut stream = Chunks(vec![vec![0, 83, 80, 0]]);
+        let mut received = HandshakeBuffer::default();
+        let err = recv_and_check_handshake(&mut stream, (16, 17), &mut received).unwrap_err();
+
+        assert_eq!(ErrorKind::WouldBlock, err.kind());
+
+        stream.0.push(vec![0, 17, 0, 0]);
+        assert!(recv_and_check_handshake(&mut stream, (16, 17), &mut received).is_ok());
+    }
+
+    #[test]
+    fn recv_handshake_of_wrong_protocol() {
+        let mut stream = Chunks(vec![vec![0, 83, 80, 0, 0, 16, 0, 0]]);
+        let mut received = HandshakeBuffer::default();
+        let err = recv_and_check_handshake(&mut stream, (16, 17), &mut received).unwrap_err();
+
+        assert_eq!(ErrorKind::InvalidData, err.kind());
+    }
+
+    #[test]
+    fn recv_handshake_from_closed_stream() {
+        let mut stream = io::Cursor::new(vec![0, 83, 80]);
+        let mut received = HandshakeBuffer::default();
+        let err = recv_and_check_handshake(&mut stream, (16, 17), &mut received).unwrap_err();
+
+        assert_eq!(ErrorKind::UnexpectedEof, err.kind());
+    }
+}
@@ -21,7 +21,8 @@ pub struct TestStepStreamSensor {
     start_send_result: Option<bool>,
     resume_send_result: Option<bool>,
     start_recv_result: Option<Message>,
-    resume_recv_result: Option<Message>
+    resume_recv_result: Option<Message>,
+    closed_by_peer: bool
 }
 
 impl TestStepStreamSensor {
@@ -32,7 +33,8 @@ impl TestStepStreamSensor {
             start_send_result: Some(true),
             resume_send_result: None,
             start_recv_result: None,
-            resume_recv_result: None
+            resume_recv_result: None,
+            closed_by_peer: false
         }
     }
 
@@ -83,6 +85,10 @@ impl TestStepStreamSensor {
     pub fn set_resume_recv_result(&mut self, res: Option<Message>) {
         self.resume_recv_result = res;
     }
+
+    pub fn set_closed_by_peer(&mut self, closed_by_peer: bool) {
+        self.closed_by_peer = closed_by_peer;
+    }
 }
 
 pub struct TestStepStream {
@@ -184,4 +190,8 @@ impl stub::Receiver for TestStepStream {
     fn has_pending_recv(&self) -> bool {
         self.pending_recv
     }
+
+    fn is_closed_by_peer(&self) -> bool {
+        self.sensor.borrow().closed_by_peer
+    }
 }
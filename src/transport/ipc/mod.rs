@@ -5,8 +5,8 @@
 // This file may not be copied, modified, or distributed except according to those terms.
 
 
-mod send;
-mod recv;
+#[doc(hidden)]
+pub mod recv;
 
 #[cfg(unix)] pub use self::unix::Ipc;
 #[cfg(unix)] mod unix;
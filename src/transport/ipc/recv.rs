@@ -5,21 +5,35 @@
 // This file may not be copied, modified, or distributed except according to those terms.
 
 use std::io;
+use std::cmp;
 
 use byteorder::{ BigEndian, ByteOrder };
 
 use core::Message;
+use core::pool::BufferPool;
 use transport::async::stub::*;
 use io_error::*;
 
+/// Number of bytes the payload buffer grows by when it is full.
+const PAYLOAD_CHUNK_SIZE: u64 = 64 * 1024;
+
 pub struct RecvOperation {
-    step: Option<RecvOperationStep>
+    step: Option<RecvOperationStep>,
+    buffer_pool: Option<BufferPool>
 }
 
 impl RecvOperation {
     pub fn new(recv_max_size: u64) -> RecvOperation {
         RecvOperation {
-            step: Some(RecvOperationStep::Header([0; 9], 0, recv_max_size))
+            step: Some(RecvOperationStep::Header([0; 9], 0, recv_max_size)),
+            buffer_pool: None
+        }
+    }
+
+    pub fn with_pool(recv_max_size: u64, buffer_pool: &BufferPool) -> RecvOperation {
+        RecvOperation {
+            step: Some(RecvOperationStep::Header([0; 9], 0, recv_max_size)),
+            buffer_pool: Some(buffer_pool.clone())
         }
     }
 
@@ -35,7 +49,7 @@ impl RecvOperation {
         let mut cur_step = step;
 
         loop {
-            let (passed, next_step) = try!(cur_step.advance(stream));
+            let (passed, next_step) = try!(cur_step.advance(stream, self.buffer_pool.as_ref()));
 
             if !passed {
                 self.step = Some(next_step);
@@ -52,44 +66,59 @@ impl RecvOperation {
 
 enum RecvOperationStep {
     Header([u8; 9], usize, u64),
-    Payload(Vec<u8>, usize),
+    Payload(Vec<u8>, usize, u64),
     Terminal(Message)
 }
 
 impl RecvOperationStep {
-    fn advance<T:io::Read>(self, stream: &mut T) -> io::Result<(bool, RecvOperationStep)> {
+    fn advance<T:io::Read>(self, stream: &mut T, buffer_pool: Option<&BufferPool>) -> io::Result<(bool, RecvOperationStep)> {
         match self {
-            RecvOperationStep::Header(buffer, read, max_size) => read_header(stream, buffer, read, max_size),
-            RecvOperationStep::Payload(buffer, read) => read_payload(stream, buffer, read),
+            RecvOperationStep::Header(buffer, read, max_size) => read_header(stream, buffer, read, max_size, buffer_pool),
+            RecvOperationStep::Payload(buffer, read, len) => read_payload(stream, buffer, read, len),
             RecvOperationStep::Terminal(_) => Err(other_io_error("Cannot advance terminal step of recv operation"))
         }
     }
 }
 
-fn read_header<T:io::Read>(stream: &mut T, mut buffer: [u8; 9], mut read: usize, max_size: u64) -> io::Result<(bool, RecvOperationStep)> {
+fn read_header<T:io::Read>(stream: &mut T, mut buffer: [u8; 9], mut read: usize, max_size: u64, buffer_pool: Option<&BufferPool>) -> io::Result<(bool, RecvOperationStep)> {
     read += try!(stream.read_buffer(&mut buffer[read..]));
 
     if read == 9 {
         let msg_len = BigEndian::read_u64(&buffer[1..]);
-        if msg_len > max_size {
+        if msg_len > max_size || msg_len > usize::MAX as u64 {
             Err(invalid_data_io_error("message is too long"))
         } else {
-            let payload = vec![0u8; msg_len as usize];
+            let payload = match buffer_pool {
+                Some(pool) => pool.take(cmp::min(msg_len, PAYLOAD_CHUNK_SIZE) as usize),
+                None => Vec::new()
+            };
 
-            Ok((true, RecvOperationStep::Payload(payload, 0)))
+            Ok((true, RecvOperationStep::Payload(payload, 0, msg_len)))
         }
     } else {
         Ok((false, RecvOperationStep::Header(buffer, read, max_size)))
     }
 }
 
-fn read_payload<T:io::Read>(stream: &mut T, mut buffer: Vec<u8>, mut read: usize) -> io::Result<(bool, RecvOperationStep)> {
-    read += try!(stream.read_buffer(&mut buffer[read..]));
+// The payload buffer grows as the bytes arrive instead of being allocated
+// upfront, so a peer cannot make us reserve memory by announcing a huge length.
+fn read_payload<T:io::Read>(stream: &mut T, mut buffer: Vec<u8>, mut read: usize, len: u64) -> io::Result<(bool, RecvOperationStep)> {
+    loop {
+        if read as u64 == len {
+            return Ok((true, RecvOperationStep::Terminal(Message::from_body(buffer))));
+        }
 
-    if read == buffer.capacity() {
-        Ok((true, RecvOperationStep::Terminal(Message::from_body(buffer))))
-    } else {
-        Ok((false, RecvOperationStep::Payload(buffer, read)))
+        if read == buffer.len() {
+            let missing = len - read as u64;
+            let spare = (buffer.capacity() - read) as u64;
+
+            buffer.resize(read + cmp::min(missing, cmp::max(spare, PAYLOAD_CHUNK_SIZE)) as usize, 0);
+        }
+
+        match try!(stream.read_buffer(&mut buffer[read..])) {
+            0 => return Ok((false, RecvOperationStep::Payload(buffer, read, len))),
+            n => read += n
+        }
     }
 }
 
@@ -99,6 +128,10 @@ mod tests {
 
     use super::*;
 
+    const HEADER_AND_BODY: [u8; 12] = [1, 0, 0, 0, 0, 0, 0, 0, 3, 65, 66, 67];
+    const EMPTY: [u8; 9] = [1, 0, 0, 0, 0, 0, 0, 0, 0];
+    const HUGE: [u8; 12] = [1, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 65, 66, 67];
+
     #[test]
     fn recv_in_one_run() {
         let buffer = vec![1, 0, 0, 0, 0, 0, 0, 0, 8, 1, 4, 3, 2, 65, 66, 67, 69];
@@ -111,4 +144,72 @@ mod tests {
 
         assert_eq!(&expected_bytes, msg.get_body());
     }
-}
\ No newline at end of file
+
+    // Reads one byte at a time, reporting `WouldBlock` between each of them.
+    struct ByteByByte {
+        data: Vec<u8>,
+        blocked: bool
+    }
+
+    impl io::Read for ByteByByte {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.blocked || buf.is_empty() {
+                self.blocked = false;
+                return Err(io::Error::new(io::ErrorKind::WouldBlock, "try later"));
+            }
+            if self.data.is_empty() {
+                return Ok(0);
+            }
+
+            buf[0] = self.data.remove(0);
+            self.blocked = true;
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn recv_in_many_runs() {
+        let mut stream = ByteByByte { data: HEADER_AND_BODY.to_vec(), blocked: false };
+        let mut operation = RecvOperation::new(1024);
+        let mut runs = 0;
+
+        let msg = loop {
+            runs += 1;
+            if let Some(msg) = operation.run(&mut stream).expect("recv should have succeeded") {
+                break msg;
+            }
+        };
+
+        assert!(runs > 1);
+        assert_eq!(&[65, 66, 67], msg.get_body());
+    }
+
+    #[test]
+    fn recv_empty_message() {
+        let mut stream = io::Cursor::new(EMPTY.to_vec());
+        let mut operation = RecvOperation::new(1024);
+        let msg = operation.run(&mut stream).
+            expect("recv should have succeeded").
+            expect("recv should be done");
+
+        assert!(msg.get_body().is_empty());
+    }
+
+    #[test]
+    fn recv_does_not_allocate_the_announced_length_upfront() {
+        let mut stream = io::Cursor::new(HUGE.to_vec());
+        let mut operation = RecvOperation::new(u64::max_value());
+        let err = operation.run(&mut stream).unwrap_err();
+
+        assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+    }
+
+    #[test]
+    fn recv_rejects_too_long_message() {
+        let mut stream = io::Cursor::new(HEADER_AND_BODY.to_vec());
+        let mut operation = RecvOperation::new(2);
+        let err = operation.run(&mut stream).unwrap_err();
+
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
+}
@@ -10,6 +10,7 @@ use mio;
 
 use mio_uds::{UnixListener, UnixStream};
 
+use core::pool::BufferPool;
 use transport::*;
 use transport::acceptor::*;
 use transport::async::AsyncPipe;
@@ -18,16 +19,18 @@ use super::stub::IpcPipeStub;
 pub struct IpcAcceptor {
     listener: UnixListener,
     proto_ids: (u16, u16),
-    recv_max_size: u64
+    recv_max_size: u64,
+    buffer_pool: BufferPool
 }
 
 impl IpcAcceptor {
 
-    pub fn new(l: UnixListener, pids: (u16, u16), recv_max_size: u64) -> IpcAcceptor {
+    pub fn new(l: UnixListener, pids: (u16, u16), recv_max_size: u64, buffer_pool: BufferPool) -> IpcAcceptor {
         IpcAcceptor {
             listener: l,
             proto_ids: pids,
-            recv_max_size: recv_max_size
+            recv_max_size: recv_max_size,
+            buffer_pool: buffer_pool
         }
     }
 
@@ -45,11 +48,14 @@ impl IpcAcceptor {
                     break;
                 }
                 Err(e) => {
-                    if e.kind() == io::ErrorKind::WouldBlock {
-                        break;
-                    } else {
-                        ctx.raise(Event::Error(e));
+                    match e.kind() {
+                        io::ErrorKind::WouldBlock => {},
+                        // the peer gave up before its connection could be accepted
+                        io::ErrorKind::ConnectionAborted |
+                        io::ErrorKind::Interrupted => continue,
+                        _ => ctx.raise(Event::Error(e))
                     }
+                    break;
                 }
             }
         }
@@ -60,7 +66,8 @@ impl IpcAcceptor {
     }
 
     fn create_pipe(&self, stream: UnixStream) -> Box<pipe::Pipe> {
-        let stub = IpcPipeStub::new(stream, self.recv_max_size);
+        let peer_cred = super::peer_cred(&stream);
+        let stub = IpcPipeStub::new(stream, peer_cred, self.recv_max_size, self.buffer_pool.clone());
 
         Box::new(AsyncPipe::new(stub, self.proto_ids))
     }
@@ -82,4 +89,16 @@ impl acceptor::Acceptor for IpcAcceptor {
         ctx.deregister(&self.listener);
         ctx.raise(Event::Closed);
     }
+
+    fn pause(&mut self, ctx: &mut Context) {
+        ctx.deregister(&self.listener);
+    }
+
+    fn resume(&mut self, ctx: &mut Context) {
+        ctx.register(&self.listener, mio::Ready::readable(), mio::PollOpt::edge());
+    }
+
+    fn local_addr(&self) -> Option<String> {
+        self.listener.local_addr().ok().and_then(|addr| super::pathname(&addr))
+    }
 }
\ No newline at end of file
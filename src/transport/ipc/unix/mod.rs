@@ -10,6 +10,14 @@ mod acceptor;
 
 use std::io;
 use std::path;
+use std::os::unix::net::SocketAddr;
+#[cfg(target_os = "linux")]
+use std::os::unix::io::AsRawFd;
+#[cfg(target_os = "linux")]
+use std::mem;
+
+#[cfg(target_os = "linux")]
+use libc;
 
 use mio_uds::{UnixListener, UnixStream};
 
@@ -19,6 +27,7 @@ use transport::{Transport, Destination};
 use transport::pipe::Pipe;
 use transport::acceptor::Acceptor;
 use transport::async::AsyncPipe;
+use core::PeerCred;
 
 pub struct Ipc;
 
@@ -26,7 +35,8 @@ impl Transport for Ipc {
     fn connect(&self, dest: &Destination) -> io::Result<Box<Pipe>> {
         let filename = path::Path::new(dest.addr);
         let stream = try!(UnixStream::connect(filename));
-        let stub = IpcPipeStub::new(stream, dest.recv_max_size);
+        let peer_cred = peer_cred(&stream);
+        let stub = IpcPipeStub::new(stream, peer_cred, dest.recv_max_size, dest.buffer_pool.clone());
         let pipe = AsyncPipe::new(stub, dest.pids);
 
         Ok(Box::new(pipe))
@@ -35,8 +45,39 @@ impl Transport for Ipc {
     fn bind(&self, dest: &Destination) -> io::Result<Box<Acceptor>> {
         let filename = path::Path::new(dest.addr);
         let listener = try!(UnixListener::bind(filename));
-        let acceptor = IpcAcceptor::new(listener, dest.pids, dest.recv_max_size);
+        let acceptor = IpcAcceptor::new(listener, dest.pids, dest.recv_max_size, dest.buffer_pool.clone());
 
         Ok(Box::new(acceptor))
     }
 }
+
+// Unnamed unix sockets, like the client side of a connection, have no address to report
+fn pathname(addr: &SocketAddr) -> Option<String> {
+    addr.as_pathname().map(|path| path.display().to_string())
+}
+
+
+#[cfg(target_os = "linux")]
+fn peer_cred(stream: &UnixStream) -> Option<PeerCred> {
+    let mut cred: libc::ucred = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let res = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len)
+    };
+
+    if res == 0 {
+        Some(PeerCred { uid: cred.uid, gid: cred.gid, pid: cred.pid })
+    } else {
+        None
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn peer_cred(_: &UnixStream) -> Option<PeerCred> {
+    None
+}
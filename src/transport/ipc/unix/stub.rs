@@ -13,8 +13,9 @@ use mio;
 
 use mio_uds::UnixStream;
 
-use core::Message;
-use transport::ipc::send::SendOperation;
+use core::pool::BufferPool;
+use core::{Message, PeerCred};
+use transport::stream::send::SendOperation;
 use transport::ipc::recv::RecvOperation;
 use transport::async::stub::*;
 use io_error::*;
@@ -27,9 +28,12 @@ use io_error::*;
 
 pub struct IpcPipeStub {
     stream: UnixStream,
+    peer_cred: Option<PeerCred>,
     recv_max_size: u64,
+    buffer_pool: BufferPool,
     send_operation: Option<SendOperation>,
-    recv_operation: Option<RecvOperation>
+    recv_operation: Option<RecvOperation>,
+    handshake: HandshakeBuffer
 }
 
 impl Deref for IpcPipeStub {
@@ -40,17 +44,20 @@ impl Deref for IpcPipeStub {
 }
 
 impl IpcPipeStub {
-    pub fn new(stream: UnixStream, recv_max_size: u64) -> IpcPipeStub {
+    pub fn new(stream: UnixStream, peer_cred: Option<PeerCred>, recv_max_size: u64, buffer_pool: BufferPool) -> IpcPipeStub {
         IpcPipeStub {
             stream: stream,
+            peer_cred: peer_cred,
             recv_max_size: recv_max_size,
+            buffer_pool: buffer_pool,
             send_operation: None,
-            recv_operation: None
+            recv_operation: None,
+            handshake: HandshakeBuffer::default()
         }
     }
 
     fn run_send_operation(&mut self, mut send_operation: SendOperation) -> io::Result<bool> {
-        if try!(send_operation.run(&mut self.stream)) {
+        if try!(send_operation.run_vectored(&mut self.stream)) {
             Ok(true)
         } else {
             self.send_operation = Some(send_operation);
@@ -83,7 +90,7 @@ impl Drop for IpcPipeStub {
 
 impl Sender for IpcPipeStub {
     fn start_send(&mut self, msg: Rc<Message>) -> io::Result<bool> {
-        let send_operation = SendOperation::new(msg);
+        let send_operation = SendOperation::with_type(msg, 1);
 
         self.run_send_operation(send_operation)
     }
@@ -109,7 +116,7 @@ impl Sender for IpcPipeStub {
 
 impl Receiver for IpcPipeStub {
     fn start_recv(&mut self) -> io::Result<Option<Message>> {
-        let recv_operation = RecvOperation::new(self.recv_max_size);
+        let recv_operation = RecvOperation::with_pool(self.recv_max_size, &self.buffer_pool);
 
         self.run_recv_operation(recv_operation)
     }
@@ -138,9 +145,20 @@ impl Handshake for IpcPipeStub {
         send_and_check_handshake(&mut self.stream, pids)
     }
     fn recv_handshake(&mut self, pids: (u16, u16)) -> io::Result<()> {
-        recv_and_check_handshake(&mut self.stream, pids)
+        recv_and_check_handshake(&mut self.stream, pids, &mut self.handshake)
     }
 }
 
 impl AsyncPipeStub for IpcPipeStub {
+    fn local_addr(&self) -> Option<String> {
+        self.stream.local_addr().ok().and_then(|addr| super::pathname(&addr))
+    }
+
+    fn peer_addr(&self) -> Option<String> {
+        self.stream.peer_addr().ok().and_then(|addr| super::pathname(&addr))
+    }
+
+    fn peer_cred(&self) -> Option<PeerCred> {
+        self.peer_cred
+    }
 }
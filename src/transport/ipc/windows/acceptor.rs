@@ -8,6 +8,7 @@ use mio;
 
 use mio_named_pipes::NamedPipe;
 
+use core::pool::BufferPool;
 use transport::*;
 use transport::acceptor::*;
 use transport::async::AsyncPipe;
@@ -16,16 +17,18 @@ use super::stub::IpcPipeStub;
 pub struct IpcAcceptor {
     addr: String,
     proto_ids: (u16, u16),
-    recv_max_size: u64
+    recv_max_size: u64,
+    buffer_pool: BufferPool
 }
 
 impl IpcAcceptor {
 
-    pub fn new(a: String, pids: (u16, u16), recv_max_size: u64) -> IpcAcceptor {
+    pub fn new(a: String, pids: (u16, u16), recv_max_size: u64, buffer_pool: BufferPool) -> IpcAcceptor {
         IpcAcceptor {
             addr: a,
             proto_ids: pids,
-            recv_max_size: recv_max_size
+            recv_max_size: recv_max_size,
+            buffer_pool: buffer_pool
         }
     }
 
@@ -49,7 +52,7 @@ impl IpcAcceptor {
     }
 
     fn create_pipe(&self, named_pipe: NamedPipe) -> Box<pipe::Pipe> {
-        let stub = IpcPipeStub::new_server(named_pipe, self.recv_max_size);
+        let stub = IpcPipeStub::new_server(named_pipe, self.recv_max_size, self.buffer_pool.clone());
 
         Box::new(AsyncPipe::new(stub, self.proto_ids))
     }
@@ -33,7 +33,7 @@ impl Transport for Ipc {
         let name = format!(r"\\.\pipe\scaproust-pipe-{}", dest.addr);
         let file = try!(options.open(name));
         let named_pipe = unsafe { NamedPipe::from_raw_handle(file.into_raw_handle()) };
-        let stub = IpcPipeStub::new_client(named_pipe, dest.recv_max_size);
+        let stub = IpcPipeStub::new_client(named_pipe, dest.recv_max_size, dest.buffer_pool.clone());
         let pipe = Box::new(AsyncPipe::new(stub, dest.pids));
 
         Ok(pipe)
@@ -41,7 +41,7 @@ impl Transport for Ipc {
 
     fn bind(&self, dest: &Destination) -> io::Result<Box<Acceptor>> {
         let addr = String::from(dest.addr);
-        let acceptor = Box::new(IpcAcceptor::new(addr, dest.pids, dest.recv_max_size));
+        let acceptor = Box::new(IpcAcceptor::new(addr, dest.pids, dest.recv_max_size, dest.buffer_pool.clone()));
 
         Ok(acceptor)
     }
@@ -13,8 +13,9 @@ use mio;
 
 use mio_named_pipes::NamedPipe;
 
+use core::pool::BufferPool;
 use core::Message;
-use transport::ipc::send::SendOperation;
+use transport::stream::send::SendOperation;
 use transport::ipc::recv::RecvOperation;
 use transport::async::stub::*;
 use io_error::*;
@@ -29,8 +30,10 @@ pub struct IpcPipeStub {
     server: bool,
     named_pipe: NamedPipe,
     recv_max_size: u64,
+    buffer_pool: BufferPool,
     send_operation: Option<SendOperation>,
-    recv_operation: Option<RecvOperation>
+    recv_operation: Option<RecvOperation>,
+    handshake: HandshakeBuffer
 }
 
 impl Deref for IpcPipeStub {
@@ -41,23 +44,27 @@ impl Deref for IpcPipeStub {
 }
 
 impl IpcPipeStub {
-    pub fn new_server(named_pipe: NamedPipe, recv_max_size: u64) -> IpcPipeStub {
+    pub fn new_server(named_pipe: NamedPipe, recv_max_size: u64, buffer_pool: BufferPool) -> IpcPipeStub {
         IpcPipeStub {
             server: true,
             named_pipe: named_pipe,
             recv_max_size: recv_max_size,
+            buffer_pool: buffer_pool,
             send_operation: None,
-            recv_operation: None
+            recv_operation: None,
+            handshake: HandshakeBuffer::default()
         }
     }
 
-    pub fn new_client(named_pipe: NamedPipe, recv_max_size: u64) -> IpcPipeStub {
+    pub fn new_client(named_pipe: NamedPipe, recv_max_size: u64, buffer_pool: BufferPool) -> IpcPipeStub {
         IpcPipeStub {
             server: false,
             named_pipe: named_pipe,
             recv_max_size: recv_max_size,
+            buffer_pool: buffer_pool,
             send_operation: None,
-            recv_operation: None
+            recv_operation: None,
+            handshake: HandshakeBuffer::default()
         }
     }
 
@@ -95,7 +102,7 @@ impl Drop for IpcPipeStub {
 
 impl Sender for IpcPipeStub {
     fn start_send(&mut self, msg: Rc<Message>) -> io::Result<bool> {
-        let send_operation = SendOperation::new(msg);
+        let send_operation = SendOperation::with_type(msg, 1);
 
         self.run_send_operation(send_operation)
     }
@@ -121,7 +128,7 @@ impl Sender for IpcPipeStub {
 
 impl Receiver for IpcPipeStub {
     fn start_recv(&mut self) -> io::Result<Option<Message>> {
-        let recv_operation = RecvOperation::new(self.recv_max_size);
+        let recv_operation = RecvOperation::with_pool(self.recv_max_size, &self.buffer_pool);
 
         self.run_recv_operation(recv_operation)
     }
@@ -150,7 +157,7 @@ impl Handshake for IpcPipeStub {
         send_and_check_handshake(&mut self.named_pipe, pids)
     }
     fn recv_handshake(&mut self, pids: (u16, u16)) -> io::Result<()> {
-        recv_and_check_handshake(&mut self.named_pipe, pids)
+        recv_and_check_handshake(&mut self.named_pipe, pids, &mut self.handshake)
     }
 }
 
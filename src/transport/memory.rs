@@ -0,0 +1,313 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+//! In-memory transport, connecting the sockets of a process without any system resource.
+//!
+//! Addresses are arbitrary names, bound by one socket at a time.
+//! Connections are byte streams made of bounded buffers and framed like tcp,
+//! so writes can be partial and the handshake is performed as usual.
+//! Combined with a [virtual clock](../../struct.VirtualClock.html), whole topologies
+//! can be simulated deterministically.
+
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use mio::{Evented, Poll, Token, Ready, PollOpt, Registration, SetReadiness};
+
+use transport::{Transport, Destination};
+use transport::pipe::Pipe;
+use transport::acceptor::Acceptor;
+use transport::async::AsyncPipe;
+use transport::stream::{StreamPipeStub, StreamAcceptor, Listener};
+
+/// Number of bytes that can be written to a connection before the peer reads them.
+const BUFFER_CAPACITY: usize = 64 * 1024;
+
+/// Transport connecting the sockets using the same instance, or a clone of it.
+///
+/// ```no_run
+/// use scaproust::*;
+///
+/// let memory = Memory::new();
+/// let session = SessionBuilder::new().with("mem", memory.clone()).build().unwrap();
+/// // sockets can now bind and connect to urls like "mem://backend"
+/// ```
+#[derive(Clone, Default)]
+pub struct Memory {
+    listeners: Arc<Mutex<HashMap<String, Arc<Mutex<Backlog>>>>>
+}
+
+impl Memory {
+    /// Creates a transport with no bound address.
+    pub fn new() -> Memory {
+        Memory::default()
+    }
+
+    fn listeners(&self) -> MutexGuard<HashMap<String, Arc<Mutex<Backlog>>>> {
+        lock(&self.listeners)
+    }
+}
+
+impl Transport for Memory {
+    // Connecting to an unbound address yields a connection closed by its peer,
+    // so the socket will try to reconnect, as it does with other transports.
+    fn connect(&self, dest: &Destination) -> io::Result<Box<Pipe>> {
+        let (stream, peer) = MemoryStream::pair();
+
+        if let Some(backlog) = self.listeners().get(dest.addr) {
+            lock(backlog).push(peer);
+        }
+
+        let stub = StreamPipeStub::new(stream, dest.recv_max_size).
+            with_addrs(None, Some(dest.addr.to_owned())).
+            with_buffer_pool(dest.buffer_pool.clone()).
+            with_hang_up_check(MemoryStream::is_closed_by_peer);
+
+        Ok(Box::new(AsyncPipe::new(stub, dest.pids)))
+    }
+
+    fn bind(&self, dest: &Destination) -> io::Result<Box<Acceptor>> {
+        let mut listeners = self.listeners();
+
+        if listeners.contains_key(dest.addr) {
+            return Err(io::Error::new(io::ErrorKind::AddrInUse, dest.addr));
+        }
+
+        let (registration, set_readiness) = Registration::new2();
+        let backlog = Arc::new(Mutex::new(Backlog {
+            streams: VecDeque::new(),
+            set_readiness: set_readiness
+        }));
+        let listener = MemoryListener {
+            addr: dest.addr.to_owned(),
+            registration: registration,
+            backlog: backlog.clone(),
+            listeners: self.listeners.clone()
+        };
+
+        listeners.insert(dest.addr.to_owned(), backlog);
+
+        Ok(Box::new(StreamAcceptor::new(listener, dest)))
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* MemoryListener                                                            */
+/*                                                                           */
+/*****************************************************************************/
+
+struct Backlog {
+    streams: VecDeque<MemoryStream>,
+    set_readiness: SetReadiness
+}
+
+impl Backlog {
+    fn push(&mut self, stream: MemoryStream) {
+        self.streams.push_back(stream);
+        let _ = self.set_readiness.set_readiness(Ready::readable());
+    }
+
+    fn pop(&mut self) -> Option<MemoryStream> {
+        let stream = self.streams.pop_front();
+
+        if self.streams.is_empty() {
+            let _ = self.set_readiness.set_readiness(Ready::empty());
+        }
+
+        stream
+    }
+}
+
+struct MemoryListener {
+    addr: String,
+    registration: Registration,
+    backlog: Arc<Mutex<Backlog>>,
+    listeners: Arc<Mutex<HashMap<String, Arc<Mutex<Backlog>>>>>
+}
+
+impl Evented for MemoryListener {
+    fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        self.registration.register(poll, token, interest, opts)
+    }
+    fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        self.registration.reregister(poll, token, interest, opts)
+    }
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        self.registration.deregister(poll)
+    }
+}
+
+impl Listener for MemoryListener {
+    type Stream = MemoryStream;
+
+    fn accept(&mut self) -> io::Result<Option<(MemoryStream, Option<String>)>> {
+        Ok(lock(&self.backlog).pop().map(|stream| (stream, None)))
+    }
+
+    fn local_addr(&self) -> Option<String> {
+        Some(self.addr.clone())
+    }
+
+    fn hang_up_check(&self) -> Option<fn(&MemoryStream) -> bool> {
+        Some(MemoryStream::is_closed_by_peer)
+    }
+}
+
+// Pending connections are dropped with the backlog, so their peers see them closed.
+impl Drop for MemoryListener {
+    fn drop(&mut self) {
+        lock(&self.listeners).remove(&self.addr);
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* MemoryStream                                                              */
+/*                                                                           */
+/*****************************************************************************/
+
+/// Both directions of a connection, each end being identified by its index.
+struct Connection {
+    // bytes flowing toward each end
+    buffers: [VecDeque<u8>; 2],
+    open: [bool; 2],
+    set_readiness: [SetReadiness; 2]
+}
+
+impl Connection {
+    fn readiness(&self, side: usize) -> Ready {
+        let peer = 1 - side;
+        let mut ready = Ready::empty();
+
+        if !self.buffers[side].is_empty() || !self.open[peer] {
+            ready = ready | Ready::readable();
+        }
+        if self.buffers[peer].len() < BUFFER_CAPACITY || !self.open[peer] {
+            ready = ready | Ready::writable();
+        }
+
+        ready
+    }
+
+    fn update_readiness(&self) {
+        for side in 0..2 {
+            let _ = self.set_readiness[side].set_readiness(self.readiness(side));
+        }
+    }
+}
+
+struct MemoryStream {
+    side: usize,
+    registration: Registration,
+    connection: Arc<Mutex<Connection>>
+}
+
+impl MemoryStream {
+    fn pair() -> (MemoryStream, MemoryStream) {
+        let (registration0, set_readiness0) = Registration::new2();
+        let (registration1, set_readiness1) = Registration::new2();
+        let connection = Arc::new(Mutex::new(Connection {
+            buffers: [VecDeque::new(), VecDeque::new()],
+            open: [true, true],
+            set_readiness: [set_readiness0, set_readiness1]
+        }));
+
+        lock(&connection).update_readiness();
+
+        let stream0 = MemoryStream { side: 0, registration: registration0, connection: connection.clone() };
+        let stream1 = MemoryStream { side: 1, registration: registration1, connection: connection };
+
+        (stream0, stream1)
+    }
+
+    // The registration cannot report a hang up to the poll,
+    // the pipe checks the connection instead when the stream gets readable.
+    fn is_closed_by_peer(&self) -> bool {
+        let connection = lock(&self.connection);
+
+        !connection.open[1 - self.side] && connection.buffers[self.side].is_empty()
+    }
+}
+
+impl Read for MemoryStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut connection = lock(&self.connection);
+        let peer = 1 - self.side;
+
+        if connection.buffers[self.side].is_empty() {
+            return if connection.open[peer] {
+                Err(io::Error::new(io::ErrorKind::WouldBlock, "no data"))
+            } else {
+                Ok(0)
+            };
+        }
+
+        let len = ::std::cmp::min(buf.len(), connection.buffers[self.side].len());
+
+        for (dst, src) in buf.iter_mut().zip(connection.buffers[self.side].drain(..len)) {
+            *dst = src;
+        }
+
+        connection.update_readiness();
+        Ok(len)
+    }
+}
+
+impl Write for MemoryStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut connection = lock(&self.connection);
+        let peer = 1 - self.side;
+
+        if !connection.open[peer] {
+            return Err(io::Error::new(io::ErrorKind::BrokenPipe, "connection closed by peer"));
+        }
+
+        let space = BUFFER_CAPACITY - connection.buffers[peer].len();
+
+        if space == 0 && !buf.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::WouldBlock, "buffer is full"));
+        }
+
+        let len = ::std::cmp::min(buf.len(), space);
+
+        connection.buffers[peer].extend(&buf[..len]);
+        connection.update_readiness();
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Evented for MemoryStream {
+    fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        self.registration.register(poll, token, interest, opts)
+    }
+    fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        self.registration.reregister(poll, token, interest, opts)
+    }
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        self.registration.deregister(poll)
+    }
+}
+
+impl Drop for MemoryStream {
+    fn drop(&mut self) {
+        let mut connection = lock(&self.connection);
+
+        connection.open[self.side] = false;
+        connection.buffers[self.side].clear();
+        connection.update_readiness();
+    }
+}
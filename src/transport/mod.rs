@@ -4,24 +4,48 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
+//! Transports carry the messages between sockets, see [`Transport`](trait.Transport.html).
+//!
+//! A stream based transport can be written with the [`stream`](stream/index.html) module,
+//! while [`async`](async/index.html) provides the state machine driving the pipe of any framed transport.
+//! The [`memory`](memory/index.html) transport connects sockets without any system resource.
+
 pub mod async;
+pub mod stream;
 pub mod tcp;
 pub mod ipc;
+pub mod memory;
 pub mod endpoint;
 pub mod pipe;
 pub mod acceptor;
 
 use std::io::Result;
 
+use core::config::TcpKeepalive;
+use core::pool::BufferPool;
+
+/// Everything a transport needs to know to create an endpoint.
 pub struct Destination<'a> {
+    /// Address part of the url, without the scheme.
     pub addr: &'a str,
+    /// Protocol ids of the socket and of its expected peers, to be given to the handshake.
     pub pids: (u16, u16),
+    /// Value of the `TcpNoDelay` option.
     pub tcp_no_delay: bool,
-    pub recv_max_size: u64
+    /// Value of the `TcpKeepalive` option.
+    pub tcp_keepalive: Option<TcpKeepalive>,
+    /// Value of the `RecvMaxSize` option.
+    pub recv_max_size: u64,
+    /// Buffers of the session, to be used for the received messages.
+    pub buffer_pool: &'a BufferPool
 }
 
+/// Creates pipes and acceptors for the urls using the scheme the transport was registered with,
+/// see [`SessionBuilder::with`](../struct.SessionBuilder.html#method.with).
 pub trait Transport {
+    /// Starts connecting to the destination.
     fn connect(&self, dest: &Destination) -> Result<Box<pipe::Pipe>>;
+    /// Starts listening on the destination.
     fn bind(&self, dest: &Destination) -> Result<Box<acceptor::Acceptor>>;
 }
 
@@ -10,7 +10,7 @@ use std::fmt;
 
 use mio::Ready;
 
-use core::Message;
+use core::{Message, PeerCred};
 use transport::endpoint::*;
 
 pub enum Command {
@@ -21,6 +21,8 @@ pub enum Command {
 }
 
 pub enum Event {
+    /// The connection is established and the handshake sent, the one of the peer is awaited.
+    Connected,
     Opened,
     Closed,
     CanSend(bool),
@@ -36,6 +38,13 @@ pub trait Pipe {
     fn close(&mut self, ctx: &mut Context);
     fn send(&mut self, ctx: &mut Context, msg: Rc<Message>);
     fn recv(&mut self, ctx: &mut Context);
+
+    /// Local address of the underlying connection, in the transport's own format.
+    fn local_addr(&self) -> Option<String> { None }
+    /// Remote address of the underlying connection, in the transport's own format.
+    fn peer_addr(&self) -> Option<String> { None }
+    /// Credentials of the remote process, when the transport can find them out.
+    fn peer_cred(&self) -> Option<PeerCred> { None }
 }
 
 pub trait Context : EndpointRegistrar + fmt::Debug {
@@ -68,6 +77,7 @@ impl fmt::Debug for Event {
 impl Event {
     pub fn name(&self) -> &'static str {
         match *self {
+            Event::Connected   => "Connected",
             Event::Opened      => "Opened",
             Event::Closed      => "Closed",
             Event::CanSend(_)  => "CanSend",
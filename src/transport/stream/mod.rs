@@ -0,0 +1,318 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+//! Generic transport building blocks for byte streams.
+//!
+//! Any `Read + Write + Evented` stream can be turned into a pipe by wrapping it in a
+//! [`StreamPipeStub`](struct.StreamPipeStub.html) and giving the stub to
+//! [`AsyncPipe::new`](../async/struct.AsyncPipe.html#method.new).
+//! Messages are framed like tcp does, with their length as a 64 bits big endian prefix.
+//! On the listening side, implementing [`Listener`](trait.Listener.html) is enough
+//! to obtain an acceptor with [`StreamAcceptor`](struct.StreamAcceptor.html).
+
+pub mod send;
+pub mod recv;
+
+use std::ops::Deref;
+use std::rc::Rc;
+use std::io;
+use std::io::{Read, Write};
+
+use mio;
+use mio::Evented;
+
+use core::{Message, PeerCred};
+use core::pool::BufferPool;
+use transport::*;
+use transport::acceptor::*;
+use transport::async::AsyncPipe;
+use transport::async::stub::*;
+use io_error::*;
+
+use self::send::SendOperation;
+use self::recv::RecvOperation;
+
+/*****************************************************************************/
+/*                                                                           */
+/* StreamPipeStub                                                            */
+/*                                                                           */
+/*****************************************************************************/
+
+/// Adapts a non-blocking byte stream to the [`AsyncPipeStub`](../async/stub/trait.AsyncPipeStub.html) interface.
+pub struct StreamPipeStub<S : Read + Write + Evented + 'static> {
+    stream: S,
+    local_addr: Option<String>,
+    peer_addr: Option<String>,
+    peer_cred: Option<PeerCred>,
+    recv_max_size: u64,
+    buffer_pool: BufferPool,
+    send_operation: Option<SendOperation>,
+    recv_operation: Option<RecvOperation>,
+    handshake: HandshakeBuffer,
+    hang_up_check: Option<fn(&S) -> bool>
+}
+
+impl<S : Read + Write + Evented + 'static> Deref for StreamPipeStub<S> {
+    type Target = mio::Evented;
+    fn deref(&self) -> &Self::Target {
+        &self.stream
+    }
+}
+
+impl<S : Read + Write + Evented + 'static> StreamPipeStub<S> {
+    /// Wraps the stream, incoming messages longer than `recv_max_size` are rejected.
+    pub fn new(stream: S, recv_max_size: u64) -> StreamPipeStub<S> {
+        StreamPipeStub {
+            stream: stream,
+            local_addr: None,
+            peer_addr: None,
+            peer_cred: None,
+            recv_max_size: recv_max_size,
+            buffer_pool: BufferPool::new(0),
+            send_operation: None,
+            recv_operation: None,
+            handshake: HandshakeBuffer::default(),
+            hang_up_check: None
+        }
+    }
+
+    /// Sets the addresses reported by the pipe, in the transport's own format.
+    pub fn with_addrs(mut self, local_addr: Option<String>, peer_addr: Option<String>) -> StreamPipeStub<S> {
+        self.local_addr = local_addr;
+        self.peer_addr = peer_addr;
+        self
+    }
+
+    /// Reads the incoming messages in buffers taken from the pool, usually the one of the destination.
+    pub fn with_buffer_pool(mut self, buffer_pool: BufferPool) -> StreamPipeStub<S> {
+        self.buffer_pool = buffer_pool;
+        self
+    }
+
+    /// Sets the credentials of the remote process reported by the pipe.
+    pub fn with_peer_cred(mut self, peer_cred: Option<PeerCred>) -> StreamPipeStub<S> {
+        self.peer_cred = peer_cred;
+        self
+    }
+
+    /// Tells how to find out the peer closed the stream, for streams polled through
+    /// a `mio::Registration`: the hang up readiness they set never reaches the poll.
+    pub fn with_hang_up_check(mut self, hang_up_check: fn(&S) -> bool) -> StreamPipeStub<S> {
+        self.hang_up_check = Some(hang_up_check);
+        self
+    }
+
+    fn run_send_operation(&mut self, mut send_operation: SendOperation) -> io::Result<bool> {
+        if try!(send_operation.run(&mut self.stream)) {
+            Ok(true)
+        } else {
+            self.send_operation = Some(send_operation);
+            Ok(false)
+        }
+    }
+
+    fn run_recv_operation(&mut self, mut recv_operation: RecvOperation) -> io::Result<Option<Message>> {
+        match try!(recv_operation.run(&mut self.stream)) {
+            Some(msg) => Ok(Some(msg)),
+            None => {
+                self.recv_operation = Some(recv_operation);
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl<S : Read + Write + Evented + 'static> Sender for StreamPipeStub<S> {
+    fn start_send(&mut self, msg: Rc<Message>) -> io::Result<bool> {
+        let send_operation = SendOperation::new(msg);
+
+        self.run_send_operation(send_operation)
+    }
+
+    fn resume_send(&mut self) -> io::Result<bool> {
+        if let Some(send_operation) = self.send_operation.take() {
+            self.run_send_operation(send_operation)
+        } else {
+            Err(other_io_error("Cannot resume send: no pending operation"))
+        }
+    }
+
+    fn has_pending_send(&self) -> bool {
+        self.send_operation.is_some()
+    }
+}
+
+impl<S : Read + Write + Evented + 'static> Receiver for StreamPipeStub<S> {
+    fn start_recv(&mut self) -> io::Result<Option<Message>> {
+        let recv_operation = RecvOperation::with_pool(self.recv_max_size, &self.buffer_pool);
+
+        self.run_recv_operation(recv_operation)
+    }
+
+    fn resume_recv(&mut self) -> io::Result<Option<Message>> {
+        if let Some(recv_operation) = self.recv_operation.take() {
+            self.run_recv_operation(recv_operation)
+        } else {
+            Err(other_io_error("Cannot resume recv: no pending operation"))
+        }
+    }
+
+    fn has_pending_recv(&self) -> bool {
+        self.recv_operation.is_some()
+    }
+
+    fn is_closed_by_peer(&self) -> bool {
+        self.hang_up_check.map_or(false, |is_closed| is_closed(&self.stream))
+    }
+}
+
+impl<S : Read + Write + Evented + 'static> Handshake for StreamPipeStub<S> {
+    fn send_handshake(&mut self, pids: (u16, u16)) -> io::Result<()> {
+        send_and_check_handshake(&mut self.stream, pids)
+    }
+    fn recv_handshake(&mut self, pids: (u16, u16)) -> io::Result<()> {
+        recv_and_check_handshake(&mut self.stream, pids, &mut self.handshake)
+    }
+}
+
+impl<S : Read + Write + Evented + 'static> AsyncPipeStub for StreamPipeStub<S> {
+    #[cfg(windows)]
+    fn read_and_write_void(&mut self) {
+        let mut buffer: [u8; 0] = [0; 0];
+
+        let _ = self.stream.read(&mut buffer);
+        let _ = self.stream.write(&buffer);
+    }
+
+    fn local_addr(&self) -> Option<String> {
+        self.local_addr.clone()
+    }
+
+    fn peer_addr(&self) -> Option<String> {
+        self.peer_addr.clone()
+    }
+
+    fn peer_cred(&self) -> Option<PeerCred> {
+        self.peer_cred
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* StreamAcceptor                                                            */
+/*                                                                           */
+/*****************************************************************************/
+
+/// Source of incoming streams, such as a listening socket.
+pub trait Listener : Evented {
+    /// Type of the accepted streams.
+    type Stream : Read + Write + Evented + 'static;
+
+    /// Accepts a pending connection and returns the stream with the peer address, if known.
+    /// Returns `Ok(None)` or a `WouldBlock` error when there is no more pending connection.
+    fn accept(&mut self) -> io::Result<Option<(Self::Stream, Option<String>)>>;
+
+    /// Address the listener is actually bound to, in the transport's own format.
+    fn local_addr(&self) -> Option<String> { None }
+
+    /// Hang up check given to the pipes of the accepted streams,
+    /// see [`StreamPipeStub::with_hang_up_check`](struct.StreamPipeStub.html#method.with_hang_up_check).
+    fn hang_up_check(&self) -> Option<fn(&Self::Stream) -> bool> { None }
+}
+
+/// Acceptor creating a framed pipe for each stream returned by a [`Listener`](trait.Listener.html).
+pub struct StreamAcceptor<L : Listener> {
+    listener: L,
+    proto_ids: (u16, u16),
+    recv_max_size: u64,
+    buffer_pool: BufferPool
+}
+
+impl<L : Listener> StreamAcceptor<L> {
+    /// Creates an acceptor for the protocol ids and limits specified in the destination.
+    pub fn new(listener: L, dest: &Destination) -> StreamAcceptor<L> {
+        StreamAcceptor {
+            listener: listener,
+            proto_ids: dest.pids,
+            recv_max_size: dest.recv_max_size,
+            buffer_pool: dest.buffer_pool.clone()
+        }
+    }
+
+    fn accept(&mut self, ctx: &mut Context) {
+        let mut pipes = Vec::new();
+
+        loop {
+            match self.listener.accept() {
+                Ok(Some((stream, peer_addr))) => {
+                    let pipe = self.create_pipe(stream, peer_addr);
+
+                    pipes.push(pipe);
+                },
+                Ok(None) => {
+                    break;
+                }
+                Err(e) => {
+                    match e.kind() {
+                        io::ErrorKind::WouldBlock => {},
+                        // the peer gave up before its connection could be accepted
+                        io::ErrorKind::ConnectionAborted |
+                        io::ErrorKind::Interrupted => continue,
+                        _ => ctx.raise(Event::Error(e))
+                    }
+                    break;
+                }
+            }
+        }
+
+        if pipes.is_empty() == false {
+            ctx.raise(Event::Accepted(pipes));
+        }
+    }
+
+    fn create_pipe(&self, stream: L::Stream, peer_addr: Option<String>) -> Box<pipe::Pipe> {
+        let local_addr = self.listener.local_addr();
+        let mut stub = StreamPipeStub::new(stream, self.recv_max_size).
+            with_addrs(local_addr, peer_addr).
+            with_buffer_pool(self.buffer_pool.clone());
+
+        if let Some(hang_up_check) = self.listener.hang_up_check() {
+            stub = stub.with_hang_up_check(hang_up_check);
+        }
+
+        Box::new(AsyncPipe::new(stub, self.proto_ids))
+    }
+}
+
+impl<L : Listener> acceptor::Acceptor for StreamAcceptor<L> {
+    fn ready(&mut self, ctx: &mut Context, events: mio::Ready) {
+        if events.is_readable() {
+            self.accept(ctx);
+        }
+    }
+
+    fn open(&mut self, ctx: &mut Context) {
+        ctx.register(&self.listener, mio::Ready::readable(), mio::PollOpt::edge());
+        ctx.raise(Event::Opened);
+    }
+
+    fn close(&mut self, ctx: &mut Context) {
+        ctx.deregister(&self.listener);
+        ctx.raise(Event::Closed);
+    }
+
+    fn pause(&mut self, ctx: &mut Context) {
+        ctx.deregister(&self.listener);
+    }
+
+    fn resume(&mut self, ctx: &mut Context) {
+        ctx.register(&self.listener, mio::Ready::readable(), mio::PollOpt::edge());
+    }
+
+    fn local_addr(&self) -> Option<String> {
+        self.listener.local_addr()
+    }
+}
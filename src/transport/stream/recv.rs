@@ -0,0 +1,221 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+//! Reads messages framed with their length as a 64 bits big endian prefix, as tcp does.
+
+use std::io;
+use std::cmp;
+
+use byteorder::{ BigEndian, ByteOrder };
+
+use core::Message;
+use core::pool::BufferPool;
+use transport::async::stub::*;
+use io_error::*;
+
+/// Number of bytes the payload buffer grows by when it is full.
+const PAYLOAD_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// Resumable read of one message.
+pub struct RecvOperation {
+    step: Option<RecvOperationStep>,
+    buffer_pool: Option<BufferPool>
+}
+
+impl RecvOperation {
+    /// Messages longer than `recv_max_size` are rejected with `InvalidData`.
+    pub fn new(recv_max_size: u64) -> RecvOperation {
+        RecvOperation {
+            step: Some(RecvOperationStep::Header([0; 8], 0, recv_max_size)),
+            buffer_pool: None
+        }
+    }
+
+    /// Same as `new`, but the payload is read in a buffer taken from the pool.
+    pub fn with_pool(recv_max_size: u64, buffer_pool: &BufferPool) -> RecvOperation {
+        RecvOperation {
+            step: Some(RecvOperationStep::Header([0; 8], 0, recv_max_size)),
+            buffer_pool: Some(buffer_pool.clone())
+        }
+    }
+
+    /// Reads as much as possible, returns the message once it has been fully read.
+    pub fn run<T:io::Read>(&mut self, stream: &mut T) -> io::Result<Option<Message>> {
+        if let Some(step) = self.step.take() {
+            self.resume_at(stream, step)
+        } else {
+            Err(other_io_error("Cannot resume already finished recv operation"))
+        }
+    }
+
+    fn resume_at<T:io::Read>(&mut self, stream: &mut T, step: RecvOperationStep) -> io::Result<Option<Message>> {
+        let mut cur_step = step;
+
+        loop {
+            let (passed, next_step) = try!(cur_step.advance(stream, self.buffer_pool.as_ref()));
+
+            if !passed {
+                self.step = Some(next_step);
+                return Ok(None);
+            }
+
+            match next_step {
+                RecvOperationStep::Terminal(msg) => return Ok(Some(msg)),
+                other => cur_step = other
+            }
+        }
+    }
+}
+
+enum RecvOperationStep {
+    Header([u8; 8], usize, u64),
+    Payload(Vec<u8>, usize, u64),
+    Terminal(Message)
+}
+
+impl RecvOperationStep {
+    fn advance<T:io::Read>(self, stream: &mut T, buffer_pool: Option<&BufferPool>) -> io::Result<(bool, RecvOperationStep)> {
+        match self {
+            RecvOperationStep::Header(buffer, read, max_size) => read_header(stream, buffer, read, max_size, buffer_pool),
+            RecvOperationStep::Payload(buffer, read, len) => read_payload(stream, buffer, read, len),
+            RecvOperationStep::Terminal(_) => Err(other_io_error("Cannot advance terminal step of recv operation"))
+        }
+    }
+}
+
+fn read_header<T:io::Read>(stream: &mut T, mut buffer: [u8; 8], mut read: usize, max_size: u64, buffer_pool: Option<&BufferPool>) -> io::Result<(bool, RecvOperationStep)> {
+    read += try!(stream.read_buffer(&mut buffer[read..]));
+
+    if read == 8 {
+        let msg_len = BigEndian::read_u64(&buffer);
+        if msg_len > max_size || msg_len > usize::MAX as u64 {
+            Err(invalid_data_io_error("message is too long"))
+        } else {
+            let payload = match buffer_pool {
+                Some(pool) => pool.take(cmp::min(msg_len, PAYLOAD_CHUNK_SIZE) as usize),
+                None => Vec::new()
+            };
+
+            Ok((true, RecvOperationStep::Payload(payload, 0, msg_len)))
+        }
+    } else {
+        Ok((false, RecvOperationStep::Header(buffer, read, max_size)))
+    }
+}
+
+// The payload buffer grows as the bytes arrive instead of being allocated
+// upfront, so a peer cannot make us reserve memory by announcing a huge length.
+fn read_payload<T:io::Read>(stream: &mut T, mut buffer: Vec<u8>, mut read: usize, len: u64) -> io::Result<(bool, RecvOperationStep)> {
+    loop {
+        if read as u64 == len {
+            return Ok((true, RecvOperationStep::Terminal(Message::from_body(buffer))));
+        }
+
+        if read == buffer.len() {
+            let missing = len - read as u64;
+            let spare = (buffer.capacity() - read) as u64;
+
+            buffer.resize(read + cmp::min(missing, cmp::max(spare, PAYLOAD_CHUNK_SIZE)) as usize, 0);
+        }
+
+        match try!(stream.read_buffer(&mut buffer[read..])) {
+            0 => return Ok((false, RecvOperationStep::Payload(buffer, read, len))),
+            n => read += n
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+
+    const HEADER_AND_BODY: [u8; 11] = [0, 0, 0, 0, 0, 0, 0, 3, 65, 66, 67];
+    const EMPTY: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 0];
+    const HUGE: [u8; 11] = [0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 65, 66, 67];
+
+    #[test]
+    fn recv_in_one_run() {
+        let buffer = vec![0, 0, 0, 0, 0, 0, 0, 8, 1, 4, 3, 2, 65, 66, 67, 69];
+        let mut stream = io::Cursor::new(buffer);
+        let mut operation = RecvOperation::new(1024);
+        let msg = operation.run(&mut stream).
+            expect("recv should have succeeded").
+            expect("recv should be done");
+        let expected_bytes = [1, 4, 3, 2, 65, 66, 67, 69];
+
+        assert_eq!(&expected_bytes, msg.get_body());
+    }
+
+    // Reads one byte at a time, reporting `WouldBlock` between each of them.
+    struct ByteByByte {
+        data: Vec<u8>,
+        blocked: bool
+    }
+
+    impl io::Read for ByteByByte {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.blocked || buf.is_empty() {
+                self.blocked = false;
+                return Err(io::Error::new(io::ErrorKind::WouldBlock, "try later"));
+            }
+            if self.data.is_empty() {
+                return Ok(0);
+            }
+
+            buf[0] = self.data.remove(0);
+            self.blocked = true;
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn recv_in_many_runs() {
+        let mut stream = ByteByByte { data: HEADER_AND_BODY.to_vec(), blocked: false };
+        let mut operation = RecvOperation::new(1024);
+        let mut runs = 0;
+
+        let msg = loop {
+            runs += 1;
+            if let Some(msg) = operation.run(&mut stream).expect("recv should have succeeded") {
+                break msg;
+            }
+        };
+
+        assert!(runs > 1);
+        assert_eq!(&[65, 66, 67], msg.get_body());
+    }
+
+    #[test]
+    fn recv_empty_message() {
+        let mut stream = io::Cursor::new(EMPTY.to_vec());
+        let mut operation = RecvOperation::new(1024);
+        let msg = operation.run(&mut stream).
+            expect("recv should have succeeded").
+            expect("recv should be done");
+
+        assert!(msg.get_body().is_empty());
+    }
+
+    #[test]
+    fn recv_does_not_allocate_the_announced_length_upfront() {
+        let mut stream = io::Cursor::new(HUGE.to_vec());
+        let mut operation = RecvOperation::new(u64::max_value());
+        let err = operation.run(&mut stream).unwrap_err();
+
+        assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+    }
+
+    #[test]
+    fn recv_rejects_too_long_message() {
+        let mut stream = io::Cursor::new(HEADER_AND_BODY.to_vec());
+        let mut operation = RecvOperation::new(2);
+        let err = operation.run(&mut stream).unwrap_err();
+
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
+}
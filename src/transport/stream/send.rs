@@ -0,0 +1,228 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+//! Writes messages framed with their length as a 64 bits big endian prefix, as tcp does.
+//!
+//! The frame prefix, the protocol header and the body are written straight from the message,
+//! which is shared by all the pipes a message is broadcast to, and never copied.
+//! Streams implementing [`WriteBufs`](../../async/stub/trait.WriteBufs.html) write
+//! the three buffers with a single system call.
+
+use std::rc::Rc;
+use std::io;
+
+use byteorder::{ BigEndian, ByteOrder };
+use iovec::IoVec;
+
+use core::Message;
+use transport::async::stub::*;
+
+// Fills the unused slots of the buffer array, it is never written.
+static PLACEHOLDER: [u8; 1] = [0];
+
+/// Resumable write of one message.
+pub struct SendOperation {
+    msg: Rc<Message>,
+    prefix: [u8; 9],
+    prefix_len: usize,
+    written: usize
+}
+
+impl SendOperation {
+    /// Prepares the write of the message.
+    pub fn new(msg: Rc<Message>) -> SendOperation {
+        let mut prefix = [0u8; 9];
+
+        BigEndian::write_u64(&mut prefix[..8], msg.len() as u64);
+
+        SendOperation {
+            msg: msg,
+            prefix: prefix,
+            prefix_len: 8,
+            written: 0
+        }
+    }
+
+    /// Prepares the write of the message, its length being preceded by a type byte, as ipc does.
+    pub fn with_type(msg: Rc<Message>, msg_type: u8) -> SendOperation {
+        let mut prefix = [msg_type; 9];
+
+        BigEndian::write_u64(&mut prefix[1..], msg.len() as u64);
+
+        SendOperation {
+            msg: msg,
+            prefix: prefix,
+            prefix_len: 9,
+            written: 0
+        }
+    }
+
+    /// Writes as much as possible, one buffer per call to `write`,
+    /// returns whether the message has been fully written.
+    pub fn run<T:io::Write>(&mut self, stream: &mut T) -> io::Result<bool> {
+        self.run_with(|bufs| stream.write(bufs[0]))
+    }
+
+    /// Writes as much as possible, all the remaining buffers per call to `write_bufs`,
+    /// returns whether the message has been fully written.
+    pub fn run_vectored<T:WriteBufs>(&mut self, stream: &mut T) -> io::Result<bool> {
+        self.run_with(|bufs| stream.write_bufs(bufs))
+    }
+
+    fn run_with<F>(&mut self, mut write: F) -> io::Result<bool> where F : FnMut(&[&IoVec]) -> io::Result<usize> {
+        loop {
+            let res = {
+                let mut bufs: [&IoVec; 3] = [PLACEHOLDER[..].into(); 3];
+                let count = self.remaining_bufs(&mut bufs);
+
+                if count == 0 {
+                    return Ok(true);
+                }
+
+                write(&bufs[..count])
+            };
+
+            match res {
+                Ok(0) => return Err(io::Error::new(io::ErrorKind::WriteZero, "failed to write message")),
+                Ok(x) => self.written += x,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(false),
+                Err(e) => return Err(e)
+            }
+        }
+    }
+
+    // Fills the array with the parts of the message not written yet,
+    // empty ones being skipped, and returns how many were filled.
+    fn remaining_bufs<'a>(&'a self, bufs: &mut [&'a IoVec; 3]) -> usize {
+        let parts: [&[u8]; 3] = [&self.prefix[..self.prefix_len], self.msg.get_header(), self.msg.get_body()];
+        let mut skipped = self.written;
+        let mut count = 0;
+
+        for part in &parts {
+            if skipped >= part.len() {
+                skipped -= part.len();
+                continue;
+            }
+
+            if let Some(buf) = IoVec::from_bytes(&part[skipped..]) {
+                bufs[count] = buf;
+                count += 1;
+            }
+            skipped = 0;
+        }
+
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::ops::Deref;
+    use std::rc::Rc;
+
+    use iovec::IoVec;
+
+    use core::Message;
+    use transport::async::stub::WriteBufs;
+    use super::*;
+
+    // Accepts at most `capacity` bytes per call, recording how many buffers each call received.
+    struct LimitedStream {
+        bytes: Vec<u8>,
+        capacity: usize,
+        calls: Vec<usize>
+    }
+
+    impl LimitedStream {
+        fn new(capacity: usize) -> LimitedStream {
+            LimitedStream { bytes: Vec::new(), capacity: capacity, calls: Vec::new() }
+        }
+    }
+
+    impl WriteBufs for LimitedStream {
+        fn write_bufs(&mut self, bufs: &[&IoVec]) -> io::Result<usize> {
+            let mut written = 0;
+
+            self.calls.push(bufs.len());
+            for buf in bufs {
+                for byte in buf.iter() {
+                    if written == self.capacity {
+                        return Ok(written);
+                    }
+                    self.bytes.push(*byte);
+                    written += 1;
+                }
+            }
+
+            Ok(written)
+        }
+    }
+
+    #[test]
+    fn send_in_one_run() {
+        let header = vec!(1, 4, 3, 2);
+        let payload = vec!(65, 66, 67, 69);
+        let msg = Message::from_header_and_body(header, payload);
+        let mut operation = SendOperation::new(Rc::new(msg));
+        let mut stream = Vec::new();
+        let result = operation.run(&mut stream).expect("send should have succeeded");
+        let expected_bytes = [0, 0, 0, 0, 0, 0, 0, 8, 1, 4, 3, 2, 65, 66, 67, 69];
+
+        assert!(result);
+        assert_eq!(&expected_bytes, stream.deref());
+    }
+
+    #[test]
+    fn send_with_type_in_one_run() {
+        let header = vec!(1, 4, 3, 2);
+        let payload = vec!(65, 66, 67, 69);
+        let msg = Message::from_header_and_body(header, payload);
+        let mut operation = SendOperation::with_type(Rc::new(msg), 1);
+        let mut stream = Vec::new();
+        let result = operation.run(&mut stream).expect("send should have succeeded");
+        let expected_bytes = [1, 0, 0, 0, 0, 0, 0, 0, 8, 1, 4, 3, 2, 65, 66, 67, 69];
+
+        assert!(result);
+        assert_eq!(&expected_bytes, stream.deref());
+    }
+
+    #[test]
+    fn send_vectored_in_one_call() {
+        let msg = Message::from_header_and_body(vec!(1, 4, 3, 2), vec!(65, 66, 67, 69));
+        let mut operation = SendOperation::new(Rc::new(msg));
+        let mut stream = LimitedStream::new(1024);
+        let result = operation.run_vectored(&mut stream).expect("send should have succeeded");
+
+        assert!(result);
+        assert_eq!(vec!(3), stream.calls);
+        assert_eq!(16, stream.bytes.len());
+    }
+
+    #[test]
+    fn send_vectored_resumes_inside_a_buffer() {
+        let msg = Message::from_header_and_body(vec!(1, 4, 3, 2), vec!(65, 66, 67, 69));
+        let mut operation = SendOperation::new(Rc::new(msg));
+        let mut stream = LimitedStream::new(5);
+        let result = operation.run_vectored(&mut stream).expect("send should have succeeded");
+        let expected_bytes = [0, 0, 0, 0, 0, 0, 0, 8, 1, 4, 3, 2, 65, 66, 67, 69];
+
+        assert!(result);
+        assert_eq!(vec!(3, 3, 2, 1), stream.calls);
+        assert_eq!(&expected_bytes, stream.bytes.deref());
+    }
+
+    #[test]
+    fn send_vectored_empty_message() {
+        let mut operation = SendOperation::new(Rc::new(Message::new()));
+        let mut stream = LimitedStream::new(1024);
+        let result = operation.run_vectored(&mut stream).expect("send should have succeeded");
+
+        assert!(result);
+        assert_eq!(vec!(1), stream.calls);
+        assert_eq!(&[0u8; 8], stream.bytes.deref());
+    }
+}
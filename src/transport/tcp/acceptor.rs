@@ -5,10 +5,13 @@
 // This file may not be copied, modified, or distributed except according to those terms.
 
 use std::io;
+use std::net;
 
 use mio;
 use mio::tcp::{TcpListener, TcpStream};
 
+use core::config::TcpKeepalive;
+use core::pool::BufferPool;
 use transport::*;
 use transport::acceptor::*;
 use transport::async::AsyncPipe;
@@ -18,7 +21,9 @@ pub struct TcpAcceptor {
     listener: TcpListener,
     proto_ids: (u16, u16),
     no_delay: bool,
-    recv_max_size: u64
+    keepalive: Option<TcpKeepalive>,
+    recv_max_size: u64,
+    buffer_pool: BufferPool
 }
 
 impl TcpAcceptor {
@@ -28,7 +33,9 @@ impl TcpAcceptor {
             listener: l,
             proto_ids: dest.pids,
             no_delay: dest.tcp_no_delay,
-            recv_max_size: dest.recv_max_size
+            keepalive: dest.tcp_keepalive,
+            recv_max_size: dest.recv_max_size,
+            buffer_pool: dest.buffer_pool.clone()
         }
     }
 
@@ -37,18 +44,22 @@ impl TcpAcceptor {
 
         loop {
             match self.listener.accept() {
-                Ok((stream, _)) => {
+                Ok((stream, addr)) => {
                     let _ = stream.set_nodelay(self.no_delay);
-                    let pipe = self.create_pipe(stream);
+                    let _ = super::set_keepalive(&stream, self.keepalive);
+                    let pipe = self.create_pipe(stream, addr);
 
                     pipes.push(pipe);
                 },
                 Err(e) => {
-                    if e.kind() == io::ErrorKind::WouldBlock {
-                        break;
-                    } else {
-                        ctx.raise(Event::Error(e));
+                    match e.kind() {
+                        io::ErrorKind::WouldBlock => {},
+                        // the peer gave up before its connection could be accepted
+                        io::ErrorKind::ConnectionAborted |
+                        io::ErrorKind::Interrupted => continue,
+                        _ => ctx.raise(Event::Error(e))
                     }
+                    break;
                 }
             }
         }
@@ -58,8 +69,8 @@ impl TcpAcceptor {
         }
     }
 
-    fn create_pipe(&self, stream: TcpStream) -> Box<pipe::Pipe> {
-        let stub = TcpPipeStub::new(stream, self.recv_max_size);
+    fn create_pipe(&self, stream: TcpStream, addr: net::SocketAddr) -> Box<pipe::Pipe> {
+        let stub = TcpPipeStub::new(stream, addr, self.recv_max_size, self.buffer_pool.clone());
 
         Box::new(AsyncPipe::new(stub, self.proto_ids))
     }
@@ -81,4 +92,16 @@ impl acceptor::Acceptor for TcpAcceptor {
         ctx.deregister(&self.listener);
         ctx.raise(Event::Closed);
     }
+
+    fn pause(&mut self, ctx: &mut Context) {
+        ctx.deregister(&self.listener);
+    }
+
+    fn resume(&mut self, ctx: &mut Context) {
+        ctx.register(&self.listener, mio::Ready::readable(), mio::PollOpt::edge());
+    }
+
+    fn local_addr(&self) -> Option<String> {
+        self.listener.local_addr().ok().map(|addr| addr.to_string())
+    }
 }
\ No newline at end of file
@@ -5,16 +5,21 @@
 // This file may not be copied, modified, or distributed except according to those terms.
 
 mod stub;
-mod send;
-mod recv;
 mod acceptor;
 
 use std::str::FromStr;
 use std::io;
 use std::net;
+#[cfg(target_os = "linux")]
+use std::{cmp, mem};
+#[cfg(target_os = "linux")]
+use std::os::unix::io::AsRawFd;
 
+#[cfg(target_os = "linux")]
+use libc;
 use mio::tcp::{TcpListener, TcpStream};
 
+use core::config::TcpKeepalive;
 use self::stub::TcpPipeStub;
 use self::acceptor::TcpAcceptor;
 use transport::{Transport, Destination};
@@ -29,7 +34,8 @@ impl Tcp {
     fn connect(&self, addr: &net::SocketAddr, dest: &Destination) -> io::Result<Box<Pipe>> {
         let stream = try!(TcpStream::connect(addr));
         try!(stream.set_nodelay(dest.tcp_no_delay));
-        let stub = TcpPipeStub::new(stream, dest.recv_max_size);
+        try!(set_keepalive(&stream, dest.tcp_keepalive));
+        let stub = TcpPipeStub::new(stream, *addr, dest.recv_max_size, dest.buffer_pool.clone());
         let pipe = AsyncPipe::new(stub, dest.pids);
 
         Ok(Box::new(pipe))
@@ -57,3 +63,46 @@ impl Transport for Tcp {
         }
     }
 }
+
+fn set_keepalive(stream: &TcpStream, keepalive: Option<TcpKeepalive>) -> io::Result<()> {
+    match keepalive {
+        Some(keepalive) => {
+            try!(stream.set_keepalive(Some(keepalive.idle)));
+            set_keepalive_probes(stream, keepalive)
+        },
+        None => Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_keepalive_probes(stream: &TcpStream, keepalive: TcpKeepalive) -> io::Result<()> {
+    // The kernel counts the interval in whole seconds and rejects zero
+    let interval = cmp::max(1, keepalive.interval.as_secs()) as libc::c_int;
+    let count = keepalive.count as libc::c_int;
+
+    try!(setsockopt(stream.as_raw_fd(), libc::TCP_KEEPINTVL, interval));
+    setsockopt(stream.as_raw_fd(), libc::TCP_KEEPCNT, count)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_keepalive_probes(_: &TcpStream, _: TcpKeepalive) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn setsockopt(fd: libc::c_int, name: libc::c_int, value: libc::c_int) -> io::Result<()> {
+    let res = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::IPPROTO_TCP,
+            name,
+            &value as *const libc::c_int as *const libc::c_void,
+            mem::size_of::<libc::c_int>() as libc::socklen_t)
+    };
+
+    if res == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
@@ -7,6 +7,7 @@
 use std::ops::Deref;
 use std::rc::Rc;
 use std::io;
+use std::net;
 #[cfg(windows)]
 use std::io::{Read, Write};
 
@@ -14,8 +15,9 @@ use mio;
 use mio::tcp::{TcpStream, Shutdown};
 
 use core::Message;
-use transport::tcp::send::SendOperation;
-use transport::tcp::recv::RecvOperation;
+use core::pool::BufferPool;
+use transport::stream::send::SendOperation;
+use transport::stream::recv::RecvOperation;
 use transport::async::stub::*;
 use io_error::*;
 
@@ -27,9 +29,12 @@ use io_error::*;
 
 pub struct TcpPipeStub {
     stream: TcpStream,
+    peer_addr: net::SocketAddr,
     recv_max_size: u64,
+    buffer_pool: BufferPool,
     send_operation: Option<SendOperation>,
-    recv_operation: Option<RecvOperation>
+    recv_operation: Option<RecvOperation>,
+    handshake: HandshakeBuffer
 }
 
 impl Deref for TcpPipeStub {
@@ -40,17 +45,20 @@ impl Deref for TcpPipeStub {
 }
 
 impl TcpPipeStub {
-    pub fn new(stream: TcpStream, recv_max_size: u64) -> TcpPipeStub {
+    pub fn new(stream: TcpStream, peer_addr: net::SocketAddr, recv_max_size: u64, buffer_pool: BufferPool) -> TcpPipeStub {
         TcpPipeStub {
             stream: stream,
+            peer_addr: peer_addr,
             recv_max_size: recv_max_size,
+            buffer_pool: buffer_pool,
             send_operation: None,
-            recv_operation: None
+            recv_operation: None,
+            handshake: HandshakeBuffer::default()
         }
     }
 
     fn run_send_operation(&mut self, mut send_operation: SendOperation) -> io::Result<bool> {
-        if try!(send_operation.run(&mut self.stream)) {
+        if try!(send_operation.run_vectored(&mut self.stream)) {
             Ok(true)
         } else {
             self.send_operation = Some(send_operation);
@@ -109,7 +117,7 @@ impl Sender for TcpPipeStub {
 
 impl Receiver for TcpPipeStub {
     fn start_recv(&mut self) -> io::Result<Option<Message>> {
-        let recv_operation = RecvOperation::new(self.recv_max_size);
+        let recv_operation = RecvOperation::with_pool(self.recv_max_size, &self.buffer_pool);
 
         self.run_recv_operation(recv_operation)
     }
@@ -138,7 +146,7 @@ impl Handshake for TcpPipeStub {
         send_and_check_handshake(&mut self.stream, pids)
     }
     fn recv_handshake(&mut self, pids: (u16, u16)) -> io::Result<()> {
-        recv_and_check_handshake(&mut self.stream, pids)
+        recv_and_check_handshake(&mut self.stream, pids, &mut self.handshake)
     }
 }
 
@@ -150,4 +158,13 @@ impl AsyncPipeStub for TcpPipeStub {
         let _ = self.stream.read(&mut buffer);
         let _ = self.stream.write(&buffer);
     }
+
+    fn local_addr(&self) -> Option<String> {
+        self.stream.local_addr().ok().map(|addr| addr.to_string())
+    }
+
+    // While a connection is in progress, the stream does not know its peer yet
+    fn peer_addr(&self) -> Option<String> {
+        Some(self.peer_addr.to_string())
+    }
 }
@@ -0,0 +1,165 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use std::time::{Duration, Instant};
+pub use std::io;
+
+pub use scaproust::*;
+
+pub use super::{urls, sleep_some};
+pub use super::make_timeout;
+
+fn make_session() -> Session {
+    SessionBuilder::new().with("tcp", Tcp).build().expect("Failed to create session !")
+}
+
+fn make_batch(count: u8) -> Vec<Message> {
+    (0..count).map(|x| Message::from_body(vec![x])).collect()
+}
+
+#[test]
+fn push_sends_a_batch_received_in_order() {
+    let _ = ::env_logger::init();
+    let mut session = make_session();
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let url = urls::tcp::get();
+
+    pull.bind(&url).unwrap();
+    push.connect(&url).unwrap();
+    push.set_send_timeout(make_timeout()).unwrap();
+    sleep_some();
+
+    assert_eq!(100, push.send_batch(make_batch(100)).expect("Push should have sent the batch"));
+
+    let mut received = Vec::new();
+    while received.len() < 100 {
+        let msgs = pull.recv_batch(100, make_timeout()).expect("Pull should have received a batch");
+
+        assert!(msgs.len() <= 100 - received.len());
+        received.extend(msgs.into_iter().map(|msg| msg.get_body()[0]));
+    }
+
+    assert_eq!((0..100).collect::<Vec<u8>>(), received);
+}
+
+#[test]
+fn push_load_balances_the_messages_of_a_batch() {
+    let _ = ::env_logger::init();
+    let mut session = make_session();
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut pull1 = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let mut pull2 = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let url = urls::tcp::get();
+
+    push.bind(&url).unwrap();
+    pull1.connect(&url).unwrap();
+    pull2.connect(&url).unwrap();
+    push.set_send_timeout(make_timeout()).unwrap();
+    sleep_some();
+
+    assert_eq!(4, push.send_batch(make_batch(4)).expect("Push should have sent the batch"));
+
+    let msgs1 = pull1.recv_batch(4, Some(Duration::from_millis(200))).expect("Pull 1 should have received a batch");
+    let msgs2 = pull2.recv_batch(4, Some(Duration::from_millis(200))).expect("Pull 2 should have received a batch");
+
+    assert_eq!(2, msgs1.len());
+    assert_eq!(2, msgs2.len());
+}
+
+#[test]
+fn pub_broadcasts_each_message_of_a_batch() {
+    let _ = ::env_logger::init();
+    let mut session = make_session();
+    let mut publ = session.create_socket::<Pub>().expect("Failed to create socket !");
+    let mut sub1 = session.create_socket::<Sub>().expect("Failed to create socket !");
+    let mut sub2 = session.create_socket::<Sub>().expect("Failed to create socket !");
+    let url = urls::tcp::get();
+
+    publ.bind(&url).unwrap();
+    sub1.connect(&url).unwrap();
+    sub2.connect(&url).unwrap();
+    sub1.set_option(ConfigOption::Subscribe(String::new())).unwrap();
+    sub2.set_option(ConfigOption::Subscribe(String::new())).unwrap();
+    sleep_some();
+
+    assert_eq!(3, publ.send_batch(make_batch(3)).expect("Pub should have sent the batch"));
+
+    for sub in &mut [sub1, sub2] {
+        let mut received = Vec::new();
+        while received.len() < 3 {
+            let msgs = sub.recv_batch(3, make_timeout()).expect("Sub should have received a batch");
+            received.extend(msgs.into_iter().map(|msg| msg.get_body()[0]));
+        }
+        assert_eq!(vec![0, 1, 2], received);
+    }
+}
+
+#[test]
+fn recv_batch_returns_what_was_received_in_time() {
+    let _ = ::env_logger::init();
+    let mut session = make_session();
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let url = urls::tcp::get();
+    let timeout = Some(Duration::from_millis(100));
+
+    pull.bind(&url).unwrap();
+    push.connect(&url).unwrap();
+    sleep_some();
+
+    let err = pull.recv_batch(10, timeout).unwrap_err();
+    assert_eq!(io::ErrorKind::TimedOut, err.kind());
+
+    push.send_batch(make_batch(3)).expect("Push should have sent the batch");
+    let mut received = 0;
+    while received < 3 {
+        received += pull.recv_batch(10, timeout).expect("Pull should have received a batch").len();
+    }
+    assert_eq!(3, received);
+
+    assert!(pull.recv_batch(0, timeout).expect("Empty batch should succeed").is_empty());
+    assert_eq!(0, push.send_batch(Vec::new()).expect("Empty batch should succeed"));
+}
+
+#[test]
+fn recv_batch_returns_the_queued_messages_without_waiting_for_more() {
+    let _ = ::env_logger::init();
+    let mut session = make_session();
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let url = urls::tcp::get();
+
+    pull.bind(&url).unwrap();
+    push.connect(&url).unwrap();
+    sleep_some();
+
+    push.send_batch(make_batch(3)).expect("Push should have sent the batch");
+    sleep_some();
+
+    let msgs = pull.recv_batch(1000, None).expect("Pull should have received a batch");
+    assert_eq!(3, msgs.len());
+
+    push.send_batch(make_batch(2)).expect("Push should have sent the batch");
+    sleep_some();
+
+    let started = Instant::now();
+    let msgs = pull.recv_batch(1000, Some(Duration::from_secs(1))).expect("Pull should have received a batch");
+    assert_eq!(2, msgs.len());
+    assert!(started.elapsed() < Duration::from_millis(500));
+}
+
+#[test]
+fn send_batch_reports_an_error_when_nothing_was_sent() {
+    let _ = ::env_logger::init();
+    let mut session = make_session();
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+
+    push.set_send_timeout(Some(Duration::from_millis(50))).unwrap();
+
+    let err = push.send_batch(make_batch(3)).unwrap_err();
+    assert_eq!(io::ErrorKind::TimedOut, err.kind());
+}
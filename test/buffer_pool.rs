@@ -0,0 +1,72 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use scaproust::*;
+
+pub use super::urls;
+pub use super::make_timeout;
+
+fn make_session(pool_size: usize) -> Session {
+    SessionBuilder::new().
+        with("tcp", Tcp).
+        with("mem", Memory::new()).
+        with_buffer_pool_size(pool_size).
+        build().
+        expect("Failed to create session !")
+}
+
+fn check_recycled_buffer_is_reused(url: &str) {
+    let _ = ::env_logger::init();
+    let mut session = make_session(8);
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+
+    pull.set_recv_timeout(make_timeout()).unwrap();
+    pull.bind(url).unwrap();
+    push.connect(url).unwrap();
+
+    push.send(vec![65, 66, 67]).expect("Push should have sent the first message");
+    let first = pull.recv_msg().expect("Pull should have received the first message");
+    let buffer_addr = first.get_body().as_ptr();
+
+    pull.recycle(first);
+
+    push.send(vec![67, 66, 65]).expect("Push should have sent the second message");
+    let second = pull.recv_msg().expect("Pull should have received the second message");
+
+    assert_eq!(&[67, 66, 65], second.get_body());
+    assert_eq!(buffer_addr, second.get_body().as_ptr());
+}
+
+#[test]
+fn recycled_buffer_is_reused_by_tcp() {
+    check_recycled_buffer_is_reused(&urls::tcp::get());
+}
+
+#[test]
+fn recycled_buffer_is_reused_by_memory() {
+    check_recycled_buffer_is_reused("mem://pool");
+}
+
+#[test]
+fn recycle_is_harmless_when_the_pool_is_disabled() {
+    let _ = ::env_logger::init();
+    let mut session = make_session(0);
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+
+    pull.set_recv_timeout(make_timeout()).unwrap();
+    pull.bind("mem://no-pool").unwrap();
+    push.connect("mem://no-pool").unwrap();
+
+    push.send(vec![65, 66, 67]).expect("Push should have sent the first message");
+    let first = pull.recv().expect("Pull should have received the first message");
+
+    pull.recycle(Message::from(first));
+
+    push.send(vec![67, 66, 65]).expect("Push should have sent the second message");
+    assert_eq!(vec![67, 66, 65], pull.recv().expect("Pull should have received the second message"));
+}
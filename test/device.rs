@@ -148,4 +148,199 @@ fn forward_messages_back_and_forth() {
 
     drop(session);
     device_thread.join().unwrap().unwrap_err();
-}
\ No newline at end of file
+}
+
+#[test]
+fn filter_and_duplicate_forwarded_messages() {
+    let (mut session, timeout) = before_each();
+    let mut d_push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut d_pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+
+    let d_push_url = urls::tcp::get();
+    let d_pull_url = urls::tcp::get();
+
+    d_push.bind(&d_push_url).unwrap();
+    d_pull.bind(&d_pull_url).unwrap();
+
+    push.set_send_timeout(timeout).unwrap();
+    pull.set_recv_timeout(timeout).unwrap();
+
+    let hook = |direction: Direction, msg: Message| {
+        assert_eq!(Direction::LeftToRight, direction);
+        match msg.get_body()[0] {
+            b'D' => vec![],
+            b'2' => vec![Message::from_body(vec![b'x']), Message::from_body(vec![b'y'])],
+            _ => vec![msg]
+        }
+    };
+    let barrier = Arc::new(Barrier::new(2));
+    let d_barrier = barrier.clone();
+    let device = session.create_bridge_device_with(d_pull, d_push, hook).unwrap();
+    let device_thread = thread::spawn(move || {
+        d_barrier.wait();
+        let res = device.run();
+        res
+    });
+
+    barrier.wait();
+    sleep_some();
+
+    push.connect(&d_pull_url).unwrap();
+    pull.connect(&d_push_url).unwrap();
+    sleep_some();
+
+    push.send(vec![b'D']).expect("Push should have sent a message");
+    push.send(vec![b'2']).expect("Push should have sent a message");
+    push.send(vec![b'K']).expect("Push should have sent a message");
+
+    assert_eq!(vec![b'x'], pull.recv().expect("Pull should have received a message"));
+    assert_eq!(vec![b'y'], pull.recv().expect("Pull should have received a message"));
+    assert_eq!(vec![b'K'], pull.recv().expect("Pull should have received a message"));
+
+    let err = pull.recv().unwrap_err();
+    assert_eq!(io::ErrorKind::TimedOut, err.kind());
+
+    drop(session);
+    device_thread.join().unwrap().unwrap_err();
+}
+
+#[test]
+fn rewrite_messages_back_and_forth() {
+    let (mut session, timeout) = before_each();
+    let mut d_req = session.create_socket::<Req>().expect("Failed to create socket !");
+    let mut d_rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+    let mut req = session.create_socket::<Req>().expect("Failed to create socket !");
+    let mut rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+
+    let d_req_url = urls::tcp::get();
+    let d_rep_url = urls::tcp::get();
+
+    d_req.bind(&d_req_url).unwrap();
+    d_rep.bind(&d_rep_url).unwrap();
+
+    req.set_send_timeout(timeout).unwrap();
+    req.set_recv_timeout(timeout).unwrap();
+    rep.set_send_timeout(timeout).unwrap();
+    rep.set_recv_timeout(timeout).unwrap();
+
+    let hook = |direction: Direction, msg: Message| {
+        let mut body: Vec<u8> = msg.into();
+
+        match direction {
+            Direction::LeftToRight => body.push(b'>'),
+            Direction::RightToLeft => body.push(b'<')
+        }
+
+        vec![Message::from_body(body)]
+    };
+    let barrier = Arc::new(Barrier::new(2));
+    let d_barrier = barrier.clone();
+    let device = session.create_bridge_device_with(d_rep, d_req, hook).unwrap();
+    let device_thread = thread::spawn(move || {
+        d_barrier.wait();
+        let res = device.run();
+        res
+    });
+
+    barrier.wait();
+    sleep_some();
+
+    req.connect(&d_rep_url).unwrap();
+    rep.connect(&d_req_url).unwrap();
+    sleep_some();
+
+    req.send(vec![65, 66, 67]).expect("Req should have sent a request");
+    let received_request = rep.recv().expect("Rep should have received a request");
+    assert_eq!(vec![65, 66, 67, b'>'], received_request);
+
+    rep.send(vec![66, 65, 67]).expect("Rep should have sent a reply");
+    let received_reply = req.recv().expect("Req should have received a reply");
+    assert_eq!(vec![66, 65, 67, b'<'], received_reply);
+
+    drop(session);
+    device_thread.join().unwrap().unwrap_err();
+}
+
+#[test]
+fn spawned_device_forwards_messages() {
+    let (mut session, timeout) = before_each();
+    let mut d_push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut d_pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+
+    let d_push_url = urls::tcp::get();
+    let d_pull_url = urls::tcp::get();
+
+    d_push.bind(&d_push_url).unwrap();
+    d_pull.bind(&d_pull_url).unwrap();
+
+    push.set_send_timeout(timeout).unwrap();
+    pull.set_recv_timeout(timeout).unwrap();
+
+    let device = session.spawn_device(d_pull, d_push).unwrap();
+
+    push.connect(&d_pull_url).unwrap();
+    pull.connect(&d_push_url).unwrap();
+    sleep_some();
+
+    push.send(vec![65, 66, 67]).expect("Push should have sent a message");
+    push.send(vec![68, 69]).expect("Push should have sent a message");
+    assert_eq!(vec![65, 66, 67], pull.recv().expect("Pull should have received a message"));
+    assert_eq!(vec![68, 69], pull.recv().expect("Pull should have received a message"));
+
+    let counters = device.counters().unwrap();
+    assert_eq!(2, counters.left_to_right_msgs);
+    assert_eq!(5, counters.left_to_right_bytes);
+    assert_eq!(0, counters.right_to_left_msgs);
+
+    device.stop().unwrap();
+    sleep_some();
+
+    push.send(vec![65, 66, 67]).expect("Push should have sent a message");
+    let err = pull.recv().unwrap_err();
+    assert_eq!(io::ErrorKind::TimedOut, err.kind());
+}
+
+#[test]
+fn spawned_device_forwards_messages_back_and_forth() {
+    let (mut session, timeout) = before_each();
+    let mut d_req = session.create_socket::<Req>().expect("Failed to create socket !");
+    let mut d_rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+    let mut req = session.create_socket::<Req>().expect("Failed to create socket !");
+    let mut rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+
+    let d_req_url = urls::tcp::get();
+    let d_rep_url = urls::tcp::get();
+
+    d_req.bind(&d_req_url).unwrap();
+    d_rep.bind(&d_rep_url).unwrap();
+
+    req.set_send_timeout(timeout).unwrap();
+    req.set_recv_timeout(timeout).unwrap();
+    rep.set_send_timeout(timeout).unwrap();
+    rep.set_recv_timeout(timeout).unwrap();
+
+    let device = session.spawn_device(d_rep, d_req).unwrap();
+
+    req.connect(&d_rep_url).unwrap();
+    rep.connect(&d_req_url).unwrap();
+    sleep_some();
+
+    req.send(vec![65, 66, 67]).expect("Req should have sent a request");
+    let received_request = rep.recv().expect("Rep should have received a request");
+    assert_eq!(vec![65, 66, 67], received_request);
+
+    rep.send(vec![66, 65, 67]).expect("Rep should have sent a reply");
+    let received_reply = req.recv().expect("Req should have received a reply");
+    assert_eq!(vec![66, 65, 67], received_reply);
+
+    let counters = device.counters().unwrap();
+    assert_eq!(1, counters.left_to_right_msgs);
+    assert_eq!(1, counters.right_to_left_msgs);
+
+    drop(device);
+    drop(session);
+}
@@ -0,0 +1,141 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use std::time::{Duration, Instant};
+pub use std::thread;
+pub use std::io;
+pub use std::sync::mpsc;
+
+pub use scaproust::*;
+
+pub use super::urls;
+pub use super::make_timeout;
+
+// The driver runs on another thread, so the operations are tried again until it has processed them
+fn retry<T, F>(mut operation: F) -> io::Result<T> where F : FnMut() -> io::Result<T> {
+    loop {
+        match operation() {
+            Err(ref e) if ReplyPending::from_io_error(e).is_some() => thread::sleep(Duration::from_millis(1)),
+            res => return res
+        }
+    }
+}
+
+fn before_each() -> (Session, Driver) {
+    let _ = ::env_logger::init();
+
+    SessionBuilder::new().
+        with("tcp", Tcp).
+        with("ipc", Ipc).
+        build_manual().
+        expect("Failed to create session !")
+}
+
+#[test]
+fn exchange_messages_on_a_single_thread() {
+    let (mut session, mut driver) = before_each();
+    let mut push = driver.run_until(|| session.create_socket::<Push>()).expect("Failed to create socket !");
+    let mut pull = driver.run_until(|| session.create_socket::<Pull>()).expect("Failed to create socket !");
+    let url = urls::tcp::get();
+
+    driver.run_until(|| push.set_send_timeout(make_timeout())).unwrap();
+    driver.run_until(|| pull.set_recv_timeout(make_timeout())).unwrap();
+
+    driver.run_until(|| pull.bind(&url)).unwrap();
+    driver.run_until(|| push.connect(&url)).unwrap();
+
+    driver.run_until(|| push.send(vec![65, 66, 67])).expect("Push should have sent a message");
+    assert_eq!(vec![65, 66, 67], driver.run_until(|| pull.recv()).expect("Pull should have received a message"));
+
+    let err = driver.run_until(|| pull.recv()).unwrap_err();
+    assert_eq!(io::ErrorKind::TimedOut, err.kind());
+}
+
+#[test]
+fn operations_do_not_wait_for_the_driver() {
+    let (mut session, mut driver) = before_each();
+    let err = session.create_socket::<Pull>().err().expect("Reply should not be available yet");
+
+    assert_eq!(io::ErrorKind::WouldBlock, err.kind());
+    assert_eq!(Some(ReplyPending), ReplyPending::from_io_error(&err));
+
+    driver.run_once(Some(Duration::from_millis(0))).unwrap();
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let err = pull.bind("tcp://127.0.0.1:0").err().expect("Reply should not be available yet");
+
+    assert_eq!(io::ErrorKind::WouldBlock, err.kind());
+    assert_eq!(io::ErrorKind::Other, pull.recv().unwrap_err().kind());
+
+    driver.run_once(Some(Duration::from_millis(0))).unwrap();
+    pull.bind("tcp://127.0.0.1:0").expect("Pull should have been bound");
+}
+
+#[test]
+fn devices_are_not_supported() {
+    let (mut session, mut driver) = before_each();
+    let left = driver.run_until(|| session.create_socket::<Pull>()).expect("Failed to create socket !");
+    let right = driver.run_until(|| session.create_socket::<Push>()).expect("Failed to create socket !");
+    let err = session.spawn_device(left, right).err().expect("Device should not have been spawned");
+
+    assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+}
+
+#[test]
+fn run_once_returns_after_timeout() {
+    let (_session, mut driver) = before_each();
+    let timeout = Duration::from_millis(50);
+    let started = Instant::now();
+
+    driver.run_once(Some(timeout)).unwrap();
+
+    assert!(started.elapsed() >= timeout);
+    assert!(driver.is_running());
+}
+
+#[cfg(unix)]
+#[test]
+fn expose_poller_file_descriptor() {
+    use std::os::unix::io::AsRawFd;
+
+    let (_session, driver) = before_each();
+
+    assert!(driver.as_raw_fd() >= 0);
+}
+
+#[test]
+fn drive_session_used_from_another_thread() {
+    let (session, mut driver) = before_each();
+    let (tx, rx) = mpsc::channel();
+    let user_thread = thread::spawn(move || {
+        let mut session = session;
+        let mut pair1 = retry(|| session.create_socket::<Pair>()).expect("Failed to create socket !");
+        let mut pair2 = retry(|| session.create_socket::<Pair>()).expect("Failed to create socket !");
+        let url = urls::tcp::get();
+
+        retry(|| pair1.set_recv_timeout(make_timeout())).unwrap();
+        retry(|| pair2.bind(&url)).unwrap();
+        retry(|| pair1.connect(&url)).unwrap();
+        retry(|| pair2.send(vec![65, 66, 67])).unwrap();
+
+        tx.send(retry(|| pair1.recv()).unwrap()).unwrap();
+    });
+
+    while driver.is_running() {
+        driver.run_once(Some(Duration::from_millis(10))).unwrap();
+    }
+
+    user_thread.join().unwrap();
+    assert_eq!(vec![65, 66, 67], rx.recv().unwrap());
+}
+
+#[test]
+fn cannot_drive_several_io_threads() {
+    let result = SessionBuilder::new().
+        with_io_threads(2).
+        build_manual();
+
+    assert_eq!(io::ErrorKind::InvalidInput, result.err().unwrap().kind());
+}
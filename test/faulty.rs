@@ -0,0 +1,139 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use std::time::{Duration, Instant};
+
+pub use scaproust::*;
+use scaproust::testing::faulty::Faulty;
+
+pub use super::urls;
+pub use super::make_timeout;
+
+fn before_each(faulty: Faulty<Tcp>) -> Session {
+    let _ = ::env_logger::init();
+
+    SessionBuilder::new().
+        with("tcp", Tcp).
+        with("faulty", faulty).
+        build().
+        expect("Failed to create session !")
+}
+
+fn faulty_url() -> String {
+    urls::tcp::get().replace("tcp://", "faulty://")
+}
+
+fn create_pipeline(session: &mut Session, url: &str) -> (Socket, Socket) {
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+
+    push.set_send_timeout(make_timeout()).unwrap();
+    pull.set_recv_timeout(Some(Duration::from_millis(250))).unwrap();
+
+    pull.bind(&url.replace("faulty://", "tcp://")).unwrap();
+    push.connect(url).unwrap();
+
+    (push, pull)
+}
+
+fn transmit(seed: u64, count: u8) -> Vec<Vec<u8>> {
+    let mut session = before_each(Faulty::new(Tcp, seed).with_drop(0.5));
+    let (mut push, mut pull) = create_pipeline(&mut session, &faulty_url());
+    let mut received = Vec::new();
+
+    for i in 0..count {
+        push.send(vec![i]).expect("Push should have sent a message");
+    }
+    while let Ok(msg) = pull.recv() {
+        received.push(msg);
+    }
+
+    received
+}
+
+#[test]
+fn faulty_transport_without_faults_is_transparent() {
+    let mut session = before_each(Faulty::new(Tcp, 1));
+    let (mut push, mut pull) = create_pipeline(&mut session, &faulty_url());
+
+    push.send(vec![65, 66, 67]).expect("Push should have sent a message");
+    let received = pull.recv().expect("Pull should have received a message");
+    assert_eq!(vec![65, 66, 67], received);
+}
+
+#[test]
+fn faulty_transport_can_refuse_connections() {
+    let mut session = before_each(Faulty::new(Tcp, 1).with_connect_failure(1.0));
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+
+    match push.connect(&faulty_url()) {
+        Ok(_) => panic!("Connect should have failed"),
+        Err(err) => assert_eq!(::std::io::ErrorKind::ConnectionRefused, err.kind())
+    }
+}
+
+#[test]
+fn faulty_transport_can_corrupt_handshakes() {
+    let mut session = before_each(Faulty::new(Tcp, 1).with_handshake_corruption(1.0));
+    let (mut push, mut pull) = create_pipeline(&mut session, &faulty_url());
+
+    push.set_send_timeout(Some(Duration::from_millis(250))).unwrap();
+    assert!(push.send(vec![65, 66, 67]).is_err());
+    assert!(pull.recv().is_err());
+}
+
+#[test]
+fn faulty_transport_can_drop_messages() {
+    let mut session = before_each(Faulty::new(Tcp, 1).with_drop(1.0));
+    let (mut push, mut pull) = create_pipeline(&mut session, &faulty_url());
+
+    push.send(vec![65, 66, 67]).expect("Push should have sent a message");
+    push.send(vec![68, 69, 70]).expect("Push should have sent a message");
+    assert!(pull.recv().is_err());
+}
+
+#[test]
+fn faulty_transport_can_truncate_messages() {
+    let mut session = before_each(Faulty::new(Tcp, 1).with_truncation(1.0));
+    let (mut push, mut pull) = create_pipeline(&mut session, &faulty_url());
+
+    push.send(vec![65, 66, 67, 68]).expect("Push should have sent a message");
+    let received = pull.recv().expect("Pull should have received a message");
+    assert!(received.len() < 4);
+    assert_eq!(&[65, 66, 67, 68][..received.len()], &received[..]);
+}
+
+#[test]
+fn faulty_transport_can_delay_messages() {
+    let mut session = before_each(Faulty::new(Tcp, 1).with_delay(Duration::from_millis(150)));
+    let (mut push, mut pull) = create_pipeline(&mut session, &faulty_url());
+
+    pull.set_recv_timeout(make_timeout()).unwrap();
+    let start = Instant::now();
+    push.send(vec![65, 66, 67]).expect("Push should have sent a message");
+    let received = pull.recv().expect("Pull should have received a message");
+    assert_eq!(vec![65, 66, 67], received);
+    assert!(start.elapsed() >= Duration::from_millis(150));
+}
+
+#[test]
+fn faulty_transport_can_disconnect_while_sending() {
+    let mut session = before_each(Faulty::new(Tcp, 1).with_disconnect(1.0));
+    let (mut push, mut pull) = create_pipeline(&mut session, &faulty_url());
+
+    push.set_send_timeout(Some(Duration::from_millis(250))).unwrap();
+    assert!(push.send(vec![65, 66, 67]).is_err());
+    assert!(pull.recv().is_err());
+}
+
+#[test]
+fn faulty_transport_failures_are_reproducible() {
+    let first = transmit(7, 32);
+    let second = transmit(7, 32);
+
+    assert!(first.len() > 0 && first.len() < 32);
+    assert_eq!(first, second);
+}
@@ -0,0 +1,207 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use std::time::Duration;
+pub use std::thread;
+pub use std::sync::{Arc, Barrier, Mutex};
+
+pub use scaproust::*;
+
+pub use super::urls;
+pub use super::{make_hard_timeout, make_timeout, sleep_some};
+
+// Sockets are assigned to the I/O threads in turn,
+// so consecutive sockets of a two threads session live on different event loops.
+fn before_each() -> (Session, Option<Duration>) {
+    let _ = ::env_logger::init();
+    let session = SessionBuilder::new().
+        with("tcp", Tcp).
+        with("ipc", Ipc).
+        with_io_threads(2).
+        build().
+        expect("Failed to create session !");
+    let timeout = make_timeout();
+
+    (session, timeout)
+}
+
+#[test]
+fn start_each_io_thread() {
+    let indexes = Arc::new(Mutex::new(Vec::new()));
+    let hook_indexes = indexes.clone();
+    let session = SessionBuilder::new().
+        with_io_threads(4).
+        with_thread_hook(move |index| {
+            let name = thread::current().name().map(String::from);
+
+            assert_eq!(Some(format!("scaproust-io-{}", index)), name);
+            hook_indexes.lock().unwrap().push(index);
+        }).
+        build().
+        expect("Failed to create session !");
+
+    drop(session);
+
+    let mut indexes = indexes.lock().unwrap().clone();
+
+    indexes.sort();
+    assert_eq!(vec![0, 1, 2, 3], indexes);
+}
+
+#[test]
+fn dropping_the_session_waits_for_every_io_thread() {
+    let (mut session, _) = before_each();
+    let mut pull1 = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let mut pull2 = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let url1 = urls::tcp::get();
+    let url2 = urls::tcp::get();
+
+    pull1.bind(&url1).unwrap();
+    pull2.bind(&url2).unwrap();
+    drop(session);
+
+    let (mut session, _) = before_each();
+    let mut pull1 = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let mut pull2 = session.create_socket::<Pull>().expect("Failed to create socket !");
+
+    pull1.bind(&url1).expect("First address should have been released");
+    pull2.bind(&url2).expect("Second address should have been released");
+}
+
+#[test]
+fn exchange_messages_across_threads() {
+    let (mut session, timeout) = before_each();
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let mut push1 = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut push2 = session.create_socket::<Push>().expect("Failed to create socket !");
+    let url = urls::tcp::get();
+
+    pull.set_recv_timeout(timeout).unwrap();
+    push1.set_send_timeout(timeout).unwrap();
+    push2.set_send_timeout(timeout).unwrap();
+
+    pull.bind(&url).unwrap();
+    push1.connect(&url).unwrap();
+    push2.connect(&url).unwrap();
+    sleep_some();
+
+    push1.send(vec![65, 66, 67]).expect("Push should have sent a message");
+    assert_eq!(vec![65, 66, 67], pull.recv().expect("Pull should have received a message"));
+    push2.send(vec![68, 69]).expect("Push should have sent a message");
+    assert_eq!(vec![68, 69], pull.recv().expect("Pull should have received a message"));
+}
+
+#[test]
+fn bridge_sockets_living_on_different_threads() {
+    let (mut session, timeout) = before_each();
+    let mut d_req = session.create_socket::<Req>().expect("Failed to create socket !");
+    let mut d_rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+    let mut req = session.create_socket::<Req>().expect("Failed to create socket !");
+    let mut rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+
+    let d_req_url = urls::tcp::get();
+    let d_rep_url = urls::tcp::get();
+
+    d_req.bind(&d_req_url).unwrap();
+    d_rep.bind(&d_rep_url).unwrap();
+
+    req.set_send_timeout(timeout).unwrap();
+    req.set_recv_timeout(timeout).unwrap();
+    rep.set_send_timeout(timeout).unwrap();
+    rep.set_recv_timeout(timeout).unwrap();
+
+    let barrier = Arc::new(Barrier::new(2));
+    let d_barrier = barrier.clone();
+    let device = session.create_bridge_device(d_rep, d_req).unwrap();
+    let device_thread = thread::spawn(move || {
+        d_barrier.wait();
+        device.run()
+    });
+
+    barrier.wait();
+    sleep_some();
+
+    req.connect(&d_rep_url).unwrap();
+    rep.connect(&d_req_url).unwrap();
+    sleep_some();
+
+    req.send(vec![65, 66, 67]).expect("Req should have sent a request");
+    assert_eq!(vec![65, 66, 67], rep.recv().expect("Rep should have received a request"));
+
+    rep.send(vec![66, 65, 67]).expect("Rep should have sent a reply");
+    assert_eq!(vec![66, 65, 67], req.recv().expect("Req should have received a reply"));
+
+    drop(session);
+    device_thread.join().unwrap().unwrap_err();
+}
+
+#[test]
+fn spawn_device_on_sockets_living_on_different_threads() {
+    let (mut session, timeout) = before_each();
+    let mut d_req = session.create_socket::<Req>().expect("Failed to create socket !");
+    let mut d_rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+    let mut req = session.create_socket::<Req>().expect("Failed to create socket !");
+    let mut rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+
+    let d_req_url = urls::tcp::get();
+    let d_rep_url = urls::tcp::get();
+
+    d_req.bind(&d_req_url).unwrap();
+    d_rep.bind(&d_rep_url).unwrap();
+
+    req.set_send_timeout(timeout).unwrap();
+    req.set_recv_timeout(timeout).unwrap();
+    rep.set_send_timeout(timeout).unwrap();
+    rep.set_recv_timeout(timeout).unwrap();
+
+    let device = session.spawn_device(d_rep, d_req).unwrap();
+
+    req.connect(&d_rep_url).unwrap();
+    rep.connect(&d_req_url).unwrap();
+    sleep_some();
+
+    for _ in 0..10 {
+        req.send(vec![65, 66, 67]).expect("Req should have sent a request");
+        assert_eq!(vec![65, 66, 67], rep.recv().expect("Rep should have received a request"));
+
+        rep.send(vec![66, 65, 67]).expect("Rep should have sent a reply");
+        assert_eq!(vec![66, 65, 67], req.recv().expect("Req should have received a reply"));
+    }
+
+    let counters = device.counters().unwrap();
+    assert_eq!(10, counters.left_to_right_msgs);
+    assert_eq!(10, counters.right_to_left_msgs);
+
+    device.stop().unwrap();
+}
+
+#[test]
+fn probe_sockets_living_on_different_threads() {
+    let (mut session, _) = before_each();
+    let timeout = make_hard_timeout();
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let requests = vec![
+        push.create_poll_req(false, true), 
+        pull.create_poll_req(true, false)];
+    let mut probe = session.create_probe(requests).expect("Failed to create probe !");
+    let url = urls::tcp::get();
+
+    push.set_send_timeout(make_timeout()).expect("Failed to set send timeout !");
+    pull.set_recv_timeout(make_timeout()).expect("Failed to set recv timeout !");
+
+    push.bind(&url).unwrap();
+    pull.connect(&url).unwrap();
+
+    let poll_result = probe.poll(timeout).expect("Before send, poll should have succeed");
+    assert!(poll_result[0].send, "Before send, Push should be send ready");
+    assert!(!poll_result[1].recv, "Before send, Pull should not be recv ready");
+
+    push.send(vec![65, 66, 67]).expect("Failed to send a message !");
+
+    let poll_result = probe.poll(timeout).expect("After send, poll should have succeed");
+    assert!(poll_result[1].recv, "After send, Pull should be recv ready");
+}
@@ -0,0 +1,108 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use std::io;
+pub use std::panic;
+
+pub use scaproust::*;
+use scaproust::testing::ProtocolHarness;
+
+fn new_msg(body: &[u8]) -> Message {
+    Message::from_body(body.to_vec())
+}
+
+#[test]
+fn push_sends_to_ready_pipe_and_cancels_timeout() {
+    let mut harness = ProtocolHarness::<Push>::new();
+    let eid = EndpointId::from(1);
+
+    harness.add_pipe(eid);
+    harness.send_ready(eid);
+    assert_eq!((true, false), harness.readiness());
+
+    let timeout = harness.send_with_timeout(new_msg(b"ABC"));
+    harness.send_ack(eid);
+    harness.expect_send_reply();
+
+    assert_eq!(1, harness.context().sent().len());
+    assert!(harness.context().is_cancelled(timeout));
+}
+
+#[test]
+fn push_holds_message_until_a_pipe_is_ready() {
+    let mut harness = ProtocolHarness::<Push>::new();
+    let eid = EndpointId::from(1);
+
+    harness.add_pipe(eid);
+    harness.send(new_msg(b"ABC"));
+    harness.expect_no_reply();
+
+    harness.send_ready(eid);
+    harness.send_ack(eid);
+    harness.expect_send_reply();
+}
+
+#[test]
+fn pull_times_out_then_receives() {
+    let mut harness = ProtocolHarness::<Pull>::new();
+    let eid = EndpointId::from(1);
+
+    harness.add_pipe(eid);
+    harness.recv_with_timeout();
+    harness.recv_timeout();
+    harness.expect_err_reply(io::ErrorKind::TimedOut);
+
+    harness.recv_ready(eid);
+    assert_eq!((false, true), harness.readiness());
+
+    harness.recv();
+    harness.recv_ack(eid, new_msg(b"ABC"));
+    assert_eq!(b"ABC", harness.expect_recv_reply().get_body());
+}
+
+#[test]
+fn pull_rejects_send() {
+    let mut harness = ProtocolHarness::<Pull>::new();
+
+    harness.send(new_msg(b"ABC"));
+    harness.expect_err_reply(io::ErrorKind::Other);
+}
+
+#[test]
+fn pair_survives_pipe_removal() {
+    let mut harness = ProtocolHarness::<Pair>::new();
+    let first = EndpointId::from(1);
+    let second = EndpointId::from(2);
+
+    harness.add_pipe(first);
+    harness.send_ready(first);
+    harness.remove_pipe(first);
+    assert_eq!((false, false), harness.readiness());
+
+    harness.add_pipe(second);
+    harness.send_ready(second);
+    harness.send(new_msg(b"ABC"));
+    harness.send_ack(second);
+    harness.expect_send_reply();
+    harness.close();
+}
+
+#[test]
+fn harness_detects_an_unexpected_acknowledgement() {
+    let result = panic::catch_unwind(|| {
+        let mut harness = ProtocolHarness::<Push>::new();
+        let eid = EndpointId::from(1);
+
+        harness.add_pipe(eid);
+        harness.send_ready(eid);
+        harness.send(new_msg(b"ABC"));
+        harness.send_ack(eid);
+        // only one message has been sent to the pipe
+        harness.send_ack(eid);
+    });
+
+    assert!(result.is_err());
+}
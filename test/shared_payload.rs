@@ -0,0 +1,103 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use scaproust::*;
+use scaproust::testing::ProtocolHarness;
+
+pub use super::urls;
+pub use super::make_timeout;
+
+fn make_session() -> Session {
+    SessionBuilder::new().
+        with("tcp", Tcp).
+        with("mem", Memory::new()).
+        build().
+        expect("Failed to create session !")
+}
+
+#[test]
+fn shared_payload_can_be_sent_on_several_sockets() {
+    let _ = ::env_logger::init();
+    let mut session = make_session();
+    let mut push1 = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut push2 = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let url = urls::tcp::get();
+    let payload = Bytes::from(vec![65, 66, 67, 68, 69]);
+
+    pull.set_recv_timeout(make_timeout()).unwrap();
+    pull.bind(&url).unwrap();
+    push1.connect(&url).unwrap();
+    push2.connect(&url).unwrap();
+
+    push1.send_msg(Message::from(payload.clone())).expect("Push 1 should have sent the payload");
+    push2.send_msg(Message::from(payload.slice(1, 4))).expect("Push 2 should have sent a slice of the payload");
+
+    let mut received = vec![
+        pull.recv().expect("Pull should have received the first message"),
+        pull.recv().expect("Pull should have received the second message")];
+    received.sort();
+
+    assert_eq!(vec![vec![65, 66, 67, 68, 69], vec![66, 67, 68]], received);
+    assert_eq!(&[65, 66, 67, 68, 69], &*payload);
+}
+
+#[test]
+fn shared_payload_is_handed_to_the_pipes_without_copy() {
+    let mut harness = ProtocolHarness::<Push>::new();
+    let eid = EndpointId::from(1);
+    let payload = Bytes::from(vec![65, 66, 67, 68, 69]);
+
+    harness.add_pipe(eid);
+    harness.send_ready(eid);
+    harness.send(Message::from(payload.slice_from(1)));
+    harness.send_ack(eid);
+    harness.expect_send_reply();
+
+    let sent = harness.context().sent()[0].1.get_body();
+
+    assert_eq!(&[66, 67, 68, 69], sent);
+    assert_eq!(unsafe { payload.as_ptr().offset(1) }, sent.as_ptr());
+}
+
+#[test]
+#[allow(deprecated)]
+fn received_body_can_still_be_read_from_the_field() {
+    let _ = ::env_logger::init();
+    let mut session = make_session();
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+
+    pull.set_recv_timeout(make_timeout()).unwrap();
+    pull.bind("mem://body_field").unwrap();
+    push.connect("mem://body_field").unwrap();
+
+    push.send(vec![65, 66, 67]).expect("Push should have sent the message");
+    let msg = pull.recv_msg().expect("Pull should have received the message");
+
+    assert_eq!(vec![65, 66, 67], msg.body);
+}
+
+#[test]
+fn recv_into_copies_the_body_into_the_buffer() {
+    let _ = ::env_logger::init();
+    let mut session = make_session();
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let mut buffer = [0u8; 4];
+
+    pull.set_recv_timeout(make_timeout()).unwrap();
+    pull.bind("mem://recv_into").unwrap();
+    push.connect("mem://recv_into").unwrap();
+
+    push.send(vec![65, 66, 67]).expect("Push should have sent the first message");
+    assert_eq!(3, pull.recv_into(&mut buffer).expect("Pull should have received the first message"));
+    assert_eq!(&[65, 66, 67], &buffer[..3]);
+
+    push.send(vec![70, 71, 72, 73, 74, 75]).expect("Push should have sent the second message");
+    assert_eq!(6, pull.recv_into(&mut buffer).expect("Pull should have received the second message"));
+    assert_eq!(&[70, 71, 72, 73], &buffer);
+}
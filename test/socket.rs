@@ -166,3 +166,226 @@ fn try_recv_return_would_block_when_buffer_is_empty() {
     assert_eq!(io::ErrorKind::WouldBlock, err.kind());
     drop(session);
 }
+
+#[test]
+fn generic_option_can_be_read_back() {
+    let (mut session, _) = before_each();
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let timeout = Some(Duration::from_millis(50));
+
+    match push.get_option(ConfigOptionKind::SendTimeout).unwrap() {
+        ConfigOption::SendTimeout(x) => assert_eq!(None, x),
+        _ => panic!("Unexpected option value")
+    }
+
+    push.set_send_timeout(timeout).unwrap();
+    push.set_option(ConfigOption::RecvMaxSize(512)).unwrap();
+
+    match push.get_option(ConfigOptionKind::SendTimeout).unwrap() {
+        ConfigOption::SendTimeout(x) => assert_eq!(timeout, x),
+        _ => panic!("Unexpected option value")
+    }
+    match push.get_option(ConfigOptionKind::RecvMaxSize).unwrap() {
+        ConfigOption::RecvMaxSize(x) => assert_eq!(512, x),
+        _ => panic!("Unexpected option value")
+    }
+    drop(session);
+}
+
+#[test]
+fn subscriptions_can_be_read_back() {
+    let (mut session, _) = before_each();
+    let mut sub = session.create_socket::<Sub>().expect("Failed to create socket !");
+
+    sub.set_option(ConfigOption::Subscribe(String::from("A"))).unwrap();
+
+    match sub.get_option(ConfigOptionKind::Subscriptions).unwrap() {
+        ConfigOption::Subscriptions(x) => assert_eq!(vec![String::from("A")], x),
+        _ => panic!("Unexpected option value")
+    }
+
+    let err = sub.set_option(ConfigOption::Subscriptions(Vec::new())).unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    drop(session);
+}
+
+#[test]
+fn linger_is_rejected_until_supported() {
+    let (mut session, _) = before_each();
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+
+    let err = push.set_option(ConfigOption::Linger(Duration::from_millis(200))).unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+
+    let err = push.get_option(ConfigOptionKind::Linger).unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    drop(session);
+}
+
+#[test]
+fn tcp_keepalive_is_applied_to_connections() {
+    let (mut session, url) = before_each();
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let keepalive = TcpKeepalive {
+        idle: Duration::from_secs(30),
+        interval: Duration::from_secs(5),
+        count: 3
+    };
+
+    push.set_option(ConfigOption::TcpKeepalive(Some(keepalive))).unwrap();
+    pull.set_option(ConfigOption::TcpKeepalive(Some(keepalive))).unwrap();
+
+    match push.get_option(ConfigOptionKind::TcpKeepalive).unwrap() {
+        ConfigOption::TcpKeepalive(x) => assert_eq!(Some(keepalive), x),
+        _ => panic!("Unexpected option value")
+    }
+
+    pull.bind(&url).unwrap();
+    push.connect(&url).unwrap();
+    push.send(vec![65, 66, 67]).expect("Push should have sent a message");
+    assert_eq!(vec![65, 66, 67], pull.recv().expect("Pull should have received a message"));
+    drop(session);
+}
+
+#[test]
+fn protocol_option_can_be_read_back() {
+    let (mut session, _) = before_each();
+    let mut req = session.create_socket::<Req>().expect("Failed to create socket !");
+    let ivl = Duration::from_millis(500);
+
+    req.set_option(ConfigOption::ReqResendIvl(ivl)).unwrap();
+
+    match req.get_option(ConfigOptionKind::ReqResendIvl).unwrap() {
+        ConfigOption::ReqResendIvl(x) => assert_eq!(ivl, x),
+        _ => panic!("Unexpected option value")
+    }
+
+    let err = req.get_option(ConfigOptionKind::SurveyDeadline).unwrap_err();
+
+    assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    drop(session);
+}
+
+#[test]
+fn bind_to_ephemeral_port_reports_actual_address() {
+    let (mut session, _) = before_each();
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+
+    let bound = pull.bind("tcp://127.0.0.1:0").unwrap();
+    let local_addr = bound.local_addr().expect("Bound endpoint should have a local address");
+
+    assert!(!local_addr.ends_with(":0"));
+    assert_eq!(format!("tcp://{}", local_addr), bound.url());
+    assert_eq!(None, bound.peer_addr());
+
+    let connected = push.connect(bound.url()).unwrap();
+
+    assert_eq!(Some(local_addr.clone()), connected.peer_addr());
+
+    sleep_some();
+    push.set_send_timeout(make_timeout()).unwrap();
+    pull.set_recv_timeout(make_timeout()).unwrap();
+    push.send(vec![65, 66, 67]).unwrap();
+    assert_eq!(vec![65, 66, 67], pull.recv().unwrap());
+    drop(session);
+}
+
+#[test]
+fn connected_endpoint_addresses_are_refreshed_on_reconnect() {
+    let (mut session, _) = before_each();
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let listener = ::std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let connected = push.connect(&format!("tcp://{}", addr)).unwrap();
+    let (first, _) = listener.accept().unwrap();
+    sleep_some();
+    assert_eq!(Some(first.peer_addr().unwrap().to_string()), connected.local_addr());
+
+    // the handshake never completes, so push reconnects once the connection is closed
+    drop(first);
+    let (second, _) = listener.accept().unwrap();
+    sleep_some();
+    assert_eq!(Some(second.peer_addr().unwrap().to_string()), connected.local_addr());
+    assert_eq!(Some(addr.to_string()), connected.peer_addr());
+    drop(session);
+}
+
+#[test]
+fn recv_with_info_reports_tcp_peer() {
+    let (mut session, url) = before_each();
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+
+    pull.bind(&url).unwrap();
+    let connected = push.connect(&url).unwrap();
+    let push_addr = connected.local_addr();
+
+    sleep_some();
+    push.set_send_timeout(make_timeout()).unwrap();
+    pull.set_recv_timeout(make_timeout()).unwrap();
+    push.send(vec![65, 66, 67]).unwrap();
+
+    let (received, info) = pull.recv_with_info().unwrap();
+    let info = info.expect("Received message should come with pipe info");
+
+    assert_eq!(vec![65, 66, 67], received);
+    assert_eq!("tcp", info.scheme());
+    assert_eq!(push_addr.as_ref().map(|addr| addr.as_str()), info.peer_addr());
+    assert_eq!(None, info.peer_cred());
+    drop(session);
+}
+
+#[test]
+fn recv_with_info_reports_the_requester_to_rep() {
+    let (mut session, url) = before_each();
+    let mut req = session.create_socket::<Req>().expect("Failed to create socket !");
+    let mut rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+
+    rep.bind(&url).unwrap();
+    let connected = req.connect(&url).unwrap();
+
+    sleep_some();
+    req.set_send_timeout(make_timeout()).unwrap();
+    req.set_recv_timeout(make_timeout()).unwrap();
+    rep.set_send_timeout(make_timeout()).unwrap();
+    rep.set_recv_timeout(make_timeout()).unwrap();
+    req.send(vec![65]).unwrap();
+
+    let (received, info) = rep.recv_with_info().unwrap();
+    let info = info.expect("Received request should come with pipe info");
+
+    assert_eq!(vec![65], received);
+    assert_eq!(connected.local_addr().as_ref().map(|addr| addr.as_str()), info.peer_addr());
+
+    rep.send(vec![66]).unwrap();
+    assert_eq!(vec![66], req.recv().unwrap());
+    drop(session);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn recv_with_info_reports_ipc_peer_credentials() {
+    let (mut session, _) = before_each();
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let url = urls::ipc::get();
+
+    pull.bind(&url).unwrap();
+    push.connect(&url).unwrap();
+
+    sleep_some();
+    push.set_send_timeout(make_timeout()).unwrap();
+    pull.set_recv_timeout(make_timeout()).unwrap();
+    push.send(vec![65, 66, 67]).unwrap();
+
+    let (_, info) = pull.recv_with_info().unwrap();
+    let info = info.expect("Received message should come with pipe info");
+    let cred = info.peer_cred().expect("IPC pipe info should have credentials");
+
+    assert_eq!("ipc", info.scheme());
+    assert_eq!(::std::process::id() as i32, cred.pid);
+    drop(session);
+}
@@ -0,0 +1,130 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use std::io;
+pub use std::net;
+pub use std::str::FromStr;
+
+use mio::{Evented, Poll, Token, Ready, PollOpt};
+use mio::tcp::{TcpListener, TcpStream};
+
+pub use scaproust::*;
+use scaproust::transport::{Transport, Destination};
+use scaproust::transport::pipe::Pipe;
+use scaproust::transport::acceptor::Acceptor;
+use scaproust::transport::async::AsyncPipe;
+use scaproust::transport::stream::{StreamPipeStub, StreamAcceptor, Listener};
+
+pub use super::urls;
+pub use super::make_timeout;
+
+// A tcp transport written outside of the crate, with the generic stream building blocks.
+pub struct Plain;
+
+struct PlainListener(TcpListener);
+
+impl Evented for PlainListener {
+    fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        self.0.register(poll, token, interest, opts)
+    }
+    fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        self.0.reregister(poll, token, interest, opts)
+    }
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        self.0.deregister(poll)
+    }
+}
+
+impl Listener for PlainListener {
+    type Stream = TcpStream;
+
+    fn accept(&mut self) -> io::Result<Option<(TcpStream, Option<String>)>> {
+        self.0.accept().map(|(stream, addr)| Some((stream, Some(addr.to_string()))))
+    }
+    fn local_addr(&self) -> Option<String> {
+        self.0.local_addr().ok().map(|addr| addr.to_string())
+    }
+}
+
+fn parse_addr(dest: &Destination) -> io::Result<net::SocketAddr> {
+    net::SocketAddr::from_str(dest.addr).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, dest.addr))
+}
+
+impl Transport for Plain {
+    fn connect(&self, dest: &Destination) -> io::Result<Box<Pipe>> {
+        let addr = try!(parse_addr(dest));
+        let stream = try!(TcpStream::connect(&addr));
+        let stub = StreamPipeStub::new(stream, dest.recv_max_size).with_addrs(None, Some(addr.to_string()));
+
+        Ok(Box::new(AsyncPipe::new(stub, dest.pids)))
+    }
+    fn bind(&self, dest: &Destination) -> io::Result<Box<Acceptor>> {
+        let addr = try!(parse_addr(dest));
+        let listener = try!(TcpListener::bind(&addr));
+
+        Ok(Box::new(StreamAcceptor::new(PlainListener(listener), dest)))
+    }
+}
+
+fn before_each() -> Session {
+    let _ = ::env_logger::init();
+
+    SessionBuilder::new().
+        with("tcp", Tcp).
+        with("plain", Plain).
+        build().
+        expect("Failed to create session !")
+}
+
+fn plain_url() -> String {
+    urls::tcp::get().replace("tcp://", "plain://")
+}
+
+#[test]
+fn exchange_messages_over_a_user_defined_transport() {
+    let mut session = before_each();
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let url = plain_url();
+
+    push.set_send_timeout(make_timeout()).unwrap();
+    pull.set_recv_timeout(make_timeout()).unwrap();
+
+    pull.bind(&url).unwrap();
+    push.connect(&url).unwrap();
+
+    push.send(vec![65, 66, 67]).expect("Push should have sent a message");
+    let received = pull.recv().expect("Pull should have received a message");
+    assert_eq!(vec![65, 66, 67], received);
+
+    push.send(vec![]).expect("Push should have sent an empty message");
+    let received = pull.recv().expect("Pull should have received an empty message");
+    assert!(received.is_empty());
+}
+
+#[test]
+fn user_defined_stream_transport_talks_to_tcp() {
+    let mut session = before_each();
+    let mut req = session.create_socket::<Req>().expect("Failed to create socket !");
+    let mut rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+    let url = plain_url();
+
+    req.set_send_timeout(make_timeout()).unwrap();
+    req.set_recv_timeout(make_timeout()).unwrap();
+    rep.set_send_timeout(make_timeout()).unwrap();
+    rep.set_recv_timeout(make_timeout()).unwrap();
+
+    rep.bind(&url).unwrap();
+    req.connect(&url.replace("plain://", "tcp://")).unwrap();
+
+    req.send(vec![65, 66, 67]).expect("Req should have sent a request");
+    let request = rep.recv().expect("Rep should have received a request");
+    assert_eq!(vec![65, 66, 67], request);
+
+    rep.send(vec![67, 66, 65]).expect("Rep should have sent a reply");
+    let reply = req.recv().expect("Req should have received a reply");
+    assert_eq!(vec![67, 66, 65], reply);
+}
@@ -5,79 +5,126 @@
 // This file may not be copied, modified, or distributed except according to those terms.
 
 pub use std::time::Duration;
-pub use std::thread;
 pub use std::io;
 
 pub use scaproust::*;
 
-pub use super::{urls, make_session, make_timeout, sleep_some};
-
-fn before_each() -> (Session, Socket, Socket, Socket, String) {
+// The survey deadline only expires when the virtual clock is advanced,
+// so the sockets of each test are driven on the test thread, over in-memory connections.
+fn before_each() -> (Session, Driver, VirtualClock, Socket, Socket, Socket) {
     let _ = ::env_logger::init();
-    let mut session = make_session();
-    let mut surv = session.create_socket::<Surveyor>().expect("Failed to create socket !");
-    let mut resp1 = session.create_socket::<Respondent>().expect("Failed to create socket !");
-    let mut resp2 = session.create_socket::<Respondent>().expect("Failed to create socket !");
-    let url = urls::tcp::get();
-    let timeout = make_timeout();
-
-    surv.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
-
-    resp1.set_send_timeout(timeout).expect("Failed to set recv timeout !");
-    resp1.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
-
-    resp2.set_send_timeout(timeout).expect("Failed to set recv timeout !");
-    resp2.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
-
-    (session, surv, resp1, resp2, url)
+    let clock = VirtualClock::new();
+    let (mut session, mut driver) = SessionBuilder::new().
+        with("mem", Memory::new()).
+        with_virtual_clock(clock.clone()).
+        build_manual().
+        expect("Failed to create session !");
+    let mut surv = driver.run_until(|| session.create_socket::<Surveyor>()).expect("Failed to create socket !");
+    let mut resp1 = driver.run_until(|| session.create_socket::<Respondent>()).expect("Failed to create socket !");
+    let mut resp2 = driver.run_until(|| session.create_socket::<Respondent>()).expect("Failed to create socket !");
+
+    driver.run_until(|| surv.bind("mem://survey")).unwrap();
+    driver.run_until(|| resp1.connect("mem://survey")).unwrap();
+    driver.run_until(|| resp2.connect("mem://survey")).unwrap();
+    driver.run_until_idle().unwrap();
+
+    (session, driver, clock, surv, resp1, resp2)
 }
 
 #[test]
 fn send_a_survey_and_receive_several_responses() {
-    let (session, mut surv, mut resp1, mut resp2, url) = before_each();
-    surv.bind(&url).unwrap();
-    resp1.connect(&url).unwrap();
-    resp2.connect(&url).unwrap();
-
-    sleep_some();
+    let (_session, mut driver, _, mut surv, mut resp1, mut resp2) = before_each();
 
     let sent_survey = vec![65, 66, 67];
-    surv.send(sent_survey).expect("Surveyor should have sent a survey");
-    let received_survey1 = resp1.recv().expect("Respondent 1 should have received a survey");
-    let received_survey2 = resp2.recv().expect("Respondent 2 should have received a survey");
+    driver.run_until(|| surv.send(sent_survey.clone())).expect("Surveyor should have sent a survey");
+    let received_survey1 = driver.run_until(|| resp1.recv()).expect("Respondent 1 should have received a survey");
+    let received_survey2 = driver.run_until(|| resp2.recv()).expect("Respondent 2 should have received a survey");
     assert_eq!(vec![65, 66, 67], received_survey1);
     assert_eq!(vec![65, 66, 67], received_survey2);
 
-    let sent_response1 = vec![66, 67, 65];
-    let sent_response2 = vec![65, 67, 66];
-    resp1.send(sent_response1).expect("Respondent 1 should have sent a response");
-    resp2.send(sent_response2).expect("Respondent 2 should have sent a response");
+    driver.run_until(|| resp1.send(vec![66, 67, 65])).expect("Respondent 1 should have sent a response");
+    driver.run_until(|| resp2.send(vec![65, 67, 66])).expect("Respondent 2 should have sent a response");
 
-    surv.recv().expect("Surveyor should have received response #1");
-    surv.recv().expect("Surveyor should have received response #2");
-    drop(session);
+    driver.run_until(|| surv.recv()).expect("Surveyor should have received response #1");
+    driver.run_until(|| surv.recv()).expect("Surveyor should have received response #2");
 }
 
 #[test]
 fn refuse_to_receive_a_response_before_sending_a_survey() {
-    let (session, mut surv, mut resp1, mut resp2, url) = before_each();
-    surv.bind(&url).unwrap();
-    resp1.connect(&url).unwrap();
-    resp2.connect(&url).unwrap();
+    let (_session, mut driver, _, mut surv, _, _) = before_each();
 
-    let not_received = surv.recv().unwrap_err();
+    let not_received = driver.run_until(|| surv.recv()).unwrap_err();
     assert_eq!(io::ErrorKind::Other, not_received.kind());
-    drop(session);
 }
 
 #[test]
 fn refuse_to_send_a_response_before_receiving_a_survey() {
-    let (session, mut surv, mut resp1, mut resp2, url) = before_each();
-    surv.bind(&url).unwrap();
-    resp1.connect(&url).unwrap();
-    resp2.connect(&url).unwrap();
+    let (_session, mut driver, _, _, mut resp1, _) = before_each();
 
-    let not_sent = resp1.send(vec![66, 65, 67]).unwrap_err();
+    let not_sent = driver.run_until(|| resp1.send(vec![66, 65, 67])).unwrap_err();
     assert_eq!(io::ErrorKind::InvalidData, not_sent.kind());
-    drop(session);
+}
+
+#[test]
+fn survey_deadline_expires_when_the_clock_advances() {
+    let (_session, mut driver, clock, mut surv, mut resp1, mut resp2) = before_each();
+    let deadline = Duration::from_secs(5);
+
+    driver.run_until(|| surv.set_option(ConfigOption::SurveyDeadline(deadline))).unwrap();
+    driver.run_until(|| surv.send(vec![65, 66, 67])).expect("Surveyor should have sent a survey");
+    assert_eq!(vec![65, 66, 67], driver.run_until(|| resp1.recv()).expect("Respondent 1 should have received the survey"));
+    assert_eq!(vec![65, 66, 67], driver.run_until(|| resp2.recv()).expect("Respondent 2 should have received the survey"));
+    driver.run_until(|| resp1.send(vec![1])).expect("Respondent 1 should have sent a response");
+    assert_eq!(vec![1], driver.run_until(|| surv.recv()).expect("Surveyor should have received response #1"));
+
+    clock.advance(deadline - Duration::from_millis(1));
+    driver.run_until_idle().unwrap();
+    driver.run_until(|| resp2.send(vec![2])).expect("Respondent 2 should have sent a response");
+    assert_eq!(vec![2], driver.run_until(|| surv.recv()).expect("Surveyor should have received response #2"));
+
+    clock.advance(Duration::from_millis(1));
+    driver.run_until_idle().unwrap();
+    let err = driver.run_until(|| surv.recv()).unwrap_err();
+    assert_eq!(io::ErrorKind::Other, err.kind());
+}
+
+#[test]
+fn response_arriving_after_the_deadline_is_dropped() {
+    let (_session, mut driver, clock, mut surv, mut resp1, mut resp2) = before_each();
+    let deadline = Duration::from_secs(5);
+
+    driver.run_until(|| surv.set_option(ConfigOption::SurveyDeadline(deadline))).unwrap();
+    driver.run_until(|| surv.send(vec![65])).expect("Surveyor should have sent a survey");
+    driver.run_until(|| resp1.recv()).expect("Respondent 1 should have received the survey");
+    driver.run_until(|| resp2.recv()).expect("Respondent 2 should have received the survey");
+
+    clock.advance(deadline);
+    driver.run_until_idle().unwrap();
+    driver.run_until(|| resp1.send(vec![1])).expect("Respondent 1 should have sent a late response");
+    driver.run_until_idle().unwrap();
+
+    driver.run_until(|| surv.send(vec![66])).expect("Surveyor should have sent another survey");
+    driver.run_until(|| resp2.recv()).expect("Respondent 2 should have received the second survey");
+    driver.run_until(|| resp2.send(vec![2])).expect("Respondent 2 should have sent a response");
+    assert_eq!(vec![2], driver.run_until(|| surv.recv()).expect("Surveyor should have received the current response only"));
+}
+
+#[test]
+fn recv_timeout_expires_before_the_deadline() {
+    let (_session, mut driver, clock, mut surv, _, _) = before_each();
+    let timeout = Duration::from_secs(1);
+
+    driver.run_until(|| surv.set_option(ConfigOption::SurveyDeadline(Duration::from_secs(5)))).unwrap();
+    driver.run_until(|| surv.set_recv_timeout(Some(timeout))).unwrap();
+    driver.run_until(|| surv.send(vec![65])).expect("Surveyor should have sent a survey");
+
+    // the recv is pending until the clock expires its timeout
+    let err = surv.recv().unwrap_err();
+    assert_eq!(Some(ReplyPending), ReplyPending::from_io_error(&err));
+    driver.run_until_idle().unwrap();
+
+    clock.advance(timeout);
+    driver.run_until_idle().unwrap();
+    let err = surv.recv().unwrap_err();
+    assert_eq!(io::ErrorKind::TimedOut, err.kind());
 }
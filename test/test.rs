@@ -8,6 +8,7 @@ extern crate log;
 extern crate env_logger;
 extern crate scaproust;
 extern crate rand;
+extern crate mio;
 
 mod socket;
 mod fair_queue;
@@ -20,6 +21,16 @@ mod bus;
 mod device;
 mod reqrep_device;
 mod probe;
+mod io_threads;
+mod driver;
+mod stream_transport;
+mod protocol_harness;
+mod transport_conformance;
+mod faulty;
+mod virtual_clock;
+mod buffer_pool;
+mod shared_payload;
+mod batch;
 
 pub use std::time::Duration;
 pub use std::thread;
@@ -112,6 +123,57 @@ fn can_use_custom_transport() {
         expect("Failed to create session !");
 }
 
+#[test]
+fn can_tune_timer_and_event_loop() {
+    let mut session = SessionBuilder::new().
+        with("tcp", Tcp).
+        with_timer_tick(Duration::from_millis(1)).
+        with_timer_slots(256).
+        with_timer_capacity(1_024).
+        with_event_capacity(64).
+        build().
+        expect("Failed to create session !");
+    let mut pull = session.create_socket::<Pull>().unwrap();
+    let timeout = Duration::from_millis(5);
+
+    pull.bind(&urls::tcp::get()).unwrap();
+    pull.set_recv_timeout(Some(timeout)).unwrap();
+
+    let started = ::std::time::Instant::now();
+    let err = pull.recv().unwrap_err();
+    let elapsed = started.elapsed();
+
+    assert_eq!(io::ErrorKind::TimedOut, err.kind());
+    assert!(elapsed >= timeout);
+}
+
+#[test]
+fn cannot_use_zero_timer_tick() {
+    let result = SessionBuilder::new().
+        with_timer_tick(Duration::from_millis(0)).
+        build();
+    let err = result.err().unwrap();
+
+    assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+}
+
+#[test]
+fn can_name_and_hook_io_thread() {
+    let (tx, rx) = ::std::sync::mpsc::channel();
+    let _session = SessionBuilder::new().
+        with_thread_name("custom-io").
+        with_thread_hook(move |_| {
+            let name = thread::current().name().map(String::from);
+            tx.send(name).unwrap();
+        }).
+        build().
+        expect("Failed to create session !");
+
+    let name = rx.recv_timeout(make_hard_timeout()).unwrap();
+
+    assert_eq!(Some(String::from("custom-io")), name);
+}
+
 struct Subway;
 
 impl transport::Transport for Subway {
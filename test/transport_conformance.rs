@@ -0,0 +1,67 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use scaproust::*;
+use scaproust::testing::transport::*;
+
+pub use super::urls;
+use super::stream_transport::Plain;
+
+const TCP_ADDR: &'static str = "127.0.0.1:0";
+
+fn ipc_addr() -> String {
+    urls::ipc::get().replace("ipc://", "")
+}
+
+#[test]
+fn tcp_connect_and_bind() {
+    check_connect_and_bind(Tcp, TCP_ADDR);
+}
+
+#[test]
+fn tcp_send_and_recv() {
+    check_send_and_recv(Tcp, TCP_ADDR);
+}
+
+#[test]
+fn tcp_handshake_mismatch() {
+    check_handshake_mismatch(Tcp, TCP_ADDR);
+}
+
+#[test]
+fn tcp_partial_reads_and_writes() {
+    check_partial_reads_and_writes(Tcp, TCP_ADDR);
+}
+
+#[test]
+fn tcp_recv_max_size() {
+    check_recv_max_size(Tcp, TCP_ADDR);
+}
+
+#[test]
+fn tcp_peer_close_mid_message() {
+    check_peer_close_mid_message(Tcp, TCP_ADDR);
+}
+
+#[test]
+fn tcp_accept_burst() {
+    check_accept_burst(Tcp, TCP_ADDR);
+}
+
+#[test]
+fn ipc_conforms() {
+    check_all(|| Ipc, ipc_addr);
+}
+
+#[test]
+fn user_defined_stream_transport_conforms() {
+    check_all(|| Plain, || TCP_ADDR.to_owned());
+}
+
+#[test]
+fn memory_conforms() {
+    check_all(Memory::new, || "conformance".to_owned());
+}
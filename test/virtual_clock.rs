@@ -0,0 +1,305 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use std::time::Duration;
+pub use std::thread;
+pub use std::io;
+pub use std::sync::Arc;
+pub use std::sync::atomic::{AtomicBool, Ordering};
+
+pub use scaproust::*;
+use scaproust::testing::faulty::Faulty;
+
+fn before_each() -> (Session, Driver, VirtualClock) {
+    let _ = ::env_logger::init();
+    let clock = VirtualClock::new();
+    let (session, driver) = SessionBuilder::new().
+        with("mem", Memory::new()).
+        with_virtual_clock(clock.clone()).
+        build_manual().
+        expect("Failed to create session !");
+
+    (session, driver, clock)
+}
+
+#[test]
+fn req_resends_request_when_the_clock_advances() {
+    let (mut session, mut driver, clock) = before_each();
+    let mut req = driver.run_until(|| session.create_socket::<Req>()).expect("Failed to create socket !");
+    let mut rep = driver.run_until(|| session.create_socket::<Rep>()).expect("Failed to create socket !");
+    let resend_ivl = Duration::from_secs(60);
+
+    driver.run_until(|| req.set_option(ConfigOption::ReqResendIvl(resend_ivl))).unwrap();
+    driver.run_until(|| rep.bind("mem://server")).unwrap();
+    driver.run_until(|| req.connect("mem://server")).unwrap();
+
+    driver.run_until(|| req.send(vec![65, 66, 67])).expect("Req should have sent a request");
+    assert_eq!(vec![65, 66, 67], driver.run_until(|| rep.recv()).expect("Rep should have received the request"));
+
+    clock.advance(resend_ivl);
+    assert_eq!(vec![65, 66, 67], driver.run_until(|| rep.recv()).expect("Rep should have received the request again"));
+
+    driver.run_until(|| rep.send(vec![67, 66, 65])).expect("Rep should have sent a reply");
+    assert_eq!(vec![67, 66, 65], driver.run_until(|| req.recv()).expect("Req should have received the reply"));
+}
+
+#[test]
+fn reconnect_happens_when_the_clock_advances() {
+    let (mut session, mut driver, clock) = before_each();
+    let mut push = driver.run_until(|| session.create_socket::<Push>()).expect("Failed to create socket !");
+    let mut pull = driver.run_until(|| session.create_socket::<Pull>()).expect("Failed to create socket !");
+
+    driver.run_until(|| push.connect("mem://late")).unwrap();
+    driver.run_until_idle().unwrap();
+    driver.run_until(|| pull.bind("mem://late")).unwrap();
+    driver.run_until_idle().unwrap();
+
+    clock.advance(Duration::from_secs(1));
+    driver.run_until(|| push.send(vec![65, 66, 67])).expect("Push should have sent a message");
+    assert_eq!(vec![65, 66, 67], driver.run_until(|| pull.recv()).expect("Pull should have received a message"));
+}
+
+#[test]
+fn recv_timeout_only_expires_when_the_clock_advances() {
+    let _ = ::env_logger::init();
+    let clock = VirtualClock::new();
+    let mut session = SessionBuilder::new().
+        with("mem", Memory::new()).
+        with_virtual_clock(clock.clone()).
+        build().
+        expect("Failed to create session !");
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let timeout = Duration::from_secs(3600);
+    let done = Arc::new(AtomicBool::new(false));
+    let advancer = {
+        let clock = clock.clone();
+        let done = done.clone();
+
+        thread::spawn(move || while !done.load(Ordering::SeqCst) {
+            clock.advance(Duration::from_secs(60));
+            thread::sleep(Duration::from_millis(1));
+        })
+    };
+
+    pull.set_recv_timeout(Some(timeout)).unwrap();
+    pull.bind("mem://nobody").unwrap();
+
+    let err = pull.recv().unwrap_err();
+    done.store(true, Ordering::SeqCst);
+    advancer.join().unwrap();
+
+    assert_eq!(io::ErrorKind::TimedOut, err.kind());
+    assert!(clock.elapsed() >= timeout);
+}
+
+#[test]
+fn memory_addresses_are_bound_once() {
+    let (mut session, mut driver, _) = before_each();
+    let mut pull1 = driver.run_until(|| session.create_socket::<Pull>()).expect("Failed to create socket !");
+    let mut pull2 = driver.run_until(|| session.create_socket::<Pull>()).expect("Failed to create socket !");
+
+    driver.run_until(|| pull1.bind("mem://taken")).unwrap();
+    match driver.run_until(|| pull2.bind("mem://taken")) {
+        Ok(_) => panic!("Second bind should have failed"),
+        Err(err) => assert_eq!(io::ErrorKind::AddrInUse, err.kind())
+    }
+}
+
+#[test]
+fn idle_pipe_is_closed_and_reconnected() {
+    let (mut session, mut driver, clock) = before_each();
+    let mut push = driver.run_until(|| session.create_socket::<Push>()).expect("Failed to create socket !");
+    let mut pull = driver.run_until(|| session.create_socket::<Pull>()).expect("Failed to create socket !");
+    let timeout = Duration::from_secs(10);
+
+    driver.run_until(|| push.set_option(ConfigOption::LivenessTimeout(Some(timeout)))).unwrap();
+    driver.run_until(|| pull.bind("mem://idle")).unwrap();
+    driver.run_until(|| push.connect("mem://idle")).unwrap();
+    driver.run_until_idle().unwrap();
+
+    driver.run_until(|| push.try_send(vec![65])).expect("Push should have sent a message");
+    assert_eq!(vec![65], driver.run_until(|| pull.recv()).expect("Pull should have received a message"));
+
+    clock.advance(timeout);
+    driver.run_until_idle().unwrap();
+    driver.run_until(|| push.try_send(vec![66])).expect("Pipe should have been kept alive by the previous message");
+    assert_eq!(vec![66], driver.run_until(|| pull.recv()).expect("Pull should have received a message"));
+
+    clock.advance(timeout);
+    driver.run_until_idle().unwrap();
+    clock.advance(timeout);
+    driver.run_until_idle().unwrap();
+    let err = driver.run_until(|| push.try_send(vec![67])).unwrap_err();
+    assert_eq!(io::ErrorKind::WouldBlock, err.kind());
+
+    clock.advance(Duration::from_secs(1));
+    driver.run_until_idle().unwrap();
+    driver.run_until(|| push.try_send(vec![68])).expect("Push should have reconnected");
+    assert_eq!(vec![68], driver.run_until(|| pull.recv()).expect("Pull should have received a message"));
+}
+
+#[test]
+fn pipe_holding_unread_messages_is_kept_alive() {
+    let (mut session, mut driver, clock) = before_each();
+    let mut push = driver.run_until(|| session.create_socket::<Push>()).expect("Failed to create socket !");
+    let mut pull = driver.run_until(|| session.create_socket::<Pull>()).expect("Failed to create socket !");
+    let timeout = Duration::from_secs(10);
+
+    driver.run_until(|| pull.set_option(ConfigOption::LivenessTimeout(Some(timeout)))).unwrap();
+    driver.run_until(|| pull.bind("mem://unread")).unwrap();
+    driver.run_until(|| push.connect("mem://unread")).unwrap();
+    driver.run_until_idle().unwrap();
+
+    // the peer is alive and has sent a message, the application just did not read it yet
+    driver.run_until(|| push.try_send(vec![65])).expect("Push should have sent a message");
+    driver.run_until_idle().unwrap();
+    for _ in 0..3 {
+        clock.advance(timeout);
+        driver.run_until_idle().unwrap();
+    }
+
+    assert_eq!(vec![65], driver.run_until(|| pull.recv()).expect("Pull should have received the message"));
+}
+
+fn before_each_faulty(faulty: Faulty<Memory>) -> (Session, Driver, VirtualClock) {
+    let _ = ::env_logger::init();
+    let clock = VirtualClock::new();
+    let (session, driver) = SessionBuilder::new().
+        with("mem", faulty).
+        with_virtual_clock(clock.clone()).
+        build_manual().
+        expect("Failed to create session !");
+
+    (session, driver, clock)
+}
+
+#[test]
+fn pending_connect_is_retried_when_it_times_out() {
+    let faulty = Faulty::new(Memory::new(), 1).with_connect_stall(1f64);
+    let stats = faulty.stats();
+    let (mut session, mut driver, clock) = before_each_faulty(faulty);
+    let mut push = driver.run_until(|| session.create_socket::<Push>()).expect("Failed to create socket !");
+    let timeout = Duration::from_secs(5);
+
+    driver.run_until(|| push.set_option(ConfigOption::ConnectTimeout(Some(timeout)))).unwrap();
+    driver.run_until(|| push.connect("mem://nowhere")).unwrap();
+    driver.run_until_idle().unwrap();
+    assert_eq!(1, stats.connects());
+
+    clock.advance(timeout - Duration::from_millis(1));
+    driver.run_until_idle().unwrap();
+    assert_eq!(1, stats.connects());
+
+    clock.advance(Duration::from_millis(1));
+    driver.run_until_idle().unwrap();
+    assert_eq!(1, stats.connects(), "reconnect should wait for the retry interval");
+
+    clock.advance(Duration::from_secs(1));
+    driver.run_until_idle().unwrap();
+    assert_eq!(2, stats.connects());
+}
+
+#[test]
+fn pending_handshake_is_retried_when_it_times_out() {
+    let faulty = Faulty::new(Memory::new(), 1).with_handshake_stall(1f64);
+    let stats = faulty.stats();
+    let (mut session, mut driver, clock) = before_each_faulty(faulty);
+    let mut push = driver.run_until(|| session.create_socket::<Push>()).expect("Failed to create socket !");
+    let timeout = Duration::from_secs(5);
+
+    driver.run_until(|| push.set_option(ConfigOption::ConnectTimeout(Some(Duration::from_secs(1))))).unwrap();
+    driver.run_until(|| push.set_option(ConfigOption::HandshakeTimeout(Some(timeout)))).unwrap();
+    driver.run_until(|| push.connect("mem://nowhere")).unwrap();
+    driver.run_until_idle().unwrap();
+
+    clock.advance(Duration::from_secs(2));
+    driver.run_until_idle().unwrap();
+    assert_eq!(1, stats.connects(), "connect timeout should not apply once connected");
+
+    clock.advance(timeout);
+    driver.run_until_idle().unwrap();
+    clock.advance(Duration::from_secs(1));
+    driver.run_until_idle().unwrap();
+    assert_eq!(2, stats.connects());
+}
+
+// Counts the connection attempts of a push to a sub, leaving the option at its default if `retry` is `None`.
+fn connect_push_to_sub(retry: Option<bool>) -> usize {
+    let faulty = Faulty::new(Memory::new(), 1);
+    let stats = faulty.stats();
+    let (mut session, mut driver, clock) = before_each_faulty(faulty);
+    let mut push = driver.run_until(|| session.create_socket::<Push>()).expect("Failed to create socket !");
+    let mut sub = driver.run_until(|| session.create_socket::<Sub>()).expect("Failed to create socket !");
+
+    if let Some(retry) = retry {
+        driver.run_until(|| push.set_option(ConfigOption::RetryOnProtocolMismatch(retry))).unwrap();
+    }
+    driver.run_until(|| sub.bind("mem://mismatch")).unwrap();
+    driver.run_until(|| push.connect("mem://mismatch")).unwrap();
+    driver.run_until_idle().unwrap();
+
+    clock.advance(Duration::from_secs(1));
+    driver.run_until_idle().unwrap();
+
+    stats.connects()
+}
+
+#[test]
+fn protocol_mismatch_is_retried_by_default() {
+    assert!(connect_push_to_sub(None) > 1);
+}
+
+#[test]
+fn protocol_mismatch_can_stop_retrying() {
+    assert_eq!(1, connect_push_to_sub(Some(false)));
+}
+
+#[test]
+fn max_connections_pauses_accepting() {
+    let (mut session, mut driver, _) = before_each();
+    let mut pull = driver.run_until(|| session.create_socket::<Pull>()).expect("Failed to create socket !");
+    let mut push1 = driver.run_until(|| session.create_socket::<Push>()).expect("Failed to create socket !");
+    let mut push2 = driver.run_until(|| session.create_socket::<Push>()).expect("Failed to create socket !");
+
+    driver.run_until(|| pull.set_option(ConfigOption::MaxConnections(Some(1)))).unwrap();
+    driver.run_until(|| pull.bind("mem://limited")).unwrap();
+    driver.run_until(|| push1.connect("mem://limited")).unwrap();
+    driver.run_until_idle().unwrap();
+    driver.run_until(|| push2.connect("mem://limited")).unwrap();
+    driver.run_until_idle().unwrap();
+
+    driver.run_until(|| push1.try_send(vec![65])).expect("First push should have been accepted");
+    assert_eq!(vec![65], driver.run_until(|| pull.recv()).expect("Pull should have received a message"));
+    let err = driver.run_until(|| push2.try_send(vec![66])).unwrap_err();
+    assert_eq!(io::ErrorKind::WouldBlock, err.kind());
+
+    drop(push1);
+    driver.run_until_idle().unwrap();
+    driver.run_until(|| push2.try_send(vec![66])).expect("Second push should have been accepted once the first one left");
+    assert_eq!(vec![66], driver.run_until(|| pull.recv()).expect("Pull should have received a message"));
+}
+
+#[test]
+fn transient_accept_error_backs_off_without_rebinding() {
+    let faulty = Faulty::new(Memory::new(), 1).with_accept_failures(1);
+    let stats = faulty.stats();
+    let (mut session, mut driver, clock) = before_each_faulty(faulty);
+    let mut pull = driver.run_until(|| session.create_socket::<Pull>()).expect("Failed to create socket !");
+    let mut push = driver.run_until(|| session.create_socket::<Push>()).expect("Failed to create socket !");
+
+    driver.run_until(|| pull.bind("mem://exhausted")).unwrap();
+    driver.run_until(|| push.connect("mem://exhausted")).unwrap();
+    driver.run_until_idle().unwrap();
+    assert_eq!(1, stats.accept_failures());
+    let err = driver.run_until(|| push.try_send(vec![65])).unwrap_err();
+    assert_eq!(io::ErrorKind::WouldBlock, err.kind());
+
+    clock.advance(Duration::from_secs(1));
+    driver.run_until_idle().unwrap();
+    driver.run_until(|| push.try_send(vec![65])).expect("Accepting should have been resumed");
+    assert_eq!(vec![65], driver.run_until(|| pull.recv()).expect("Pull should have received a message"));
+    assert_eq!(1, stats.binds(), "listener should not have been rebound");
+}